@@ -48,6 +48,9 @@ fn create_user_element(doc: RefDocument, name: &str, value: &str) -> RefNode {
     let mut node = doc
         .create_element(name)
         .expect("could not create a new element");
-    node.append_child(doc.create_text_node(value));
+    node.append_child(
+        doc.create_text_node(value)
+            .expect("could not create a new text node"),
+    );
     node
 }
@@ -3,8 +3,11 @@ Implementation of `EntityResolver` that navigates the `Document` structure.
 */
 
 use crate::level2::convert::{as_document, as_document_type};
+use crate::level2::node_impl::Extension;
 use crate::level2::{Name, Node, NodeType, RefNode};
-use crate::shared::text::EntityResolver;
+use crate::shared::error::{Error, Result};
+use crate::shared::syntax::{XML_ENTITYREF_END, XML_ENTITYREF_START};
+use crate::shared::text::{EntityLimits, EntityResolver};
 use std::str::FromStr;
 
 // ------------------------------------------------------------------------------------------------
@@ -12,7 +15,7 @@ use std::str::FromStr;
 // ------------------------------------------------------------------------------------------------
 
 impl EntityResolver for RefNode {
-    fn resolve(&self, entity: &str) -> Option<String> {
+    fn resolve(&self, entity: &str) -> Result<Option<String>> {
         let doc_type = match self.node_type() {
             NodeType::DocumentType => Some(self.clone()),
             NodeType::Document => {
@@ -28,15 +31,39 @@ impl EntityResolver for RefNode {
             },
         };
         match doc_type {
-            None => None,
+            None => Ok(None),
             Some(doc_type) => {
                 let doc_type = as_document_type(&doc_type).unwrap();
-                let name = Name::from_str(entity).unwrap();
-                match doc_type.entities().get(&name) {
-                    None => None,
-                    Some(entity) => entity.node_value(),
-                }
+                //
+                // References are passed in their complete form, `&name;`.
+                //
+                let entity = entity.strip_prefix(XML_ENTITYREF_START).unwrap_or(entity);
+                let entity = entity.strip_suffix(XML_ENTITYREF_END).unwrap_or(entity);
+                let name = Name::from_str(entity).map_err(|_| {
+                    error!("invalid entity name '{}'", entity);
+                    Error::Syntax
+                })?;
+                Ok(doc_type
+                    .entities()
+                    .get(&name)
+                    .and_then(|entity| entity.node_value()))
             }
         }
     }
+
+    fn entity_limits(&self) -> EntityLimits {
+        let document = match self.node_type() {
+            NodeType::Document => Some(self.clone()),
+            _ => self.owner_document(),
+        };
+        match document {
+            Some(document) => match &document.borrow().i_extension {
+                Extension::Document {
+                    i_entity_limits, ..
+                } => *i_entity_limits,
+                _ => Default::default(),
+            },
+            None => Default::default(),
+        }
+    }
 }
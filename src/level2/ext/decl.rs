@@ -17,7 +17,7 @@ use std::str::FromStr;
 ///
 /// Captures the supported version of the XML specification itself, as used in `XmlDecl`.
 ///
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum XmlVersion {
     /// Version 1.0 [`<https://www.w3.org/TR/xml>`]
     V10,
@@ -170,7 +170,7 @@ impl XmlDecl {
     /// Return the `version` asserted in this declaration.
     ///
     pub fn version(&self) -> XmlVersion {
-        self.version.clone()
+        self.version
     }
     ///
     /// Return the `encoding` value in this declaration.
@@ -0,0 +1,113 @@
+/*!
+This module provides support types for the [`SourceLocated`](trait.SourceLocated.html) trait.
+*/
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+// ------------------------------------------------------------------------------------------------
+//  Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A location in parser input; lines and columns are counted from 1, columns count characters
+/// rather than bytes.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    /// The line number.
+    pub line: usize,
+    /// The column number within `line`.
+    pub column: usize,
+    /// The offset in bytes from the start of the input.
+    pub byte_offset: usize,
+}
+
+///
+/// The span of source text a node was parsed from; `start` is the position of the first character
+/// of the node and `end` the position immediately following its last character.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The position of the start of the node.
+    pub start: Position,
+    /// The position immediately after the end of the node.
+    pub end: Position,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "line {}, column {} (byte {})",
+            self.line, self.column, self.byte_offset
+        )
+    }
+}
+
+impl Position {
+    ///
+    /// The position at the start of any input.
+    ///
+    pub fn start() -> Self {
+        Self {
+            line: 1,
+            column: 1,
+            byte_offset: 0,
+        }
+    }
+
+    ///
+    /// Returns the position following `text`, which must be the UTF-8 input starting at this
+    /// position.
+    ///
+    pub(crate) fn advanced(mut self, text: &[u8]) -> Self {
+        for byte in text {
+            if *byte == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else if (*byte & 0xC0) != 0x80 {
+                //
+                // Only count the first byte of each UTF-8 encoded character.
+                //
+                self.column += 1;
+            }
+        }
+        self.byte_offset += text.len();
+        self
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} to {}", self.start, self.end)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advanced() {
+        let position = Position::start().advanced("<a>\n  é".as_bytes());
+        assert_eq!(
+            position,
+            Position {
+                line: 2,
+                column: 4,
+                byte_offset: 8
+            }
+        );
+        assert_eq!(position.to_string(), "line 2, column 4 (byte 8)");
+    }
+}
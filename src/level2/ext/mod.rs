@@ -13,9 +13,14 @@ pub use decl::{XmlDecl, XmlVersion};
 
 pub mod dom_impl;
 
+pub mod location;
+pub use location::{Position, SourceLocation};
+
 pub mod options;
 pub use options::ProcessingOptions;
 
+pub use crate::shared::text::SpaceHandling;
+
 pub mod namespaced;
 pub use namespaced::NamespacePrefix;
 
@@ -34,8 +34,6 @@ pub(crate) trait MutNamespaced: Namespaced {
         prefix: Option<&str>,
         namespace_uri: &str,
     ) -> Result<Option<String>>;
-    fn remove_mapping(&mut self, prefix: Option<&str>) -> Result<Option<String>>;
-    fn normalize_mappings(&mut self) -> Result<()>;
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -226,32 +224,6 @@ impl MutNamespaced for RefNode {
             Err(Error::InvalidState)
         }
     }
-
-    fn remove_mapping(&mut self, prefix: Option<&str>) -> Result<Option<String>> {
-        if !add_namespaces(self) {
-            return Ok(None);
-        }
-        let mut mut_self = self.borrow_mut();
-        if mut_self.i_node_type == NodeType::Element {
-            if let Extension::Element { i_namespaces, .. } = &mut mut_self.i_extension {
-                Ok(i_namespaces.remove(&prefix.map(String::from)))
-            } else {
-                warn!("{}", MSG_INVALID_EXTENSION);
-                Err(Error::InvalidState)
-            }
-        } else {
-            warn!("{}", MSG_INVALID_NODE_TYPE);
-            Err(Error::InvalidState)
-        }
-    }
-
-    fn normalize_mappings(&mut self) -> Result<()> {
-        // TODO: ensure this element has a mapping for it's own namespace and for any namespaced attributes
-        if !add_namespaces(self) {
-            return Ok(());
-        }
-        unimplemented!()
-    }
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -1,10 +1,19 @@
+use crate::level2::convert::{as_attribute, as_document, as_document_type, as_element, as_entity};
 use crate::level2::dom_impl::Implementation;
 use crate::level2::ext::decl::*;
+use crate::level2::ext::location::SourceLocation;
 use crate::level2::ext::options::ProcessingOptions;
 use crate::level2::ext::traits::*;
 use crate::level2::node_impl::*;
 use crate::level2::trait_impls::create_document_with_options;
+use crate::level2::traits::{Node, NodeType};
 use crate::shared::error::*;
+use crate::shared::syntax::{
+    XML_NS_ATTRIBUTE, XML_NS_ATTR_BASE, XML_NS_ATTR_SPACE, XML_NS_SEPARATOR,
+};
+use crate::shared::text::SpaceHandling;
+use crate::shared::uri::resolve_reference;
+use std::str::FromStr;
 
 // ------------------------------------------------------------------------------------------------
 // Implementations
@@ -37,6 +46,38 @@ impl DocumentDecl for RefNode {
             Err(Error::InvalidState)
         }
     }
+
+    fn document_uri(&self) -> Option<String> {
+        let ref_self = self.borrow();
+        if let Extension::Document { i_document_uri, .. } = &ref_self.i_extension {
+            i_document_uri.clone()
+        } else {
+            warn!("{}", MSG_INVALID_EXTENSION);
+            None
+        }
+    }
+
+    fn set_document_uri(&mut self, document_uri: &str) -> Result<()> {
+        let mut mut_self = self.borrow_mut();
+        if let Extension::Document { i_document_uri, .. } = &mut mut_self.i_extension {
+            *i_document_uri = Some(document_uri.to_string());
+            Ok(())
+        } else {
+            warn!("{}", MSG_INVALID_EXTENSION);
+            Err(Error::InvalidState)
+        }
+    }
+
+    fn unset_document_uri(&mut self) -> Result<()> {
+        let mut mut_self = self.borrow_mut();
+        if let Extension::Document { i_document_uri, .. } = &mut mut_self.i_extension {
+            *i_document_uri = None;
+            Ok(())
+        } else {
+            warn!("{}", MSG_INVALID_EXTENSION);
+            Err(Error::InvalidState)
+        }
+    }
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -52,3 +93,119 @@ impl DOMImplementation for Implementation {
         create_document_with_options(namespace_uri, qualified_name, doc_type, options)
     }
 }
+
+// ------------------------------------------------------------------------------------------------
+
+impl SourceLocated for RefNode {
+    fn source_location(&self) -> Option<SourceLocation> {
+        self.borrow().i_location.as_deref().cloned()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl SpaceHandled for RefNode {
+    fn space_handling(&self) -> SpaceHandling {
+        let mut current = match self.node_type() {
+            NodeType::Element => Some(self.clone()),
+            NodeType::Attribute => as_attribute(self).unwrap().owner_element(),
+            _ => self.parent_node(),
+        };
+        while let Some(node) = current {
+            if node.node_type() == NodeType::Element {
+                if let Some(space_handling) = element_space_handling(&node) {
+                    return space_handling;
+                }
+            }
+            current = node.parent_node();
+        }
+        SpaceHandling::Default
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl XmlBase for RefNode {
+    fn base_uri(&self) -> Option<String> {
+        match self.node_type() {
+            NodeType::Document => self.document_uri(),
+            NodeType::Attribute => match as_attribute(self).unwrap().owner_element() {
+                None => owner_document_uri(self),
+                Some(element) => element.base_uri(),
+            },
+            NodeType::Element => {
+                let parent_base_uri = parent_base_uri(self);
+                match as_element(self).unwrap().get_attribute(&format!(
+                    "{}{}{}",
+                    XML_NS_ATTRIBUTE, XML_NS_SEPARATOR, XML_NS_ATTR_BASE
+                )) {
+                    None => parent_base_uri,
+                    Some(base) => Some(match parent_base_uri {
+                        None => base,
+                        Some(parent_base_uri) => resolve_reference(&parent_base_uri, &base),
+                    }),
+                }
+            }
+            NodeType::EntityReference => {
+                let parent_base_uri = parent_base_uri(self);
+                match entity_system_id(self) {
+                    None => parent_base_uri,
+                    //
+                    // The system identifier is relative to the document containing the
+                    // declaration, which is the document itself.
+                    //
+                    Some(system_id) => Some(match owner_document_uri(self) {
+                        None => system_id,
+                        Some(document_uri) => resolve_reference(&document_uri, &system_id),
+                    }),
+                }
+            }
+            _ => parent_base_uri(self),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn parent_base_uri(node: &RefNode) -> Option<String> {
+    match node.parent_node() {
+        None => owner_document_uri(node),
+        Some(parent) => parent.base_uri(),
+    }
+}
+
+fn owner_document_uri(node: &RefNode) -> Option<String> {
+    node.owner_document()
+        .and_then(|document| document.document_uri())
+}
+
+///
+/// Returns the system identifier of the external entity referenced by `reference`, if declared.
+///
+fn entity_system_id(reference: &RefNode) -> Option<String> {
+    let document = reference.owner_document()?;
+    let doc_type = as_document(&document).unwrap().doc_type()?;
+    let entities = as_document_type(&doc_type).unwrap().entities();
+    let entity = entities.get(&reference.node_name())?;
+    as_entity(entity).unwrap().system_id()
+}
+
+///
+/// Returns the value of the `xml:space` attribute on this, and only this, element if it is one
+/// of the allowed values.
+///
+fn element_space_handling(element: &RefNode) -> Option<SpaceHandling> {
+    let value = as_element(element).unwrap().get_attribute(&format!(
+        "{}{}{}",
+        XML_NS_ATTRIBUTE, XML_NS_SEPARATOR, XML_NS_ATTR_SPACE
+    ))?;
+    match SpaceHandling::from_str(&value) {
+        Ok(space_handling) => Some(space_handling),
+        Err(_) => {
+            warn!("ignoring invalid value '{}' for xml:space", value);
+            None
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use crate::level2::ext::decl::XmlDecl;
+use crate::level2::ext::location::SourceLocation;
 use crate::level2::ext::namespaced::NamespacePrefix;
 use crate::level2::ext::options::ProcessingOptions;
 use crate::level2::traits as base;
 use crate::shared::error::Result;
+use crate::shared::text::SpaceHandling;
 
 // ------------------------------------------------------------------------------------------------
 // Public Traits
@@ -10,7 +12,7 @@ use crate::shared::error::Result;
 
 ///
 /// This interface extends the DOM standard `Document` and allows the setting, and retrieval,
-/// of the XML declaration from the document prolog.
+/// of the XML declaration from the document prolog, and of the URI of the document.
 ///
 /// # Specification
 ///
@@ -32,6 +34,20 @@ pub trait DocumentDecl: base::Document {
     /// Note that it is not possible to unset (set to `None`) this value.
     ///
     fn set_xml_declaration(&mut self, xml_decl: XmlDecl) -> Result<()>;
+    ///
+    /// Retrieve the URI of the document, if known. This is set by the parser from
+    /// [`ParserOptions::base_uri`](../../parser/struct.ParserOptions.html#method.base_uri), and
+    /// is the base URI of any node in the document not within an element with `xml:base`.
+    ///
+    fn document_uri(&self) -> Option<String>;
+    ///
+    /// Set the URI of the document.
+    ///
+    fn set_document_uri(&mut self, document_uri: &str) -> Result<()>;
+    ///
+    /// Remove the URI of the document.
+    ///
+    fn unset_document_uri(&mut self) -> Result<()>;
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -134,3 +150,100 @@ pub trait Namespaced: base::Element {
     ///
     fn resolve_prefix(&self, namespace_uri: &str) -> NamespacePrefix;
 }
+
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This interface extends the DOM standard `Node` to provide the location in the source text from
+/// which the node was parsed.
+///
+/// Locations are only recorded by the parser when requested using
+/// [`ParserOptions::track_locations`](../../parser/struct.ParserOptions.html#method.track_locations),
+/// nodes created in any other way, or copied using `clone_node`, have no location.
+///
+pub trait SourceLocated: base::Node {
+    ///
+    /// Returns the location in the source text of this node, if recorded.
+    ///
+    fn source_location(&self) -> Option<SourceLocation>;
+}
+
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This interface extends the DOM standard `Node` to report the whitespace handling in effect for
+/// the node, as specified by the `xml:space` attribute on the element itself or the closest
+/// ancestor element on which it is specified. For an attribute the owner element is used, for any
+/// other node its parent.
+///
+/// An `xml:space` attribute with a value other than `default` or `preserve` is ignored.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::as_document;
+/// use xml_dom::level2::ext::{SpaceHandled, SpaceHandling};
+/// use xml_dom::level2::{Document, Node};
+/// use xml_dom::parser::read_xml;
+///
+/// let dom = read_xml(r#"<doc><pre xml:space="preserve"><b>  </b></pre></doc>"#).unwrap();
+/// let root = as_document(&dom).unwrap().document_element().unwrap();
+/// assert_eq!(root.space_handling(), SpaceHandling::Default);
+/// let bold = root.first_child().unwrap().first_child().unwrap();
+/// assert_eq!(bold.space_handling(), SpaceHandling::Preserve);
+/// assert_eq!(
+///     bold.first_child().unwrap().node_value(),
+///     Some("  ".to_string())
+/// );
+/// ```
+///
+pub trait SpaceHandled: base::Node {
+    ///
+    /// Returns the whitespace handling in effect for this node.
+    ///
+    fn space_handling(&self) -> SpaceHandling;
+}
+
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This interface extends the DOM standard `Node` to report the base URI of the node, against
+/// which relative URI references in the node are resolved.
+///
+/// # Specification
+///
+/// From [XML Base](https://www.w3.org/TR/xmlbase/) §4.2 -- the base URI of an element is the
+/// base URI specified by an `xml:base` attribute on the element, resolved against the base URI
+/// of its parent if relative, and otherwise the base URI of its parent; the base URI of the
+/// document is its URI. Relative references are resolved as described in
+/// [RFC 3986](https://www.rfc-editor.org/rfc/rfc3986) §5.2.
+///
+/// The base URI of an attribute is that of its owner element, of a processing instruction,
+/// comment, or text that of its parent, and of the content of an external entity reference the
+/// system identifier of the entity. A node that is not in a tree has the document's URI.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::as_document;
+/// use xml_dom::level2::ext::XmlBase;
+/// use xml_dom::level2::{Document, Node};
+/// use xml_dom::parser::{read_xml_with_options, ParserOptions};
+///
+/// let dom = read_xml_with_options(
+///     r#"<doc xml:base="http://example.com/docs/"><a xml:base="../images/"><img/></a></doc>"#,
+///     ParserOptions::new().base_uri("file:///home/doc.xml"),
+/// )
+/// .unwrap();
+/// assert_eq!(dom.base_uri(), Some("file:///home/doc.xml".to_string()));
+/// let root = as_document(&dom).unwrap().document_element().unwrap();
+/// let image = root.first_child().unwrap().first_child().unwrap();
+/// assert_eq!(image.base_uri(), Some("http://example.com/images/".to_string()));
+/// ```
+///
+pub trait XmlBase: base::Node {
+    ///
+    /// Returns the base URI of this node, if known.
+    ///
+    fn base_uri(&self) -> Option<String>;
+}
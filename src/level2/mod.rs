@@ -16,7 +16,7 @@ Implementation for DOM Core Level 2.
 | `Element`               | [`Element`](level2/trait.Element.html)                             |
 | `Entity`                | [`Entity`](level2/trait.Entity.html)                               |
 | `EntityReference`       | [`EntityReference`](level2/trait.EntityReference.html)             |
-| `NamedNodeMap`          | `HashMap<Name, RefNode>`                                    |
+| `NamedNodeMap`          | [`NamedNodeMap`](level2/struct.NamedNodeMap.html)                  |
 | `Node`                  | [`Node`](level2/trait.Node.html)                                   |
 | `NodeList`              | `Vec<Rc<RefNode>>`                                          |
 | `Notation`              | [`Notation`](level2/trait.Notation.html)                           |
@@ -78,6 +78,9 @@ pub mod convert;
 pub mod dom_impl;
 pub use dom_impl::get_implementation;
 
+mod named_node_map;
+pub use named_node_map::{NamedNodeMap, NamedNodeMapIter};
+
 pub(crate) mod node_impl;
 pub use node_impl::RefNode;
 
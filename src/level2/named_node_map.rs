@@ -0,0 +1,254 @@
+/*!
+The collection of attributes of an element, which keeps the attributes in the order they were
+added.
+*/
+
+use crate::level2::Name;
+use std::iter::FromIterator;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Corresponds to the DOM `NamedNodeMap` interface, a collection of nodes accessed by name. This
+/// is used for the attributes of an element, see [`Node::attributes`](trait.Node.html#tymethod.attributes),
+/// and iterates over them in the order in which they were added; replacing the node for a name
+/// keeps its position.
+///
+/// # Specification
+///
+/// > Objects implementing the `NamedNodeMap` interface are used to represent collections of nodes
+/// > that can be accessed by name. Note that `NamedNodeMap` does not inherit from `NodeList`;
+/// > `NamedNodeMaps` are not maintained in any particular order.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::{as_document, as_element_mut};
+/// use xml_dom::level2::{get_implementation, Element, Node};
+///
+/// let document_node = get_implementation()
+///     .create_document(None, Some("config"), None)
+///     .unwrap();
+/// let mut root_node = as_document(&document_node).unwrap().document_element().unwrap();
+/// let root = as_element_mut(&mut root_node).unwrap();
+/// root.set_attribute("version", "2").unwrap();
+/// root.set_attribute("name", "main").unwrap();
+/// root.set_attribute("version", "3").unwrap();
+///
+/// let names: Vec<String> = root.attributes().keys().map(|name| name.to_string()).collect();
+/// assert_eq!(names, vec!["version", "name"]);
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamedNodeMap<N> {
+    entries: Vec<(Name, N)>,
+}
+
+///
+/// An iterator over the names and nodes in a `NamedNodeMap`, in order.
+///
+#[derive(Clone, Debug)]
+pub struct NamedNodeMapIter<'a, N> {
+    inner: std::slice::Iter<'a, (Name, N)>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<N> Default for NamedNodeMap<N> {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+        }
+    }
+}
+
+impl<N> NamedNodeMap<N> {
+    ///
+    /// Construct a new, empty, map.
+    ///
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    ///
+    /// The number of nodes in the map.
+    ///
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    ///
+    /// Returns `true` if the map contains no nodes.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///
+    /// Returns the node with the name `name`, if any.
+    ///
+    pub fn get(&self, name: &Name) -> Option<&N> {
+        self.entries
+            .iter()
+            .find(|(entry_name, _)| entry_name == name)
+            .map(|(_, node)| node)
+    }
+
+    ///
+    /// Returns `true` if the map contains a node with the name `name`.
+    ///
+    pub fn contains_key(&self, name: &Name) -> bool {
+        self.get(name).is_some()
+    }
+
+    ///
+    /// Returns the node at `index`, in the order in which nodes were added, if any.
+    ///
+    pub fn item(&self, index: usize) -> Option<&N> {
+        self.entries.get(index).map(|(_, node)| node)
+    }
+
+    ///
+    /// Add `node` with the name `name`, returning the node it replaces, if any. A replacement
+    /// takes the position of the node it replaces, otherwise the node is added at the end.
+    ///
+    pub fn insert(&mut self, name: Name, node: N) -> Option<N> {
+        match self
+            .entries
+            .iter_mut()
+            .find(|(entry_name, _)| *entry_name == name)
+        {
+            Some((_, existing)) => Some(std::mem::replace(existing, node)),
+            None => {
+                self.entries.push((name, node));
+                None
+            }
+        }
+    }
+
+    ///
+    /// Remove, and return, the node with the name `name`, if any; the order of the remaining
+    /// nodes is unchanged.
+    ///
+    pub fn remove(&mut self, name: &Name) -> Option<N> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(entry_name, _)| entry_name == name)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    ///
+    /// An iterator over the names and nodes in the map, in order.
+    ///
+    pub fn iter(&self) -> NamedNodeMapIter<'_, N> {
+        NamedNodeMapIter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    ///
+    /// An iterator over the names in the map, in order.
+    ///
+    pub fn keys(&self) -> impl Iterator<Item = &Name> {
+        self.entries.iter().map(|(name, _)| name)
+    }
+
+    ///
+    /// An iterator over the nodes in the map, in order.
+    ///
+    pub fn values(&self) -> impl Iterator<Item = &N> {
+        self.entries.iter().map(|(_, node)| node)
+    }
+}
+
+impl<N> FromIterator<(Name, N)> for NamedNodeMap<N> {
+    fn from_iter<T: IntoIterator<Item = (Name, N)>>(iter: T) -> Self {
+        let mut map = Self::new();
+        for (name, node) in iter {
+            let _safe_to_ignore = map.insert(name, node);
+        }
+        map
+    }
+}
+
+impl<N> IntoIterator for NamedNodeMap<N> {
+    type Item = (Name, N);
+    type IntoIter = std::vec::IntoIter<(Name, N)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a, N> IntoIterator for &'a NamedNodeMap<N> {
+    type Item = (&'a Name, &'a N);
+    type IntoIter = NamedNodeMapIter<'a, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<'a, N> Iterator for NamedNodeMapIter<'a, N> {
+    type Item = (&'a Name, &'a N);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(name, node)| (name, node))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<N> ExactSizeIterator for NamedNodeMapIter<'_, N> {}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map: NamedNodeMap<u32> = NamedNodeMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert(name("c"), 1), None);
+        assert_eq!(map.insert(name("a"), 2), None);
+        assert_eq!(map.insert(name("b"), 3), None);
+        assert_eq!(map.insert(name("a"), 4), Some(2));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.values().copied().collect::<Vec<u32>>(), vec![1, 4, 3]);
+        assert_eq!(map.item(1), Some(&4));
+        assert_eq!(map.get(&name("b")), Some(&3));
+
+        assert_eq!(map.remove(&name("c")), Some(1));
+        assert_eq!(map.remove(&name("c")), None);
+        assert!(!map.contains_key(&name("c")));
+        let names: Vec<String> = map.keys().map(|name| name.to_string()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        let map: NamedNodeMap<u32> = vec![(name("z"), 1), (name("y"), 2), (name("z"), 3)]
+            .into_iter()
+            .collect();
+        let entries: Vec<(String, u32)> = map
+            .into_iter()
+            .map(|(name, node)| (name.to_string(), node))
+            .collect();
+        assert_eq!(entries, vec![("z".to_string(), 3), ("y".to_string(), 2)]);
+    }
+}
@@ -1,9 +1,11 @@
 use crate::level2::ext::ProcessingOptions;
+use crate::level2::ext::SourceLocation;
 use crate::level2::ext::XmlDecl;
 use crate::level2::traits::{Node, NodeType};
-use crate::level2::{get_implementation, DOMImplementation};
+use crate::level2::{get_implementation, DOMImplementation, NamedNodeMap};
 use crate::shared::name::Name;
 use crate::shared::rc_cell::{RcRefCell, WeakRefCell};
+use crate::shared::text::EntityLimits;
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 
@@ -45,9 +47,11 @@ pub(crate) enum Extension {
     Document {
         i_implementation: &'static dyn DOMImplementation<NodeRef = RefNode>,
         i_xml_declaration: Option<XmlDecl>,
+        i_document_uri: Option<String>,
         i_document_type: Option<RefNode>,
         i_id_map: HashMap<String, WeakRefNode>,
         i_options: ProcessingOptions,
+        i_entity_limits: EntityLimits,
     },
     DocumentType {
         i_entities: HashMap<Name, RefNode>,
@@ -57,7 +61,7 @@ pub(crate) enum Extension {
         i_internal_subset: Option<String>,
     },
     Element {
-        i_attributes: HashMap<Name, RefNode>,
+        i_attributes: NamedNodeMap<RefNode>,
         i_namespaces: HashMap<Option<String>, String>,
     },
     Entity {
@@ -85,6 +89,7 @@ pub struct NodeImpl {
     pub(crate) i_parent_node: Option<WeakRefNode>,
     pub(crate) i_owner_document: Option<WeakRefNode>,
     pub(crate) i_child_nodes: Vec<RefNode>,
+    pub(crate) i_location: Option<Box<SourceLocation>>,
     pub(crate) i_extension: Extension,
 }
 
@@ -109,6 +114,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: Some(owner_document),
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::Element {
                 i_attributes: Default::default(),
                 i_namespaces: Default::default(),
@@ -132,6 +138,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: Some(owner_document),
             i_child_nodes: children,
+            i_location: None,
             i_extension: Extension::Attribute {
                 i_owner_element: None,
             },
@@ -145,6 +152,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: Some(owner_document),
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::None,
         }
     }
@@ -156,6 +164,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: Some(owner_document),
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::None,
         }
     }
@@ -171,6 +180,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: Some(owner_document),
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::None,
         }
     }
@@ -182,6 +192,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: Some(owner_document),
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::None,
         }
     }
@@ -193,12 +204,15 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: None,
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::Document {
                 i_implementation: get_implementation(),
                 i_xml_declaration: None,
+                i_document_uri: None,
                 i_document_type: doc_type,
                 i_id_map: Default::default(),
                 i_options: options,
+                i_entity_limits: Default::default(),
             },
         }
     }
@@ -210,6 +224,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: Some(owner_document),
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::None,
         }
     }
@@ -226,6 +241,7 @@ impl NodeImpl {
             i_parent_node: owner_document.clone(),
             i_owner_document: owner_document,
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::DocumentType {
                 i_entities: Default::default(),
                 i_notations: Default::default(),
@@ -243,6 +259,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: Some(owner_document),
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::None,
         }
     }
@@ -259,6 +276,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: owner_document,
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::Entity {
                 i_public_id: public_id.map(String::from),
                 i_system_id: system_id.map(String::from),
@@ -278,6 +296,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: owner_document,
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::Entity {
                 i_public_id: None,
                 i_system_id: None,
@@ -298,6 +317,7 @@ impl NodeImpl {
             i_parent_node: None,
             i_owner_document: owner_document,
             i_child_nodes: vec![],
+            i_location: None,
             i_extension: Extension::Notation {
                 i_public_id: public_id.map(String::from),
                 i_system_id: system_id.map(String::from),
@@ -314,15 +334,19 @@ impl NodeImpl {
             Extension::Document {
                 i_implementation,
                 i_xml_declaration,
+                i_document_uri,
                 i_document_type,
                 i_id_map,
                 i_options,
+                i_entity_limits,
             } => Extension::Document {
                 i_implementation: i_implementation.clone(),
                 i_xml_declaration: i_xml_declaration.clone(),
+                i_document_uri: i_document_uri.clone(),
                 i_document_type: i_document_type.clone(),
                 i_id_map: i_id_map.clone(),
                 i_options: i_options.clone(),
+                i_entity_limits: *i_entity_limits,
             },
             Extension::DocumentType {
                 i_entities,
@@ -361,6 +385,7 @@ impl NodeImpl {
             } else {
                 vec![]
             },
+            i_location: None,
             i_extension: extension,
         }
     }
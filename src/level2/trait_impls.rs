@@ -2,8 +2,10 @@ use crate::level2::convert::*;
 use crate::level2::dom_impl::{get_implementation, Implementation};
 use crate::level2::ext::convert::as_element_namespaced_mut;
 use crate::level2::ext::options::ProcessingOptions;
+use crate::level2::ext::{DocumentDecl, XmlVersion};
 use crate::level2::node_impl::*;
 use crate::level2::traits::*;
+use crate::level2::NamedNodeMap;
 use crate::shared::error::*;
 use crate::shared::name::Name;
 use crate::shared::syntax::*;
@@ -95,17 +97,23 @@ impl Attribute for RefNode {
                     }
                 }
             }
-            let normalized = text::normalize_attribute_value(&result, self, false);
-            Some(text::escape(normalized))
+            match text::normalize_attribute_value(&result, self, false) {
+                Ok(normalized) => Some(text::escape(normalized)),
+                Err(err) => {
+                    warn!("attribute value could not be normalized: {}", err);
+                    None
+                }
+            }
         } else {
             None
         }
     }
     fn set_value(&mut self, value: &str) -> Result<()> {
+        check_attribute_value(self, value)?;
         self.unset_value()?;
         let document_node = self.owner_document().unwrap();
         let document = as_document(&document_node).unwrap();
-        let _safe_to_ignore = self.append_child(document.create_text_node(value))?;
+        let _safe_to_ignore = self.append_child(document.create_text_node(value)?)?;
         Ok(())
     }
     fn unset_value(&mut self) -> Result<()> {
@@ -168,11 +176,15 @@ impl CharacterData for RefNode {
         if new_data.is_empty() {
             return Ok(());
         }
+        check_characters(self, new_data)?;
+        let node_type = self.node_type();
         let mut mut_self = self.borrow_mut();
-        match &mut_self.i_value {
-            None => mut_self.i_value = Some(new_data.to_string()),
-            Some(old_data) => mut_self.i_value = Some(format!("{}{}", old_data, new_data)),
-        }
+        let new_data = match &mut_self.i_value {
+            None => new_data.to_string(),
+            Some(old_data) => format!("{}{}", old_data, new_data),
+        };
+        check_markup(node_type, &new_data)?;
+        mut_self.i_value = Some(new_data);
         Ok(())
     }
 
@@ -192,6 +204,8 @@ impl CharacterData for RefNode {
     }
 
     fn replace_data(&mut self, offset: usize, count: usize, replace_data: &str) -> Result<()> {
+        check_characters(self, replace_data)?;
+        let node_type = self.node_type();
         let mut mut_self = self.borrow_mut();
         match &mut_self.i_value {
             None => {
@@ -199,6 +213,7 @@ impl CharacterData for RefNode {
                     warn!("{}", MSG_INDEX_ERROR);
                     Err(Error::IndexSize)
                 } else {
+                    check_markup(node_type, replace_data)?;
                     mut_self.i_value = Some(replace_data.to_string());
                     Ok(())
                 }
@@ -214,6 +229,7 @@ impl CharacterData for RefNode {
                     } else {
                         new_data.replace_range(offset..offset + count, replace_data);
                     }
+                    check_markup(node_type, &new_data)?;
                     mut_self.i_value = Some(new_data);
                     Ok(())
                 }
@@ -257,6 +273,7 @@ impl Document for RefNode {
 
     fn create_attribute_with(&self, name: &str, value: &str) -> Result<RefNode> {
         let name = Name::from_str(name)?;
+        check_attribute_value(self, value)?;
         let node_impl = NodeImpl::new_attribute(self.clone().downgrade(), name, Some(value));
         Ok(RefNode::new(node_impl))
     }
@@ -268,6 +285,7 @@ impl Document for RefNode {
     }
 
     fn create_cdata_section(&self, data: &str) -> Result<RefNode> {
+        check_characters(self, data)?;
         let node_impl = NodeImpl::new_cdata(self.clone().downgrade(), data);
         Ok(RefNode::new(node_impl))
     }
@@ -283,9 +301,11 @@ impl Document for RefNode {
         Ok(RefNode::new(node_impl))
     }
 
-    fn create_comment(&self, data: &str) -> RefNode {
+    fn create_comment(&self, data: &str) -> Result<RefNode> {
+        check_characters(self, data)?;
+        check_markup(NodeType::Comment, data)?;
         let node_impl = NodeImpl::new_comment(self.clone().downgrade(), data);
-        RefNode::new(node_impl)
+        Ok(RefNode::new(node_impl))
     }
 
     fn create_element(&self, tag_name: &str) -> Result<RefNode> {
@@ -310,14 +330,19 @@ impl Document for RefNode {
             return Err(Error::Syntax);
         }
         let target = Name::from_str(target)?;
+        if let Some(data) = data {
+            check_characters(self, data)?;
+            check_markup(NodeType::ProcessingInstruction, data)?;
+        }
         let node_impl =
             NodeImpl::new_processing_instruction(self.clone().downgrade(), target, data);
         Ok(RefNode::new(node_impl))
     }
 
-    fn create_text_node(&self, data: &str) -> RefNode {
+    fn create_text_node(&self, data: &str) -> Result<RefNode> {
+        check_characters(self, data)?;
         let node_impl = NodeImpl::new_text(self.clone().downgrade(), data);
-        RefNode::new(node_impl)
+        Ok(RefNode::new(node_impl))
     }
 
     fn get_element_by_id(&self, id: &str) -> Option<RefNode> {
@@ -441,6 +466,7 @@ impl Element for RefNode {
 
     fn set_attribute(&mut self, name: &str, value: &str) -> Result<()> {
         let attr_name = Name::from_str(name)?;
+        check_attribute_value(self, value)?;
         let attr_node = {
             let ref_self = &self.borrow_mut();
             let document = ref_self.i_owner_document.as_ref().unwrap();
@@ -511,9 +537,14 @@ impl Element for RefNode {
                 let namespace_uri = attribute.value().unwrap();
 
                 let as_namespaced = as_element_namespaced_mut(self).unwrap();
+                //
+                // For `xmlns:p` the declared prefix is the local name, `xmlns` declares the default.
+                //
                 let _ignore = match &name.prefix() {
                     None => as_namespaced.insert_mapping(None, &namespace_uri),
-                    Some(prefix) => as_namespaced.insert_mapping(Some(prefix), &namespace_uri),
+                    Some(_) => {
+                        as_namespaced.insert_mapping(Some(name.local_name()), &namespace_uri)
+                    }
                 }?;
             }
 
@@ -527,21 +558,23 @@ impl Element for RefNode {
                     //
                     let attribute = as_attribute(&new_attribute).unwrap();
                     let document = attribute.owner_document().unwrap();
-                    let mut mut_document = document.borrow_mut();
-                    let lax =
-                        if let Extension::Document { i_options, .. } = &mut_document.i_extension {
-                            i_options.has_assume_ids()
-                        } else {
-                            warn!("{}", MSG_INVALID_EXTENSION);
-                            false
-                        };
+                    let lax = if let Extension::Document { i_options, .. } =
+                        &document.borrow().i_extension
+                    {
+                        i_options.has_assume_ids()
+                    } else {
+                        warn!("{}", MSG_INVALID_EXTENSION);
+                        false
+                    };
                     if name.is_id_attribute(lax) {
                         //
-                        // Update the document ID mapping
+                        // Update the document ID mapping; the value is retrieved first as
+                        // references in it are resolved using the document.
                         //
+                        let id_value = attribute.value().unwrap();
+                        let mut mut_document = document.borrow_mut();
                         if let Extension::Document { i_id_map, .. } = &mut mut_document.i_extension
                         {
-                            let id_value = attribute.value().unwrap();
                             if i_id_map.contains_key(&id_value) {
                                 warn!("{}", MSG_DUPLICATE_ID);
                                 return Err(Error::Syntax);
@@ -625,6 +658,7 @@ impl Element for RefNode {
         value: &str,
     ) -> Result<()> {
         let attr_name = Name::new_ns(namespace_uri, qualified_name)?;
+        check_attribute_value(self, value)?;
         let attr_node = {
             let ref_self = &self.borrow_mut();
             let document = ref_self.i_owner_document.as_ref().unwrap();
@@ -793,6 +827,13 @@ impl Node for RefNode {
     }
 
     fn set_node_value(&mut self, value: &str) -> Result<()> {
+        if matches!(
+            self.node_type(),
+            NodeType::Text | NodeType::CData | NodeType::Comment | NodeType::ProcessingInstruction
+        ) {
+            check_characters(self, value)?;
+            check_markup(self.node_type(), value)?;
+        }
         let mut mut_self = self.borrow_mut();
         mut_self.i_value = Some(value.to_string());
         Ok(())
@@ -897,12 +938,12 @@ impl Node for RefNode {
         }
     }
 
-    fn attributes(&self) -> HashMap<Name, RefNode, RandomState> {
+    fn attributes(&self) -> NamedNodeMap<RefNode> {
         if is_element(self) {
             unwrap_extension_field!(self, Element, i_attributes)
         } else {
             warn!("{}", MSG_INVALID_NODE_TYPE);
-            HashMap::default()
+            NamedNodeMap::default()
         }
     }
 
@@ -933,6 +974,25 @@ impl Node for RefNode {
             return Err(Error::HierarchyRequest);
         }
 
+        //
+        // The children of a fragment are inserted, in order, in place of the fragment itself;
+        // this leaves the fragment empty.
+        //
+        if is_document_fragment(&new_child) {
+            if new_child
+                .child_nodes()
+                .iter()
+                .any(|child| !is_child_allowed(self, child))
+            {
+                warn!("The child you tried to add is not valid for this parent.");
+                return Err(Error::HierarchyRequest);
+            }
+            for child in new_child.child_nodes() {
+                let _safe_to_ignore = self.insert_before(child, ref_child.clone())?;
+            }
+            return Ok(new_child);
+        }
+
         //
         // Special case for Document only.
         //
@@ -994,19 +1054,7 @@ impl Node for RefNode {
             }
         }
 
-        //
-        // Special case
-        //
-        if is_document_fragment(&new_child) {
-            for (index, child) in new_child.child_nodes().iter().enumerate() {
-                match insert_position {
-                    None => insert_or_append(self, child, None),
-                    Some(position) => insert_or_append(self, child, Some(position + index)),
-                }
-            }
-        } else {
-            insert_or_append(self, &new_child, insert_position)
-        }
+        insert_or_append(self, &new_child, insert_position);
 
         Ok(new_child)
     }
@@ -1205,6 +1253,59 @@ fn namespaced_name_match(
     }
 }
 
+//
+// CHECK: Raise `Error::NotFound` if `value` references an entity that is not declared, or
+// `Error::Syntax` if it references an entity recursively; references are only replaced when the
+// value of the attribute is retrieved.
+//
+fn check_attribute_value(node: &RefNode, value: &str) -> Result<()> {
+    check_characters(node, value)?;
+    text::normalize_attribute_value(value, node, true).map(|_| ())
+}
+
+//
+// CHECK: Raise `Error::InvalidCharacter` if `data` contains a character that does not match the
+// `Char` production of the version of XML declared by the document owning `node`; XML 1.0 is
+// assumed if there is no XML declaration.
+//
+fn check_characters(node: &RefNode, data: &str) -> Result<()> {
+    let document = if node.node_type() == NodeType::Document {
+        Some(node.clone())
+    } else {
+        node.owner_document()
+    };
+    let version = document
+        .and_then(|document| document.xml_declaration())
+        .map(|xml_decl| xml_decl.version())
+        .unwrap_or(XmlVersion::V10);
+    if text::is_xml_chars(data, version) {
+        Ok(())
+    } else {
+        warn!("data contains a character not allowed in XML {}", version);
+        Err(Error::InvalidCharacter)
+    }
+}
+
+///
+/// A comment may not contain `--` or end with `-`, and the data of a processing instruction may not
+/// contain `?>`, as either would end the markup early when written.
+///
+fn check_markup(node_type: NodeType, data: &str) -> Result<()> {
+    let is_valid = match node_type {
+        NodeType::Comment => {
+            !data.contains(XML_COMMENT_DOUBLE_HYPHEN) && !data.ends_with(XML_COMMENT_HYPHEN)
+        }
+        NodeType::ProcessingInstruction => !data.contains(XML_PI_END),
+        _ => true,
+    };
+    if is_valid {
+        Ok(())
+    } else {
+        warn!("data cannot be written in a {:?} node", node_type);
+        Err(Error::Syntax)
+    }
+}
+
 //
 // CHECK: Raise `Error::WrongDocument` if `newChild` was created from a different
 // document than the one that created this node.
@@ -1264,6 +1365,9 @@ fn check_same_document(self_node: &RefNode, new_child: &RefNode) -> Result<()> {
 // * Entity -- Element, ProcessingInstruction, Comment, Text, CDATASection, EntityReference
 // * Notation -- no children
 //
+// A DocumentFragment may be inserted into any node that may have children of the types above
+// other than Attr, its own children are then checked as they are inserted.
+//
 fn is_child_allowed(parent: &RefNode, child: &RefNode) -> bool {
     let self_node_type = { &parent.borrow().i_node_type };
     let child_node_type = { &child.borrow().i_node_type };
@@ -1276,6 +1380,7 @@ fn is_child_allowed(parent: &RefNode, child: &RefNode) -> bool {
                 | NodeType::ProcessingInstruction
                 | NodeType::CData
                 | NodeType::EntityReference
+                | NodeType::DocumentFragment
         ),
         NodeType::Attribute => {
             matches!(child_node_type, NodeType::Text | NodeType::EntityReference)
@@ -1290,6 +1395,7 @@ fn is_child_allowed(parent: &RefNode, child: &RefNode) -> bool {
                 | NodeType::ProcessingInstruction
                 | NodeType::CData
                 | NodeType::EntityReference
+                | NodeType::DocumentFragment
         ),
         NodeType::Entity => matches!(
             child_node_type,
@@ -1299,12 +1405,16 @@ fn is_child_allowed(parent: &RefNode, child: &RefNode) -> bool {
                 | NodeType::ProcessingInstruction
                 | NodeType::CData
                 | NodeType::EntityReference
+                | NodeType::DocumentFragment
         ),
         NodeType::ProcessingInstruction => false,
         NodeType::Comment => false,
         NodeType::Document => matches!(
             child_node_type,
-            NodeType::Element | NodeType::Comment | NodeType::ProcessingInstruction
+            NodeType::Element
+                | NodeType::Comment
+                | NodeType::ProcessingInstruction
+                | NodeType::DocumentFragment
         ),
         NodeType::DocumentType => false,
         NodeType::DocumentFragment => matches!(
@@ -1315,6 +1425,7 @@ fn is_child_allowed(parent: &RefNode, child: &RefNode) -> bool {
                 | NodeType::ProcessingInstruction
                 | NodeType::CData
                 | NodeType::EntityReference
+                | NodeType::DocumentFragment
         ),
         NodeType::Notation => false,
     }
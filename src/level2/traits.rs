@@ -1,3 +1,4 @@
+use crate::level2::NamedNodeMap;
 use crate::shared::error::Result;
 use crate::shared::name::Name;
 use crate::shared::text;
@@ -263,7 +264,8 @@ pub trait CharacterData: Node {
     ///   replaced; (i.e., the effect is the same as a remove method call with the same range,
     ///   followed by an append method invocation).
     /// * `arg` of type `DOMString`: The `DOMString` with which the range must be replaced.
-    /// Exceptions
+    ///
+    /// **Exceptions**
     ///
     /// INDEX_SIZE_ERR: Raised if the specified `offset` is negative or greater than the number
     ///   of 16-bit units in data, or if the specified `count` is negative.
@@ -350,7 +352,8 @@ pub trait Document: Node {
     fn create_attribute(&self, name: &str) -> Result<Self::NodeRef>;
     ///
     /// Implementation defined extension: this is the same as `create_attribute` except that it
-    /// also sets the attribute value.
+    /// also sets the attribute value. References to entities in `value` must be to entities
+    /// declared in the document type, `Error::NotFound` is returned otherwise.
     ///
     fn create_attribute_with(&self, name: &str, value: &str) -> Result<Self::NodeRef>;
     ///
@@ -408,6 +411,8 @@ pub trait Document: Node {
     ///
     /// **Exceptions**
     ///
+    /// * `INVALID_CHARACTER_ERR`: Raised if the specified data contains a character that is not
+    ///   allowed in the version of XML of this document.
     /// * `NOT_SUPPORTED_ERR`: Raised if this document is an HTML document.
     ///
     fn create_cdata_section(&self, data: &str) -> Result<Self::NodeRef>;
@@ -462,7 +467,14 @@ pub trait Document: Node {
     ///
     /// * `Comment`: The new `Comment` object.
     ///
-    fn create_comment(&self, data: &str) -> Self::NodeRef;
+    /// **Exceptions**
+    ///
+    /// * `INVALID_CHARACTER_ERR`: Raised if the specified data contains a character that is not
+    ///   allowed in the version of XML of this document.
+    /// * `SYNTAX_ERR`: Raised if the specified data contains `--` or ends with `-`, as the comment
+    ///   could not be written (an extension to the specification).
+    ///
+    fn create_comment(&self, data: &str) -> Result<Self::NodeRef>;
     ///
     /// Creates an element of the type specified.
     ///
@@ -546,8 +558,12 @@ pub trait Document: Node {
     ///
     /// **Exceptions**
     ///
-    /// * `INVALID_CHARACTER_ERR`: Raised if the specified target contains an illegal character.
+    /// * `INVALID_CHARACTER_ERR`: Raised if the specified target contains an illegal character,
+    ///   or the data contains a character that is not allowed in the version of XML of this
+    ///   document.
     /// * `NOT_SUPPORTED_ERR`: Raised if this document is an HTML document.
+    /// * `SYNTAX_ERR`: Raised if the specified data contains `?>`, as the processing instruction
+    ///   could not be written (an extension to the specification).
     ///
     fn create_processing_instruction(
         &self,
@@ -567,7 +583,12 @@ pub trait Document: Node {
     ///
     /// * `Text`: The new Text object.
     ///
-    fn create_text_node(&self, data: &str) -> Self::NodeRef;
+    /// **Exceptions**
+    ///
+    /// * `INVALID_CHARACTER_ERR`: Raised if the specified data contains a character that is not
+    ///   allowed in the version of XML of this document.
+    ///
+    fn create_text_node(&self, data: &str) -> Result<Self::NodeRef>;
     ///
     /// Returns the [`Element`](trait.Element.html) whose ID is given by `elementId`.
     ///
@@ -910,7 +931,7 @@ pub trait Element: Node {
     /// **Return Value**
     ///
     /// * `DOMString`: The `Attr` value as a string, or the empty string if that attribute does not
-    /// have a specified or default value.
+    ///   have a specified or default value.
     ///
     fn get_attribute(&self, name: &str) -> Option<String>;
     ///
@@ -931,7 +952,7 @@ pub trait Element: Node {
     /// **Parameters**
     ///
     /// * `name` of type `DOMString`: The name of the attribute to create or alter.
-    /// `value` of type `DOMString`: Value to set in string form.
+    /// * `value` of type `DOMString`: Value to set in string form.
     ///
     /// **Exceptions**
     ///
@@ -972,7 +993,7 @@ pub trait Element: Node {
     /// **Return Value**
     ///
     /// * `Attr`: The `Attr` node with the specified name (`nodeName`) or null if there is no such
-    /// attribute.
+    ///   attribute.
     ///
     fn get_attribute_node(&self, name: &str) -> Option<Self::NodeRef>;
     ///
@@ -1092,7 +1113,7 @@ pub trait Element: Node {
     ///   prefix and the `namespaceURI` is null, if the `qualifiedName` has a prefix that is "xml"
     ///   and the `namespaceURI` is different from '<http://www.w3.org/XML/1998/namespace>', or if
     ///   the `qualifiedName` is "xmlns" and the `namespaceURI` is different from
-    /// `<http://www.w3.org/2000/xmlns/>`.
+    ///   `<http://www.w3.org/2000/xmlns/>`.
     ///
     fn set_attribute_ns(
         &mut self,
@@ -1221,7 +1242,7 @@ pub trait Element: Node {
     /// **Parameters**
     ///
     /// * `namespaceURI` of type `DOMString`: The namespace URI of the attribute to look for.
-    /// `localName` of type `DOMString`: The local name of the attribute to look for.
+    /// * `localName` of type `DOMString`: The local name of the attribute to look for.
     ///
     /// **Return Value**
     ///
@@ -1316,6 +1337,11 @@ pub trait Entity: Node {
 ///
 /// As for `Entity` nodes, `EntityReference` nodes and all their descendants are readonly.
 ///
+/// **Note:** This implementation does not enforce readonly nodes; modifying an `Entity`, an
+/// `EntityReference`, or any of their descendants succeeds rather than failing with
+/// `NO_MODIFICATION_ALLOWED_ERR`. The parser builds these subtrees with the same methods, so
+/// changes made to them are the caller's responsibility.
+///
 pub trait EntityReference: Node {}
 
 // ------------------------------------------------------------------------------------------------
@@ -1377,7 +1403,7 @@ pub trait Node {
     /// **Exceptions on retrieval**
     ///
     /// * `DOMSTRING_SIZE_ERR`: Raised when it would return more characters than fit in a DOMString
-    /// variable on the implementation platform.
+    ///   variable on the implementation platform.
     ///
     fn node_value(&self) -> Option<String>;
     ///
@@ -1420,10 +1446,10 @@ pub trait Node {
     ///
     fn next_sibling(&self) -> Option<Self::NodeRef>;
     ///
-    /// A `HashMap` containing the attributes of this node (if it is an `Element`) or
-    /// `None` otherwise.
+    /// A `NamedNodeMap` containing the attributes of this node, in the order in which they were
+    /// added, if it is an `Element`; or an empty map otherwise.
     ///
-    fn attributes(&self) -> HashMap<Name, Self::NodeRef>;
+    fn attributes(&self) -> NamedNodeMap<Self::NodeRef>;
     ///
     /// The `Document` object associated with this node. This is also the `Document`
     /// object used to create new nodes. When this node is a `Document` or a `DocumentType` which is
@@ -49,6 +49,10 @@ let xml = document_node.to_string();
 println!("document 2: {}", xml);
 ```
 
+Nodes may also be written, without first building the text in memory, to any `std::io::Write`
+using the [`writer`](writer/index.html) module, which provides options for the XML declaration,
+character encoding, and other details of the output.
+
 # Specifications
 
 * [Document Object Model (DOM) Level 1 Specification](https://www.w3.org/TR/REC-DOM-Level-1/),
@@ -64,6 +68,8 @@ println!("document 2: {}", xml);
   Especially §7.1 _Conformance to xml:id_.
 * [XML Base (Second Edition)](https://www.w3.org/TR/xmlbase/), W3C Recommendation 28 January 2009.
 * [The "xml" Namespace](https://www.w3.org/XML/1998/namespace), W3C 26 October 2009.
+* [XML Inclusions (XInclude) Version 1.0 (Second Edition)](https://www.w3.org/TR/xinclude/), W3C
+  Recommendation 15 November 2006.
 
 ## Levels supported.
 
@@ -153,6 +159,8 @@ pub mod parser;
 
 pub mod level2;
 
+pub mod writer;
+
 // ------------------------------------------------------------------------------------------------
 // Private Modules
 // ------------------------------------------------------------------------------------------------
@@ -0,0 +1,369 @@
+/*!
+Construction of a DOM from the events reported by the parser; this is the
+[`ContentHandler`](../handler/trait.ContentHandler.html) used by
+[`read_xml`](../fn.read_xml.html) and the other functions that return a document.
+*/
+
+use crate::level2::convert::{as_attribute_mut, as_character_data_mut, as_document};
+use crate::level2::ext::dom_impl::{
+    create_entity, create_internal_entity, create_notation, get_implementation_ext,
+};
+use crate::level2::ext::{DOMImplementation, DocumentDecl, SourceLocation, XmlDecl};
+use crate::level2::node_impl::Extension;
+use crate::level2::*;
+use crate::parser::dtd::{DocTypeDecl, EntityDecl, NotationDecl};
+use crate::parser::handler::{ContentHandler, ParsedAttribute};
+use crate::parser::{ParserOptions, Result};
+use crate::shared::text::EntityLimits;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Builds a document from parser events. Content is appended to the most recently opened element
+/// or entity reference, or to the document itself if there is none.
+///
+pub(crate) struct DocumentBuilder {
+    implementation: &'static dyn DOMImplementation<NodeRef = RefNode>,
+    document: RefNode,
+    open_nodes: Vec<RefNode>,
+    /// For each open entity, whether an `EntityReference` node was added to `open_nodes`.
+    open_entities: Vec<bool>,
+    /// Set when the replacement text of an entity is expanded in place, so that text is merged
+    /// with the text that precedes it.
+    merge_text: bool,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Debug for DocumentBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("DocumentBuilder")
+            .field("document", &self.document)
+            .field("open_nodes", &self.open_nodes)
+            .field("open_entities", &self.open_entities)
+            .field("merge_text", &self.merge_text)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DocumentBuilder {
+    ///
+    /// Create an empty document using the implementation and processing options in `options`.
+    ///
+    pub(crate) fn new(options: &ParserOptions) -> Result<Self> {
+        let mut document = options.implementation.create_document_with_options(
+            None,
+            None,
+            None,
+            options.processing_options.clone(),
+        )?;
+        if let Some(base_uri) = &options.base_uri {
+            document.set_document_uri(base_uri)?;
+        }
+        if let Extension::Document {
+            i_entity_limits, ..
+        } = &mut document.borrow_mut().i_extension
+        {
+            //
+            // References in attribute values set later are expanded within the same limits.
+            //
+            *i_entity_limits = EntityLimits {
+                max_depth: options.max_entity_depth,
+                max_expansion: options.max_entity_expansion,
+            };
+        }
+        Ok(Self {
+            implementation: options.implementation,
+            document,
+            open_nodes: Default::default(),
+            open_entities: Default::default(),
+            merge_text: false,
+        })
+    }
+
+    ///
+    /// Create a builder that appends content to `fragment`, creating nodes using the existing
+    /// `document`.
+    ///
+    pub(crate) fn for_fragment(document: &RefNode, fragment: RefNode) -> Self {
+        Self {
+            implementation: get_implementation_ext(),
+            document: document.clone(),
+            open_nodes: vec![fragment],
+            open_entities: Default::default(),
+            merge_text: false,
+        }
+    }
+
+    ///
+    /// Returns the document built so far.
+    ///
+    pub(crate) fn into_document(self) -> RefNode {
+        self.document
+    }
+
+    fn parent(&self) -> RefNode {
+        self.open_nodes.last().unwrap_or(&self.document).clone()
+    }
+
+    fn append(&mut self, new_node: RefNode, location: Option<SourceLocation>) -> Result<RefNode> {
+        new_node.borrow_mut().i_location = location.map(Box::new);
+        self.merge_text = false;
+        Ok(self.parent().append_child(new_node)?)
+    }
+
+    fn is_parse_entities(&self) -> bool {
+        if let Extension::Document { i_options, .. } = &self.document.borrow().i_extension {
+            i_options.has_parse_entities()
+        } else {
+            false
+        }
+    }
+
+    fn create_entities(
+        &self,
+        doc_type: &RefNode,
+        entities: &[EntityDecl],
+    ) -> Result<HashMap<Name, RefNode>> {
+        let mut entity_nodes: HashMap<Name, RefNode> = Default::default();
+        for decl in entities {
+            let name = Name::from_str(&decl.name)?;
+            let entity = match &decl.value {
+                Some(value) => create_internal_entity(self.document.clone(), &decl.name, value)?,
+                None => {
+                    let entity = create_entity(
+                        self.document.clone(),
+                        &decl.name,
+                        decl.public_id.as_deref(),
+                        decl.system_id.as_deref(),
+                    )?;
+                    if let Extension::Entity {
+                        i_notation_name, ..
+                    } = &mut entity.borrow_mut().i_extension
+                    {
+                        *i_notation_name = decl.notation_name.clone();
+                    }
+                    entity
+                }
+            };
+            entity.borrow_mut().i_parent_node = Some(doc_type.clone().downgrade());
+            let _safe_to_ignore = entity_nodes.insert(name, entity);
+        }
+        Ok(entity_nodes)
+    }
+
+    fn create_notations(
+        &self,
+        doc_type: &RefNode,
+        notations: &[NotationDecl],
+    ) -> Result<HashMap<Name, RefNode>> {
+        let mut notation_nodes: HashMap<Name, RefNode> = Default::default();
+        for decl in notations {
+            let name = Name::from_str(&decl.name)?;
+            let notation = create_notation(
+                self.document.clone(),
+                &decl.name,
+                decl.public_id.as_deref(),
+                decl.system_id.as_deref(),
+            )?;
+            notation.borrow_mut().i_parent_node = Some(doc_type.clone().downgrade());
+            let _safe_to_ignore = notation_nodes.insert(name, notation);
+        }
+        Ok(notation_nodes)
+    }
+}
+
+impl ContentHandler for DocumentBuilder {
+    fn xml_declaration(&mut self, declaration: &XmlDecl) -> Result<()> {
+        if let Extension::Document {
+            i_xml_declaration, ..
+        } = &mut self.document.borrow_mut().i_extension
+        {
+            *i_xml_declaration = Some(declaration.clone());
+        }
+        Ok(())
+    }
+
+    fn document_type(
+        &mut self,
+        declaration: &DocTypeDecl,
+        entities: &[EntityDecl],
+        notations: &[NotationDecl],
+    ) -> Result<()> {
+        let doc_type = self.implementation.create_document_type(
+            &declaration.name,
+            declaration.public_id.as_deref(),
+            declaration.system_id.as_deref(),
+        )?;
+        let entity_nodes = self.create_entities(&doc_type, entities)?;
+        let notation_nodes = self.create_notations(&doc_type, notations)?;
+        {
+            let mut mut_doc_type = doc_type.borrow_mut();
+            mut_doc_type.i_parent_node = Some(self.document.clone().downgrade());
+            mut_doc_type.i_owner_document = Some(self.document.clone().downgrade());
+            if let Extension::DocumentType {
+                i_internal_subset,
+                i_entities,
+                i_notations,
+                ..
+            } = &mut mut_doc_type.i_extension
+            {
+                *i_internal_subset = declaration.internal_subset.clone();
+                *i_entities = entity_nodes;
+                *i_notations = notation_nodes;
+            }
+        }
+
+        if let Extension::Document {
+            i_document_type, ..
+        } = &mut self.document.borrow_mut().i_extension
+        {
+            *i_document_type = Some(doc_type);
+        }
+        Ok(())
+    }
+
+    fn start_element(
+        &mut self,
+        name: &Name,
+        attributes: &[ParsedAttribute],
+        location: Option<SourceLocation>,
+    ) -> Result<()> {
+        let document_node = self.document.clone();
+        let document = as_document(&document_node).unwrap();
+        let new_node = match name.namespace_uri() {
+            None => document.create_element(&name.to_string())?,
+            Some(namespace_uri) => document.create_element_ns(namespace_uri, &name.to_string())?,
+        };
+        let mut element = self.append(new_node, location)?;
+
+        for attribute in attributes {
+            let qualified_name = attribute.name.to_string();
+            let attribute_node = match attribute.name.namespace_uri() {
+                None => document.create_attribute_with(&qualified_name, &attribute.value)?,
+                Some(namespace_uri) => {
+                    let mut attribute_node =
+                        document.create_attribute_ns(namespace_uri, &qualified_name)?;
+                    as_attribute_mut(&mut attribute_node)
+                        .unwrap()
+                        .set_value(&attribute.value)?;
+                    attribute_node
+                }
+            };
+            attribute_node.borrow_mut().i_location = attribute.location.map(Box::new);
+            let _safe_to_ignore = element.set_attribute_node(attribute_node)?;
+        }
+
+        self.open_nodes.push(element);
+        Ok(())
+    }
+
+    fn end_element(&mut self, _name: &Name, location: Option<SourceLocation>) -> Result<()> {
+        if let Some(element) = self.open_nodes.pop() {
+            //
+            // Extend the location of the element from the start tag to include this end tag.
+            //
+            if let (Some(element_location), Some(location)) =
+                (element.borrow_mut().i_location.as_mut(), location)
+            {
+                element_location.end = location.end;
+            }
+        }
+        self.merge_text = false;
+        Ok(())
+    }
+
+    fn characters(&mut self, text: &str, location: Option<SourceLocation>) -> Result<()> {
+        let previous = self
+            .parent()
+            .last_child()
+            .filter(|node| self.merge_text && node.node_type() == NodeType::Text);
+        match previous {
+            Some(mut previous) => {
+                as_character_data_mut(&mut previous)
+                    .unwrap()
+                    .append_data(text)?;
+                if let (Some(previous_location), Some(location)) =
+                    (previous.borrow_mut().i_location.as_mut(), location)
+                {
+                    previous_location.end = location.end;
+                }
+            }
+            None => {
+                let new_node = as_document(&self.document)
+                    .unwrap()
+                    .create_text_node(text)?;
+                let _safe_to_ignore = self.append(new_node, location)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cdata(&mut self, text: &str, location: Option<SourceLocation>) -> Result<()> {
+        let new_node = as_document(&self.document)
+            .unwrap()
+            .create_cdata_section(text)?;
+        let _safe_to_ignore = self.append(new_node, location)?;
+        Ok(())
+    }
+
+    fn comment(&mut self, text: &str, location: Option<SourceLocation>) -> Result<()> {
+        let new_node = as_document(&self.document).unwrap().create_comment(text)?;
+        let _safe_to_ignore = self.append(new_node, location)?;
+        Ok(())
+    }
+
+    fn processing_instruction(
+        &mut self,
+        target: &str,
+        data: Option<&str>,
+        location: Option<SourceLocation>,
+    ) -> Result<()> {
+        let new_node = as_document(&self.document)
+            .unwrap()
+            .create_processing_instruction(target, data)?;
+        let _safe_to_ignore = self.append(new_node, location)?;
+        Ok(())
+    }
+
+    fn start_entity(&mut self, name: &str, location: Option<SourceLocation>) -> Result<()> {
+        if self.is_parse_entities() {
+            //
+            // The replacement text is added in place of the reference.
+            //
+            self.open_entities.push(false);
+            self.merge_text = true;
+        } else {
+            //
+            // The DOM has no readonly nodes, so the children of the reference may be modified
+            // after parsing, see the `EntityReference` trait.
+            //
+            let new_node = as_document(&self.document)
+                .unwrap()
+                .create_entity_reference(name)?;
+            let reference_node = self.append(new_node, location)?;
+            self.open_nodes.push(reference_node);
+            self.open_entities.push(true);
+        }
+        Ok(())
+    }
+
+    fn end_entity(&mut self, _name: &str) -> Result<()> {
+        if let Some(has_node) = self.open_entities.pop() {
+            if has_node {
+                let _safe_to_ignore = self.open_nodes.pop();
+                self.merge_text = false;
+            } else {
+                self.merge_text = true;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,541 @@
+/*!
+Support for [OASIS XML Catalogs 1.1](https://www.oasis-open.org/committees/download.php/14809/xml-catalogs.html),
+which map the public and system identifiers of external entities, and other URIs, to local
+resources.
+
+The `public`, `system`, `rewriteSystem`, `uri`, `nextCatalog`, and `group` entries are supported,
+as are the `prefer` and `xml:base` attributes; other entries, and elements in other namespaces,
+are ignored. Catalogs referenced by `nextCatalog` entries are read when the catalog itself is read,
+and are consulted, in order, only if no entry in the referencing catalog matches.
+
+A [`Catalog`](struct.Catalog.html) may be used standalone to look up identifiers, or as the
+[`ExternalResolver`](trait.ExternalResolver.html) of the parser in which case only entities that
+the catalog maps to a `file:` URI, or a path, are loaded.
+
+# Example
+
+```rust
+use xml_dom::parser::Catalog;
+
+let catalog = Catalog::read_xml(
+    r#"<catalog xmlns="urn:oasis:names:tc:entity:xmlns:xml:catalog">
+  <public publicId="-//OASIS//DTD DocBook XML V4.5//EN" uri="docbook/docbookx.dtd"/>
+  <rewriteSystem systemIdStartString="http://www.oasis-open.org/docbook/xml/4.5/"
+                 rewritePrefix="docbook/"/>
+</catalog>"#,
+    Some("file:///usr/share/xml/catalog.xml"),
+)
+.unwrap();
+assert_eq!(
+    catalog.resolve_external(Some("-//OASIS//DTD DocBook XML V4.5//EN"), None),
+    Some("file:///usr/share/xml/docbook/docbookx.dtd".to_string())
+);
+assert_eq!(
+    catalog.resolve_external(None, Some("http://www.oasis-open.org/docbook/xml/4.5/dbpoolx.mod")),
+    Some("file:///usr/share/xml/docbook/dbpoolx.mod".to_string())
+);
+```
+*/
+
+use crate::level2::ext::SourceLocation;
+use crate::level2::Name;
+use crate::parser::handler::{ContentHandler, ParsedAttribute};
+use crate::parser::resolver::{file_path, resolve_relative, ExternalResolver};
+use crate::parser::{read_xml_with_handler, Error, ParserOptions, Result};
+use crate::shared::syntax::{XML_NS_ATTR_BASE, XML_NS_URI};
+use crate::shared::text::is_xml_space;
+use std::fs;
+use std::path::Path;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// An OASIS XML Catalog, with any catalogs it references using `nextCatalog`.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Catalog {
+    entries: Vec<Entry>,
+    next_catalogs: Vec<Catalog>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Clone, Debug, PartialEq)]
+enum Entry {
+    Public {
+        public_id: String,
+        uri: String,
+        prefer_public: bool,
+    },
+    System {
+        system_id: String,
+        uri: String,
+    },
+    RewriteSystem {
+        start_string: String,
+        rewrite_prefix: String,
+    },
+    Uri {
+        name: String,
+        uri: String,
+    },
+}
+
+///
+/// Collects the entries of a catalog as it is parsed.
+///
+#[derive(Debug)]
+struct CatalogReader {
+    /// For each open element, its base URI and whether public identifiers are preferred.
+    open_elements: Vec<(Option<String>, bool)>,
+    base_uri: Option<String>,
+    entries: Vec<Entry>,
+    next_catalogs: Vec<String>,
+}
+
+const CATALOG_NS_URI: &str = "urn:oasis:names:tc:entity:xmlns:xml:catalog";
+
+const CATALOG_ELEMENT: &str = "catalog";
+const GROUP_ELEMENT: &str = "group";
+const PUBLIC_ELEMENT: &str = "public";
+const SYSTEM_ELEMENT: &str = "system";
+const REWRITE_SYSTEM_ELEMENT: &str = "rewriteSystem";
+const URI_ELEMENT: &str = "uri";
+const NEXT_CATALOG_ELEMENT: &str = "nextCatalog";
+
+const PREFER_ATTRIBUTE: &str = "prefer";
+const PREFER_PUBLIC: &str = "public";
+const PREFER_SYSTEM: &str = "system";
+const PUBLIC_ID_ATTRIBUTE: &str = "publicId";
+const SYSTEM_ID_ATTRIBUTE: &str = "systemId";
+const START_STRING_ATTRIBUTE: &str = "systemIdStartString";
+const REWRITE_PREFIX_ATTRIBUTE: &str = "rewritePrefix";
+const URI_ATTRIBUTE: &str = "uri";
+const NAME_ATTRIBUTE: &str = "name";
+const CATALOG_ATTRIBUTE: &str = "catalog";
+
+const FILE_SCHEME: &str = "file://";
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Catalog {
+    ///
+    /// Read the catalog in the file at `path`, relative URIs in the catalog are relative to the
+    /// file.
+    ///
+    pub fn read_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut visited: Vec<String> = Default::default();
+        Self::read_file_visited(path.as_ref(), &mut visited)
+    }
+
+    ///
+    /// Read the catalog in `xml`, relative URIs in the catalog are relative to `base_uri` if
+    /// provided.
+    ///
+    pub fn read_xml(xml: impl AsRef<str>, base_uri: Option<&str>) -> Result<Self> {
+        let mut visited: Vec<String> = base_uri.map(str::to_string).into_iter().collect();
+        Self::read_xml_visited(xml.as_ref(), base_uri, &mut visited)
+    }
+
+    ///
+    /// Returns the URI of the external entity with the given public and system identifiers, if
+    /// either is mapped by the catalog. Following the resolution of external identifiers in the
+    /// specification, `system` entries are consulted before `rewriteSystem` entries, and both
+    /// before `public` entries; a `public` entry in the scope of `prefer="system"` is only used
+    /// if there is no system identifier.
+    ///
+    pub fn resolve_external(
+        &self,
+        public_id: Option<&str>,
+        system_id: Option<&str>,
+    ) -> Option<String> {
+        if let Some(system_id) = system_id {
+            let found = self.entries.iter().find_map(|entry| match entry {
+                Entry::System {
+                    system_id: entry_id,
+                    uri,
+                } if entry_id == system_id => Some(uri.clone()),
+                _ => None,
+            });
+            if found.is_some() {
+                return found;
+            }
+            let rewrite = self
+                .entries
+                .iter()
+                .filter_map(|entry| match entry {
+                    Entry::RewriteSystem {
+                        start_string,
+                        rewrite_prefix,
+                    } if system_id.starts_with(start_string.as_str()) => {
+                        Some((start_string, rewrite_prefix))
+                    }
+                    _ => None,
+                })
+                .max_by_key(|(start_string, _)| start_string.len());
+            if let Some((start_string, rewrite_prefix)) = rewrite {
+                return Some(format!(
+                    "{}{}",
+                    rewrite_prefix,
+                    &system_id[start_string.len()..]
+                ));
+            }
+        }
+        if let Some(public_id) = public_id {
+            let public_id = normalize_public_id(public_id);
+            let found = self.entries.iter().find_map(|entry| match entry {
+                Entry::Public {
+                    public_id: entry_id,
+                    uri,
+                    prefer_public,
+                } if *entry_id == public_id && (*prefer_public || system_id.is_none()) => {
+                    Some(uri.clone())
+                }
+                _ => None,
+            });
+            if found.is_some() {
+                return found;
+            }
+        }
+        self.next_catalogs
+            .iter()
+            .find_map(|catalog| catalog.resolve_external(public_id, system_id))
+    }
+
+    ///
+    /// Returns the URI that `uri` is mapped to by a `uri` entry in the catalog, if any.
+    ///
+    pub fn resolve_uri(&self, uri: &str) -> Option<String> {
+        self.entries
+            .iter()
+            .find_map(|entry| match entry {
+                Entry::Uri { name, uri: mapped } if name == uri => Some(mapped.clone()),
+                _ => None,
+            })
+            .or_else(|| {
+                self.next_catalogs
+                    .iter()
+                    .find_map(|catalog| catalog.resolve_uri(uri))
+            })
+    }
+
+    fn read_file_visited(path: &Path, visited: &mut Vec<String>) -> Result<Self> {
+        let path = path.canonicalize()?;
+        let base_uri = format!("{}{}", FILE_SCHEME, path.display());
+        visited.push(base_uri.clone());
+        let xml = fs::read_to_string(&path)?;
+        Self::read_xml_visited(&xml, Some(&base_uri), visited)
+    }
+
+    fn read_xml_visited(
+        xml: &str,
+        base_uri: Option<&str>,
+        visited: &mut Vec<String>,
+    ) -> Result<Self> {
+        let mut reader = CatalogReader {
+            open_elements: Default::default(),
+            base_uri: base_uri.map(str::to_string),
+            entries: Default::default(),
+            next_catalogs: Default::default(),
+        };
+        read_xml_with_handler(xml, ParserOptions::new(), &mut reader)?;
+
+        let mut next_catalogs: Vec<Catalog> = Default::default();
+        for uri in reader.next_catalogs {
+            if visited.contains(&uri) {
+                warn!("catalog '{}' has already been read", uri);
+                continue;
+            }
+            match file_path(&uri) {
+                None => warn!("catalog '{}' is not a file, ignored", uri),
+                Some(path) => match Self::read_file_visited(&path, visited) {
+                    Ok(catalog) => next_catalogs.push(catalog),
+                    //
+                    // A catalog that cannot be read is ignored, as required by the specification.
+                    //
+                    Err(err) => warn!("catalog '{}' could not be read: {}", uri, err),
+                },
+            }
+        }
+        Ok(Self {
+            entries: reader.entries,
+            next_catalogs,
+        })
+    }
+}
+
+impl ExternalResolver for Catalog {
+    fn resolve(
+        &self,
+        public_id: Option<&str>,
+        system_id: &str,
+        base_uri: Option<&str>,
+    ) -> Result<Option<Vec<u8>>> {
+        let system_id = resolve_relative(base_uri, system_id);
+        let uri = match self.resolve_external(public_id, Some(&system_id)) {
+            None => {
+                warn!("no catalog entry for '{}'", system_id);
+                return Ok(None);
+            }
+            Some(uri) => uri,
+        };
+        match file_path(&uri) {
+            None => {
+                warn!(
+                    "catalog maps '{}' to '{}' which is not a file",
+                    system_id, uri
+                );
+                Ok(None)
+            }
+            Some(path) => Ok(Some(fs::read(path)?)),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl ContentHandler for CatalogReader {
+    fn start_element(
+        &mut self,
+        name: &Name,
+        attributes: &[ParsedAttribute],
+        _location: Option<SourceLocation>,
+    ) -> Result<()> {
+        let is_catalog_element = name.namespace_uri().as_deref() == Some(CATALOG_NS_URI);
+        if self.open_elements.is_empty()
+            && !(is_catalog_element && name.local_name() == CATALOG_ELEMENT)
+        {
+            error!("catalog document element must be 'catalog', not '{}'", name);
+            return Error::Malformed.into();
+        }
+
+        let (parent_base, parent_prefer) = match self.open_elements.last() {
+            None => (self.base_uri.clone(), true),
+            Some((base_uri, prefer_public)) => (base_uri.clone(), *prefer_public),
+        };
+        let base_uri = match attributes.iter().find(|attribute| {
+            attribute.name.namespace_uri().as_deref() == Some(XML_NS_URI)
+                && attribute.name.local_name() == XML_NS_ATTR_BASE
+        }) {
+            None => parent_base,
+            Some(attribute) => Some(resolve_relative(parent_base.as_deref(), &attribute.value)),
+        };
+        let prefer_public = match attribute_value(attributes, PREFER_ATTRIBUTE) {
+            Some(PREFER_PUBLIC) => true,
+            Some(PREFER_SYSTEM) => false,
+            _ => parent_prefer,
+        };
+        let uri = |attribute_name: &str| -> Result<String> {
+            Ok(resolve_relative(
+                base_uri.as_deref(),
+                required_value(name, attributes, attribute_name)?,
+            ))
+        };
+
+        if is_catalog_element {
+            match name.local_name().as_str() {
+                PUBLIC_ELEMENT => self.entries.push(Entry::Public {
+                    public_id: normalize_public_id(required_value(
+                        name,
+                        attributes,
+                        PUBLIC_ID_ATTRIBUTE,
+                    )?),
+                    uri: uri(URI_ATTRIBUTE)?,
+                    prefer_public,
+                }),
+                SYSTEM_ELEMENT => self.entries.push(Entry::System {
+                    system_id: required_value(name, attributes, SYSTEM_ID_ATTRIBUTE)?.to_string(),
+                    uri: uri(URI_ATTRIBUTE)?,
+                }),
+                REWRITE_SYSTEM_ELEMENT => self.entries.push(Entry::RewriteSystem {
+                    start_string: required_value(name, attributes, START_STRING_ATTRIBUTE)?
+                        .to_string(),
+                    rewrite_prefix: uri(REWRITE_PREFIX_ATTRIBUTE)?,
+                }),
+                URI_ELEMENT => self.entries.push(Entry::Uri {
+                    name: required_value(name, attributes, NAME_ATTRIBUTE)?.to_string(),
+                    uri: uri(URI_ATTRIBUTE)?,
+                }),
+                NEXT_CATALOG_ELEMENT => self.next_catalogs.push(uri(CATALOG_ATTRIBUTE)?),
+                CATALOG_ELEMENT | GROUP_ELEMENT => {}
+                other => warn!("ignoring unsupported catalog entry '{}'", other),
+            }
+        }
+        self.open_elements.push((base_uri, prefer_public));
+        Ok(())
+    }
+
+    fn end_element(&mut self, _name: &Name, _location: Option<SourceLocation>) -> Result<()> {
+        let _safe_to_ignore = self.open_elements.pop();
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn attribute_value<'a>(attributes: &'a [ParsedAttribute], local_name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|attribute| {
+            attribute.name.namespace_uri().is_none() && attribute.name.local_name() == local_name
+        })
+        .map(|attribute| attribute.value.as_str())
+}
+
+fn required_value<'a>(
+    element: &Name,
+    attributes: &'a [ParsedAttribute],
+    local_name: &str,
+) -> Result<&'a str> {
+    match attribute_value(attributes, local_name) {
+        None => {
+            error!(
+                "catalog entry '{}' requires the attribute '{}'",
+                element, local_name
+            );
+            Error::Malformed.into()
+        }
+        Some(value) => Ok(value),
+    }
+}
+
+///
+/// Public identifiers are compared after white space is normalized, as described in §6.2 of the
+/// specification.
+///
+fn normalize_public_id(public_id: &str) -> String {
+    public_id
+        .split(is_xml_space)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level2::convert::as_document;
+    use crate::level2::Node;
+    use crate::parser::read_xml_with_options;
+
+    const CATALOG: &str = r#"<catalog xmlns="urn:oasis:names:tc:entity:xmlns:xml:catalog"
+         xmlns:other="urn:other" prefer="public">
+  <public publicId="-//Example//DTD  Book//EN" uri="dtd/book.dtd"/>
+  <system systemId="http://example.com/book.dtd" uri="dtd/system-book.dtd"/>
+  <rewriteSystem systemIdStartString="http://example.com/" rewritePrefix="local/"/>
+  <rewriteSystem systemIdStartString="http://example.com/modules/" rewritePrefix="modules/"/>
+  <group prefer="system" xml:base="http://mirror.example.com/">
+    <public publicId="-//Example//DTD Article//EN" uri="article.dtd"/>
+    <uri name="http://example.com/logo.png" uri="logo.png"/>
+  </group>
+  <other:public publicId="-//Example//Ignored//EN" uri="ignored.dtd"/>
+</catalog>"#;
+
+    #[test]
+    fn test_lookups() {
+        let catalog = Catalog::read_xml(CATALOG, Some("file:///xml/catalog.xml")).unwrap();
+        assert_eq!(
+            catalog.resolve_external(Some("-//Example//DTD Book//EN"), None),
+            Some("file:///xml/dtd/book.dtd".to_string())
+        );
+        assert_eq!(
+            catalog.resolve_external(
+                Some("-//Example//DTD Book//EN"),
+                Some("http://example.com/book.dtd")
+            ),
+            Some("file:///xml/dtd/system-book.dtd".to_string())
+        );
+        assert_eq!(
+            catalog.resolve_external(None, Some("http://example.com/modules/a.mod")),
+            Some("file:///xml/modules/a.mod".to_string())
+        );
+        assert_eq!(
+            catalog.resolve_external(None, Some("http://example.com/other.mod")),
+            Some("file:///xml/local/other.mod".to_string())
+        );
+        assert_eq!(
+            catalog.resolve_external(Some("-//Example//DTD Article//EN"), None),
+            Some("http://mirror.example.com/article.dtd".to_string())
+        );
+        assert!(catalog
+            .resolve_external(
+                Some("-//Example//DTD Article//EN"),
+                Some("http://elsewhere.com/article.dtd")
+            )
+            .is_none());
+        assert!(catalog
+            .resolve_external(Some("-//Example//Ignored//EN"), None)
+            .is_none());
+        assert_eq!(
+            catalog.resolve_uri("http://example.com/logo.png"),
+            Some("http://mirror.example.com/logo.png".to_string())
+        );
+        assert!(catalog
+            .resolve_uri("http://example.com/other.png")
+            .is_none());
+    }
+
+    #[test]
+    fn test_malformed_catalog() {
+        assert!(Catalog::read_xml("<catalog/>", None).is_err());
+        assert!(Catalog::read_xml(
+            r#"<catalog xmlns="urn:oasis:names:tc:entity:xmlns:xml:catalog"><public uri="a"/></catalog>"#,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_catalog_files() {
+        let root = std::env::temp_dir().join(format!("xml_dom_catalog_{}", std::process::id()));
+        fs::create_dir_all(root.join("dtd")).unwrap();
+        fs::write(
+            root.join("catalog.xml"),
+            r#"<catalog xmlns="urn:oasis:names:tc:entity:xmlns:xml:catalog">
+  <nextCatalog catalog="dtd/catalog.xml"/>
+  <nextCatalog catalog="missing.xml"/>
+</catalog>"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("dtd/catalog.xml"),
+            r#"<catalog xmlns="urn:oasis:names:tc:entity:xmlns:xml:catalog">
+  <public publicId="-//Example//DTD Book//EN" uri="book.dtd"/>
+  <nextCatalog catalog="../catalog.xml"/>
+</catalog>"#,
+        )
+        .unwrap();
+        fs::write(root.join("dtd/book.dtd"), "<!ENTITY title 'A Book'>").unwrap();
+
+        let catalog = Catalog::read_file(root.join("catalog.xml")).unwrap();
+        let document = read_xml_with_options(
+            r#"<!DOCTYPE book PUBLIC "-//Example//DTD Book//EN" "http://example.com/book.dtd">
+<book>&title;</book>"#,
+            ParserOptions::new().external_resolver(catalog),
+        )
+        .unwrap();
+        let book = as_document(&document).unwrap().document_element().unwrap();
+        assert_eq!(
+            book.first_child()
+                .unwrap()
+                .first_child()
+                .unwrap()
+                .node_value(),
+            Some("A Book".to_string())
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,1058 @@
+/*!
+Support for the document type declaration. The quick-xml reader only provides the raw content
+between `<!DOCTYPE` and the closing `>`, this module splits that content into the document type
+name, external identifier, and internal subset. The internal subset, followed by the external
+subset if it is loaded, is then parsed for entity and notation declarations, all other markup
+declarations are skipped.
+
+```ebnf
+doctypedecl       ::= '<!DOCTYPE' S Name (S ExternalID)? S? ('[' intSubset ']' S?)? '>'
+
+ExternalID        ::= 'SYSTEM' S SystemLiteral
+                    | 'PUBLIC' S PubidLiteral S SystemLiteral
+
+SystemLiteral     ::= ('"' [^"]* '"') | ("'" [^']* "'")
+
+PubidLiteral      ::= '"' PubidChar* '"' | "'" (PubidChar - "'")* "'"
+
+PubidChar         ::= #x20 | #xD | #xA | [a-zA-Z0-9] | [-'()+,./:=?;!*#@$_%]
+
+intSubset         ::= (markupdecl | DeclSep)*
+
+DeclSep           ::= PEReference | S
+
+markupdecl        ::= elementdecl | AttlistDecl | EntityDecl | NotationDecl | PI | Comment
+
+EntityDecl        ::= GEDecl | PEDecl
+
+GEDecl            ::= '<!ENTITY' S Name S EntityDef S? '>'
+
+PEDecl            ::= '<!ENTITY' S '%' S Name S PEDef S? '>'
+
+EntityDef         ::= EntityValue | (ExternalID NDataDecl?)
+
+PEDef             ::= EntityValue | ExternalID
+
+NDataDecl         ::= S 'NDATA' S Name
+
+NotationDecl      ::= '<!NOTATION' S Name S (ExternalID | PublicID) S? '>'
+
+PublicID          ::= 'PUBLIC' S PubidLiteral
+
+conditionalSect   ::= includeSect | ignoreSect
+
+includeSect       ::= '<![' S? 'INCLUDE' S? '[' extSubsetDecl ']]>'
+
+ignoreSect        ::= '<![' S? 'IGNORE' S? '[' ignoreSectContents* ']]>'
+```
+
+*/
+
+use crate::parser::reference::char_from_reference;
+use crate::parser::resolver::resolve_relative;
+use crate::parser::{Error, Limit, Result};
+use crate::shared::syntax::{
+    XML_COMMENT_END, XML_COMMENT_START, XML_DOCTYPE_ENTITY_END, XML_DOCTYPE_ENTITY_START,
+    XML_DOCTYPE_PUBLIC, XML_DOCTYPE_SYSTEM, XML_ENTITYREF_END, XML_ENTITY_END, XML_ENTITY_NOTATION,
+    XML_ENTITY_START, XML_NOTATION_END, XML_NOTATION_START, XML_NUMBERED_ENTITYREF_START,
+    XML_PI_END, XML_PI_START,
+};
+use crate::shared::text::{is_xml_name_char, is_xml_name_start_char, is_xml_space, EntityLimits};
+use std::collections::HashMap;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The components of a parsed `<!DOCTYPE ...>` declaration.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DocTypeDecl {
+    /// The name of the document type, this should match the name of the document element.
+    pub name: String,
+    /// The public identifier of the external subset, if any.
+    pub public_id: Option<String>,
+    /// The system identifier of the external subset, if any.
+    pub system_id: Option<String>,
+    /// The unparsed text of the internal subset, if any.
+    pub internal_subset: Option<String>,
+}
+
+///
+/// A parsed `<!ENTITY ...>` declaration; exactly one of `value` or `system_id` will be set.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityDecl {
+    /// The name of the entity.
+    pub name: String,
+    /// `true` for a parameter entity, declared with `%`.
+    pub is_parameter: bool,
+    /// The replacement text of an internal entity, with character references replaced.
+    pub value: Option<String>,
+    /// The public identifier of an external entity, if any.
+    pub public_id: Option<String>,
+    /// The system identifier of an external entity.
+    pub system_id: Option<String>,
+    /// The notation of an unparsed entity.
+    pub notation_name: Option<String>,
+    /// The URI of the entity, or document, containing the declaration; a relative `system_id`
+    /// is relative to this.
+    pub base_uri: Option<String>,
+}
+
+///
+/// A parsed `<!NOTATION ...>` declaration.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NotationDecl {
+    /// The name of the notation.
+    pub name: String,
+    /// The public identifier of the notation, if any.
+    pub public_id: Option<String>,
+    /// The system identifier of the notation, if any.
+    pub system_id: Option<String>,
+}
+
+///
+/// The markup declarations from the internal and external subsets that are retained by the parser.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum MarkupDecl {
+    Entity(EntityDecl),
+    Notation(NotationDecl),
+}
+
+///
+/// Loads the text of the external subset, or of an external parameter entity, given its public
+/// identifier, system identifier, and base URI; returns `None` if it is not loaded.
+///
+pub(crate) type Loader<'a> =
+    dyn FnMut(Option<&str>, &str, Option<&str>) -> Result<Option<String>> + 'a;
+
+///
+/// Parses the internal and external subsets of a document type declaration. References to
+/// parameter entities are expanded in place, the first declaration of a parameter entity is
+/// binding. The nesting and total size of these expansions are subject to the same limits as
+/// general entities.
+///
+pub(crate) struct SubsetParser<'a> {
+    declarations: Vec<MarkupDecl>,
+    parameter_entities: HashMap<String, EntityDecl>,
+    expanding: Vec<String>,
+    limits: EntityLimits,
+    expanded_bytes: usize,
+    loader: &'a mut Loader<'a>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Parse the content of a document type declaration, this is the text following `<!DOCTYPE` and
+/// up to, but not including, the closing `>`.
+///
+pub(crate) fn parse_doctype_decl(content: &str) -> Result<DocTypeDecl> {
+    let mut scanner = Scanner::new(content);
+    let _safe_to_ignore = scanner.skip_space();
+
+    let name = match scanner.take_name() {
+        None => {
+            error!("DOCTYPE declaration must start with a name");
+            return Error::Malformed.into();
+        }
+        Some(name) => name.to_string(),
+    };
+
+    let had_space = scanner.skip_space();
+    let (public_id, system_id) = if had_space {
+        scanner.take_external_id()?
+    } else {
+        (None, None)
+    };
+    let _safe_to_ignore = scanner.skip_space();
+
+    let internal_subset = if scanner.take_str(XML_DOCTYPE_ENTITY_START) {
+        let rest = scanner.rest();
+        match rest.rfind(XML_DOCTYPE_ENTITY_END) {
+            Some(end) if rest[end + 1..].chars().all(is_xml_space) => {
+                scanner.advance(rest.len());
+                Some(rest[..end].to_string())
+            }
+            _ => {
+                error!("DOCTYPE internal subset is not closed");
+                return Error::Malformed.into();
+            }
+        }
+    } else {
+        None
+    };
+
+    if !scanner.is_empty() {
+        error!("unexpected content in DOCTYPE: {:?}", scanner.rest());
+        return Error::Malformed.into();
+    }
+
+    Ok(DocTypeDecl {
+        name,
+        public_id,
+        system_id,
+        internal_subset,
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct Scanner<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<'a> SubsetParser<'a> {
+    pub(crate) fn new(loader: &'a mut Loader<'a>, limits: EntityLimits) -> Self {
+        Self {
+            declarations: Default::default(),
+            parameter_entities: Default::default(),
+            expanding: Default::default(),
+            limits,
+            expanded_bytes: 0,
+            loader,
+        }
+    }
+
+    ///
+    /// Parse the internal subset, `base_uri` is the URI of the document.
+    ///
+    pub(crate) fn parse_internal(&mut self, subset: &str, base_uri: Option<&str>) -> Result<()> {
+        self.parse_markup_decls(subset, base_uri, false)
+    }
+
+    ///
+    /// Load and parse the external subset, this must follow the internal subset.
+    ///
+    pub(crate) fn parse_external(
+        &mut self,
+        public_id: Option<&str>,
+        system_id: &str,
+        base_uri: Option<&str>,
+    ) -> Result<()> {
+        if let Some(subset) = (self.loader)(public_id, system_id, base_uri)? {
+            let subset_uri = resolve_relative(base_uri, system_id);
+            self.parse_markup_decls(&subset, Some(&subset_uri), true)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// The total size of the replacement text of the parameter entities expanded.
+    ///
+    pub(crate) fn expanded_bytes(&self) -> usize {
+        self.expanded_bytes
+    }
+
+    ///
+    /// Returns the entity and notation declarations, in the order they were parsed.
+    ///
+    pub(crate) fn into_declarations(self) -> Vec<MarkupDecl> {
+        self.declarations
+    }
+
+    fn parse_markup_decls(
+        &mut self,
+        subset: &str,
+        base_uri: Option<&str>,
+        is_external: bool,
+    ) -> Result<()> {
+        let mut scanner = Scanner::new(subset);
+        loop {
+            let _safe_to_ignore = scanner.skip_space();
+            if scanner.is_empty() {
+                return Ok(());
+            } else if scanner.take_str(XML_COMMENT_START) {
+                if scanner.take_until(XML_COMMENT_END).is_none() {
+                    error!("comment in DTD is not closed");
+                    return Error::Malformed.into();
+                }
+            } else if scanner.take_str(XML_ENTITY_START) {
+                let mut decl = parse_entity_decl(&mut scanner)?;
+                decl.base_uri = base_uri.map(str::to_string);
+                if let Some(literal) = decl.value.take() {
+                    decl.value = Some(self.expand_entity_value(&literal, is_external)?);
+                }
+                if decl.is_parameter && !self.parameter_entities.contains_key(&decl.name) {
+                    let _safe_to_ignore = self
+                        .parameter_entities
+                        .insert(decl.name.clone(), decl.clone());
+                }
+                self.declarations.push(MarkupDecl::Entity(decl));
+            } else if scanner.take_str(XML_NOTATION_START) {
+                let decl = parse_notation_decl(&mut scanner)?;
+                self.declarations.push(MarkupDecl::Notation(decl));
+            } else if scanner.take_str(XML_DECL_ELEMENT_START)
+                || scanner.take_str(XML_DECL_ATTLIST_START)
+            {
+                scanner.skip_markup_decl()?;
+            } else if scanner.take_str(XML_PI_START) {
+                if scanner.take_until(XML_PI_END).is_none() {
+                    error!("processing instruction in DTD is not closed");
+                    return Error::Malformed.into();
+                }
+            } else if scanner.take_str(XML_CONDITIONAL_START) {
+                if !is_external {
+                    error!("conditional sections are only allowed in the external subset");
+                    return Error::Malformed.into();
+                }
+                self.parse_conditional_sect(&mut scanner, base_uri)?;
+            } else if scanner.take_str(XML_PEREF_START) {
+                let name = scanner.take_peref_name()?;
+                self.expand_parameter_entity(&name, base_uri, is_external)?;
+            } else {
+                error!("unexpected content in DTD: {:?}", scanner.rest());
+                return Error::Malformed.into();
+            }
+        }
+    }
+
+    fn parse_conditional_sect(
+        &mut self,
+        scanner: &mut Scanner<'_>,
+        base_uri: Option<&str>,
+    ) -> Result<()> {
+        let _safe_to_ignore = scanner.skip_space();
+        let keyword = if scanner.take_str(XML_PEREF_START) {
+            let name = scanner.take_peref_name()?;
+            match self
+                .parameter_entities
+                .get(&name)
+                .and_then(|entity| entity.value.as_ref())
+            {
+                None => {
+                    error!("conditional section keyword '%{};' is not declared", name);
+                    return Error::Malformed.into();
+                }
+                Some(value) => value.trim_matches(is_xml_space).to_string(),
+            }
+        } else {
+            scanner.take_name().unwrap_or_default().to_string()
+        };
+        let _safe_to_ignore = scanner.skip_space();
+        if !scanner.take_str(XML_DOCTYPE_ENTITY_START) {
+            error!("expecting '[' at {:?}", scanner.rest());
+            return Error::Malformed.into();
+        }
+        let content = scanner.take_conditional_content()?;
+        match keyword.as_str() {
+            XML_CONDITIONAL_INCLUDE => self.parse_markup_decls(content, base_uri, true),
+            XML_CONDITIONAL_IGNORE => Ok(()),
+            _ => {
+                error!("invalid conditional section keyword '{}'", keyword);
+                Error::Malformed.into()
+            }
+        }
+    }
+
+    fn expand_parameter_entity(
+        &mut self,
+        name: &str,
+        base_uri: Option<&str>,
+        is_external: bool,
+    ) -> Result<()> {
+        let entity = match self.parameter_entities.get(name) {
+            None => {
+                error!("reference to undeclared parameter entity '{}'", name);
+                return Error::Malformed.into();
+            }
+            Some(entity) => entity.clone(),
+        };
+        if self.expanding.iter().any(|expanding| expanding == name) {
+            error!("recursive reference to parameter entity '{}'", name);
+            return Error::Malformed.into();
+        }
+        match (&entity.value, &entity.system_id) {
+            (Some(value), _) => {
+                self.open(name, value)?;
+                self.parse_markup_decls(value, base_uri, is_external)?
+            }
+            (None, Some(system_id)) => {
+                let base_uri = entity.base_uri.as_deref();
+                match (self.loader)(entity.public_id.as_deref(), system_id, base_uri)? {
+                    None => {
+                        self.open(name, "")?;
+                        warn!("external parameter entity '{}' is not loaded", name)
+                    }
+                    Some(text) => {
+                        self.open(name, &text)?;
+                        let entity_uri = resolve_relative(base_uri, system_id);
+                        self.parse_markup_decls(&text, Some(&entity_uri), true)?
+                    }
+                }
+            }
+            (None, None) => self.open(name, "")?,
+        }
+        let _safe_to_ignore = self.expanding.pop();
+        Ok(())
+    }
+
+    ///
+    /// Returns the replacement text of an entity from its literal value; character references
+    /// are expanded, as are references to parameter entities which are only allowed in the
+    /// external subset.
+    ///
+    fn expand_entity_value(&mut self, literal: &str, is_external: bool) -> Result<String> {
+        let mut result = String::with_capacity(literal.len());
+        let mut scanner = Scanner::new(literal);
+        while !scanner.is_empty() {
+            let rest = scanner.rest();
+            let text = match rest.find(XML_PEREF_START) {
+                None => rest,
+                Some(start) => &rest[..start],
+            };
+            result.push_str(&expand_char_references(text)?);
+            scanner.advance(text.len());
+            if scanner.take_str(XML_PEREF_START) {
+                let name = scanner.take_peref_name()?;
+                if !is_external {
+                    error!(
+                        "reference to parameter entity '{}' in an entity value in the internal subset",
+                        name
+                    );
+                    return Error::Malformed.into();
+                }
+                result.push_str(&self.parameter_entity_text(&name)?);
+            }
+        }
+        Ok(result)
+    }
+
+    ///
+    /// Returns the replacement text of the parameter entity `name` for inclusion in an entity
+    /// value.
+    ///
+    fn parameter_entity_text(&mut self, name: &str) -> Result<String> {
+        let entity = match self.parameter_entities.get(name) {
+            None => {
+                error!("reference to undeclared parameter entity '{}'", name);
+                return Error::Malformed.into();
+            }
+            Some(entity) => entity.clone(),
+        };
+        if self.expanding.iter().any(|expanding| expanding == name) {
+            error!("recursive reference to parameter entity '{}'", name);
+            return Error::Malformed.into();
+        }
+        let text = match (&entity.value, &entity.system_id) {
+            (Some(value), _) => {
+                self.open(name, value)?;
+                value.clone()
+            }
+            (None, Some(system_id)) => {
+                let base_uri = entity.base_uri.as_deref();
+                match (self.loader)(entity.public_id.as_deref(), system_id, base_uri)? {
+                    None => {
+                        self.open(name, "")?;
+                        warn!("external parameter entity '{}' is not loaded", name);
+                        String::new()
+                    }
+                    Some(text) => {
+                        self.open(name, &text)?;
+                        self.expand_entity_value(&text, true)?
+                    }
+                }
+            }
+            (None, None) => {
+                self.open(name, "")?;
+                String::new()
+            }
+        };
+        let _safe_to_ignore = self.expanding.pop();
+        Ok(text)
+    }
+
+    ///
+    /// Start expanding the parameter entity `name`, charging its replacement text against the
+    /// limits.
+    ///
+    fn open(&mut self, name: &str, replacement: &str) -> Result<()> {
+        if self.expanding.len() + 1 > self.limits.max_depth {
+            error!(
+                "limit on {} of {} exceeded",
+                Limit::EntityDepth,
+                self.limits.max_depth
+            );
+            return Error::LimitExceeded(Limit::EntityDepth).into();
+        }
+        self.expanded_bytes = self.expanded_bytes.saturating_add(replacement.len());
+        if self.expanded_bytes > self.limits.max_expansion {
+            error!(
+                "limit on {} of {} exceeded",
+                Limit::EntityExpansion,
+                self.limits.max_expansion
+            );
+            return Error::LimitExceeded(Limit::EntityExpansion).into();
+        }
+        self.expanding.push(name.to_string());
+        Ok(())
+    }
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    fn advance(&mut self, count: usize) {
+        self.position += count;
+    }
+
+    fn skip_space(&mut self) -> bool {
+        let rest = self.rest();
+        let trimmed = rest.trim_start_matches(is_xml_space);
+        self.advance(rest.len() - trimmed.len());
+        rest.len() != trimmed.len()
+    }
+
+    fn take_str(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.advance(s.len());
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_name(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        if !rest.starts_with(is_xml_name_start_char) {
+            return None;
+        }
+        let end = rest
+            .char_indices()
+            .find(|(_, c)| !is_xml_name_char(*c))
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        self.advance(end);
+        Some(&rest[..end])
+    }
+
+    fn take_literal(&mut self) -> Result<&'a str> {
+        let rest = self.rest();
+        let quote = match rest.chars().next() {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => {
+                error!("expecting a quoted literal");
+                return Error::InvalidCharacter.into();
+            }
+        };
+        match rest[1..].find(quote) {
+            None => {
+                error!("quoted literal is not closed");
+                Error::InvalidCharacter.into()
+            }
+            Some(end) => {
+                self.advance(end + 2);
+                Ok(&rest[1..end + 1])
+            }
+        }
+    }
+
+    fn take_external_id(&mut self) -> Result<(Option<String>, Option<String>)> {
+        if self.take_str(XML_DOCTYPE_SYSTEM) {
+            self.expect_space()?;
+            let system_id = self.take_literal()?;
+            Ok((None, Some(system_id.to_string())))
+        } else if self.take_str(XML_DOCTYPE_PUBLIC) {
+            self.expect_space()?;
+            let public_id = self.take_literal()?;
+            if !public_id.chars().all(is_pubid_char) {
+                error!("invalid character in public identifier {:?}", public_id);
+                return Error::InvalidCharacter.into();
+            }
+            self.expect_space()?;
+            let system_id = self.take_literal()?;
+            Ok((Some(public_id.to_string()), Some(system_id.to_string())))
+        } else {
+            Ok((None, None))
+        }
+    }
+
+    fn take_until(&mut self, end: &str) -> Option<&'a str> {
+        let rest = self.rest();
+        let found = rest.find(end)?;
+        self.advance(found + end.len());
+        Some(&rest[..found])
+    }
+
+    fn take_peref_name(&mut self) -> Result<String> {
+        let name = match self.take_name() {
+            None => {
+                error!("expecting a parameter entity name at {:?}", self.rest());
+                return Error::Malformed.into();
+            }
+            Some(name) => name.to_string(),
+        };
+        if !self.take_str(XML_ENTITYREF_END) {
+            error!("parameter entity reference is not closed");
+            return Error::Malformed.into();
+        }
+        Ok(name)
+    }
+
+    ///
+    /// Returns the content of a conditional section up to the matching `]]>`, which is consumed.
+    ///
+    fn take_conditional_content(&mut self) -> Result<&'a str> {
+        let rest = self.rest();
+        let mut depth = 1;
+        let mut position = 0;
+        while depth > 0 {
+            let next_start = rest[position..].find(XML_CONDITIONAL_START);
+            let next_end = match rest[position..].find(XML_CONDITIONAL_END) {
+                None => {
+                    error!("conditional section is not closed");
+                    return Error::Malformed.into();
+                }
+                Some(next_end) => next_end,
+            };
+            match next_start {
+                Some(next_start) if next_start < next_end => {
+                    depth += 1;
+                    position += next_start + XML_CONDITIONAL_START.len();
+                }
+                _ => {
+                    depth -= 1;
+                    position += next_end + XML_CONDITIONAL_END.len();
+                }
+            }
+        }
+        self.advance(position);
+        Ok(&rest[..position - XML_CONDITIONAL_END.len()])
+    }
+
+    fn skip_markup_decl(&mut self) -> Result<()> {
+        //
+        // Skip to the closing '>' ignoring any that appear within quoted literals.
+        //
+        let rest = self.rest();
+        let mut quote: Option<char> = None;
+        for (index, c) in rest.char_indices() {
+            match (quote, c) {
+                (None, '>') => {
+                    self.advance(index + 1);
+                    return Ok(());
+                }
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (Some(q), c) if q == c => quote = None,
+                _ => {}
+            }
+        }
+        error!("markup declaration is not closed");
+        Error::Malformed.into()
+    }
+
+    fn take_decl_end(&mut self, end: &str) -> Result<()> {
+        let _safe_to_ignore = self.skip_space();
+        if self.take_str(end) {
+            Ok(())
+        } else {
+            error!("expecting '{}' at {:?}", end, self.rest());
+            Error::Malformed.into()
+        }
+    }
+
+    fn expect_space(&mut self) -> Result<()> {
+        if self.skip_space() {
+            Ok(())
+        } else {
+            error!("expecting white space at {:?}", self.rest());
+            Error::Malformed.into()
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const XML_DECL_ELEMENT_START: &str = "<!ELEMENT";
+const XML_DECL_ATTLIST_START: &str = "<!ATTLIST";
+const XML_PEREF_START: &str = "%";
+const XML_CONDITIONAL_START: &str = "<![";
+const XML_CONDITIONAL_END: &str = "]]>";
+const XML_CONDITIONAL_INCLUDE: &str = "INCLUDE";
+const XML_CONDITIONAL_IGNORE: &str = "IGNORE";
+
+fn parse_entity_decl(scanner: &mut Scanner<'_>) -> Result<EntityDecl> {
+    scanner.expect_space()?;
+    let is_parameter = if scanner.take_str(XML_PEREF_START) {
+        scanner.expect_space()?;
+        true
+    } else {
+        false
+    };
+    let name = match scanner.take_name() {
+        None => {
+            error!("expecting an entity name at {:?}", scanner.rest());
+            return Error::Malformed.into();
+        }
+        Some(name) => name.to_string(),
+    };
+    scanner.expect_space()?;
+
+    let mut decl = EntityDecl {
+        name,
+        is_parameter,
+        ..Default::default()
+    };
+    let (public_id, system_id) = scanner.take_external_id()?;
+    if system_id.is_some() {
+        decl.public_id = public_id;
+        decl.system_id = system_id;
+        let had_space = scanner.skip_space();
+        if had_space && scanner.take_str(XML_ENTITY_NOTATION) {
+            if is_parameter {
+                error!("parameter entities may not be unparsed");
+                return Error::Malformed.into();
+            }
+            scanner.expect_space()?;
+            match scanner.take_name() {
+                None => {
+                    error!("expecting a notation name at {:?}", scanner.rest());
+                    return Error::Malformed.into();
+                }
+                Some(notation_name) => decl.notation_name = Some(notation_name.to_string()),
+            }
+        }
+    } else {
+        decl.value = Some(scanner.take_literal()?.to_string());
+    }
+    scanner.take_decl_end(XML_ENTITY_END)?;
+    Ok(decl)
+}
+
+fn parse_notation_decl(scanner: &mut Scanner<'_>) -> Result<NotationDecl> {
+    scanner.expect_space()?;
+    let name = match scanner.take_name() {
+        None => {
+            error!("expecting a notation name at {:?}", scanner.rest());
+            return Error::Malformed.into();
+        }
+        Some(name) => name.to_string(),
+    };
+    scanner.expect_space()?;
+
+    let (public_id, system_id) = if scanner.take_str(XML_DOCTYPE_PUBLIC) {
+        //
+        // Either `ExternalID` or `PublicID`, the system literal is optional here.
+        //
+        scanner.expect_space()?;
+        let public_id = scanner.take_literal()?;
+        if !public_id.chars().all(is_pubid_char) {
+            error!("invalid character in public identifier {:?}", public_id);
+            return Error::InvalidCharacter.into();
+        }
+        let had_space = scanner.skip_space();
+        let system_id = if had_space && !scanner.rest().starts_with(XML_NOTATION_END) {
+            Some(scanner.take_literal()?.to_string())
+        } else {
+            None
+        };
+        (Some(public_id.to_string()), system_id)
+    } else {
+        match scanner.take_external_id()? {
+            (_, None) => {
+                error!(
+                    "notation '{}' requires an external or public identifier",
+                    name
+                );
+                return Error::Malformed.into();
+            }
+            ids => ids,
+        }
+    };
+    scanner.take_decl_end(XML_NOTATION_END)?;
+    Ok(NotationDecl {
+        name,
+        public_id,
+        system_id,
+    })
+}
+
+///
+/// Character references in entity values are expanded when the entity is declared, general
+/// entity references are left in place to be expanded when the entity is referenced.
+///
+fn expand_char_references(value: &str) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find(XML_NUMBERED_ENTITYREF_START) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(XML_ENTITYREF_END) {
+            None => {
+                error!("character reference is not closed");
+                return Error::Malformed.into();
+            }
+            Some(end) => end,
+        };
+        result.push(char_from_reference(&rest[..=end])?);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn is_pubid_char(c: char) -> bool {
+    c == '\u{20}'
+        || c == '\u{0D}'
+        || c == '\u{0A}'
+        || c.is_ascii_alphanumeric()
+        || "-'()+,./:=?;!*#@$_%".contains(c)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_internal_subset(subset: &str) -> Result<Vec<MarkupDecl>> {
+        let mut loader = |_: Option<&str>, _: &str, _: Option<&str>| Ok(None);
+        let mut parser = SubsetParser::new(&mut loader, EntityLimits::default());
+        parser.parse_internal(subset, None)?;
+        Ok(parser.into_declarations())
+    }
+
+    fn parse_external_subset(subset: &str) -> Result<Vec<MarkupDecl>> {
+        let mut loader = |_: Option<&str>, _: &str, _: Option<&str>| Ok(Some(subset.to_string()));
+        let mut parser = SubsetParser::new(&mut loader, EntityLimits::default());
+        parser.parse_external(None, "subset.dtd", None)?;
+        Ok(parser.into_declarations())
+    }
+
+    #[test]
+    fn test_name_only() {
+        let decl = parse_doctype_decl("html").unwrap();
+        assert_eq!(decl.name, "html");
+        assert!(decl.public_id.is_none());
+        assert!(decl.system_id.is_none());
+        assert!(decl.internal_subset.is_none());
+    }
+
+    #[test]
+    fn test_system_id() {
+        let decl = parse_doctype_decl("greeting SYSTEM 'hello.dtd'").unwrap();
+        assert_eq!(decl.name, "greeting");
+        assert!(decl.public_id.is_none());
+        assert_eq!(decl.system_id, Some("hello.dtd".to_string()));
+    }
+
+    #[test]
+    fn test_public_id() {
+        let decl = parse_doctype_decl(
+            r#"html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN"
+    "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd""#,
+        )
+        .unwrap();
+        assert_eq!(decl.name, "html");
+        assert_eq!(
+            decl.public_id,
+            Some("-//W3C//DTD XHTML 1.0 Strict//EN".to_string())
+        );
+        assert_eq!(
+            decl.system_id,
+            Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd".to_string())
+        );
+    }
+
+    #[test]
+    fn test_internal_subset() {
+        let decl =
+            parse_doctype_decl("greeting SYSTEM \"hello.dtd\" [\n  <!ENTITY name \"[value]\">\n] ")
+                .unwrap();
+        assert_eq!(decl.system_id, Some("hello.dtd".to_string()));
+        assert_eq!(
+            decl.internal_subset,
+            Some("\n  <!ENTITY name \"[value]\">\n".to_string())
+        );
+
+        let decl = parse_doctype_decl("greeting[]").unwrap();
+        assert_eq!(decl.internal_subset, Some(String::new()));
+    }
+
+    #[test]
+    fn test_internal_entities() {
+        let decls = parse_internal_subset(
+            r#"
+  <!ELEMENT doc (#PCDATA)>
+  <!ATTLIST doc type CDATA "a > b">
+  <!-- a <!ENTITY commented "out"> -->
+  <?pi <!ENTITY in "pi"> ?>
+  <!ENTITY copy "&#169; &#xA9; &name;">
+  <!ENTITY % draft 'INCLUDE'>
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            decls,
+            vec![
+                MarkupDecl::Entity(EntityDecl {
+                    name: "copy".to_string(),
+                    value: Some("© © &name;".to_string()),
+                    ..Default::default()
+                }),
+                MarkupDecl::Entity(EntityDecl {
+                    name: "draft".to_string(),
+                    is_parameter: true,
+                    value: Some("INCLUDE".to_string()),
+                    ..Default::default()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_external_entities() {
+        let decls = parse_internal_subset(
+            r#"<!ENTITY chap1 SYSTEM "chap1.xml">
+<!ENTITY logo PUBLIC "-//Example//Logo" "logo.gif" NDATA gif >
+<!ENTITY % ents SYSTEM "ents.dtd">"#,
+        )
+        .unwrap();
+        assert_eq!(
+            decls,
+            vec![
+                MarkupDecl::Entity(EntityDecl {
+                    name: "chap1".to_string(),
+                    system_id: Some("chap1.xml".to_string()),
+                    ..Default::default()
+                }),
+                MarkupDecl::Entity(EntityDecl {
+                    name: "logo".to_string(),
+                    public_id: Some("-//Example//Logo".to_string()),
+                    system_id: Some("logo.gif".to_string()),
+                    notation_name: Some("gif".to_string()),
+                    ..Default::default()
+                }),
+                MarkupDecl::Entity(EntityDecl {
+                    name: "ents".to_string(),
+                    is_parameter: true,
+                    system_id: Some("ents.dtd".to_string()),
+                    ..Default::default()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_notations() {
+        let decls = parse_internal_subset(
+            r#"<!NOTATION gif SYSTEM "image/gif">
+<!NOTATION png PUBLIC "-//Example//PNG">
+<!NOTATION jpg PUBLIC "-//Example//JPEG" "image/jpeg">"#,
+        )
+        .unwrap();
+        assert_eq!(
+            decls,
+            vec![
+                MarkupDecl::Notation(NotationDecl {
+                    name: "gif".to_string(),
+                    public_id: None,
+                    system_id: Some("image/gif".to_string()),
+                }),
+                MarkupDecl::Notation(NotationDecl {
+                    name: "png".to_string(),
+                    public_id: Some("-//Example//PNG".to_string()),
+                    system_id: None,
+                }),
+                MarkupDecl::Notation(NotationDecl {
+                    name: "jpg".to_string(),
+                    public_id: Some("-//Example//JPEG".to_string()),
+                    system_id: Some("image/jpeg".to_string()),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parameter_entity_reference() {
+        let decls = parse_internal_subset(
+            r#"<!ENTITY % decls '<!ENTITY one "1"><!ENTITY two "2">'> %decls;"#,
+        )
+        .unwrap();
+        assert_eq!(decls.len(), 3);
+        assert_eq!(
+            decls[2],
+            MarkupDecl::Entity(EntityDecl {
+                name: "two".to_string(),
+                value: Some("2".to_string()),
+                ..Default::default()
+            })
+        );
+
+        assert!(parse_internal_subset("%undeclared;").is_err());
+        assert!(parse_internal_subset("<!ENTITY % p 'x'> <!ENTITY e '[%p;]'>").is_err());
+        assert!(parse_internal_subset("<!ENTITY % loop '%loop;'> %loop;").is_err());
+    }
+
+    #[test]
+    fn test_parameter_entity_in_entity_value() {
+        let decls = parse_external_subset(
+            "<!ENTITY % p 'x&#37;y'> <!ENTITY % q '(%p;)'> <!ENTITY e '[%q;&#37;&amp;]'>",
+        )
+        .unwrap();
+        assert_eq!(decls.len(), 3);
+        assert_eq!(
+            decls[2],
+            MarkupDecl::Entity(EntityDecl {
+                name: "e".to_string(),
+                value: Some("[(x%y)%&amp;]".to_string()),
+                base_uri: Some("subset.dtd".to_string()),
+                ..Default::default()
+            })
+        );
+
+        assert!(parse_external_subset("<!ENTITY e '%undeclared;'>").is_err());
+        assert!(parse_external_subset("<!ENTITY e '100%'>").is_err());
+    }
+
+    #[test]
+    fn test_malformed_subset() {
+        assert!(parse_internal_subset("<!ENTITY name>").is_err());
+        assert!(parse_internal_subset("<!ENTITY name 'value'").is_err());
+        assert!(parse_internal_subset("<!ENTITY name 'value' extra>").is_err());
+        assert!(parse_internal_subset("<!ENTITY % name SYSTEM 'x' NDATA gif>").is_err());
+        assert!(parse_internal_subset("<!ENTITY bad '&#xD800;'>").is_err());
+        assert!(parse_internal_subset("<!NOTATION name>").is_err());
+        assert!(parse_internal_subset("<!-- not closed").is_err());
+        assert!(parse_internal_subset("stray").is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(parse_doctype_decl("").is_err());
+        assert!(parse_doctype_decl("1html").is_err());
+        assert!(parse_doctype_decl("html PUBLIC \"only-one\"").is_err());
+        assert!(parse_doctype_decl("html SYSTEM \"unclosed").is_err());
+        assert!(parse_doctype_decl("html PUBLIC \"{bad}\" \"x.dtd\"").is_err());
+        assert!(parse_doctype_decl("html [ <!ENTITY x 'y'>").is_err());
+        assert!(parse_doctype_decl("html SYSTEM \"x.dtd\" junk").is_err());
+    }
+}
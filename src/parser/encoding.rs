@@ -0,0 +1,415 @@
+/*!
+Detection and decoding of the character encoding of byte input, following XML 1.0
+[Appendix F](https://www.w3.org/TR/xml/#sec-guessing), Autodetection of Character Encodings.
+
+The encoding is determined from a byte order mark if present, from the pattern of the first bytes
+of the XML declaration for UTF-16 without a byte order mark, and otherwise from the `encoding`
+of an XML declaration. In the absence of any of these the input is UTF-8. An `encoding` in the
+XML declaration that does not agree with a byte order mark, or with the pattern of the first
+bytes, is an error.
+
+Only UTF-8, US-ASCII, UTF-16, and the parts of ISO-8859 (all but the abandoned ISO-8859-12) are
+supported.
+*/
+
+use crate::parser::iso_8859;
+use crate::parser::{Error, Result};
+use crate::shared::syntax::{XML_DECL_ENCODING, XML_DECL_START};
+use crate::shared::text::is_xml_space;
+use std::borrow::Cow;
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The encodings detected, and decoded, by this module.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Utf8,
+    UsAscii,
+    Utf16Le,
+    Utf16Be,
+    /// A part of ISO-8859, the part number is from 1 to 16.
+    Iso8859(u8),
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+const ISO_8859_NAMES: [&str; 16] = [
+    "ISO-8859-1",
+    "ISO-8859-2",
+    "ISO-8859-3",
+    "ISO-8859-4",
+    "ISO-8859-5",
+    "ISO-8859-6",
+    "ISO-8859-7",
+    "ISO-8859-8",
+    "ISO-8859-9",
+    "ISO-8859-10",
+    "ISO-8859-11",
+    "ISO-8859-12",
+    "ISO-8859-13",
+    "ISO-8859-14",
+    "ISO-8859-15",
+    "ISO-8859-16",
+];
+
+const BYTE_ORDER_MARK: char = '\u{FEFF}';
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Detect the encoding of `bytes` and decode them, any byte order mark is removed. Returns an
+/// error if the encoding declared by the XML declaration is not the encoding detected.
+///
+pub(crate) fn decode(bytes: &[u8]) -> Result<(Cow<'_, str>, Encoding)> {
+    let encoding = detect(bytes)?;
+    let text = decode_as(bytes, encoding)?;
+    if text.starts_with(XML_DECL_START) && text[XML_DECL_START.len()..].starts_with(is_xml_space) {
+        if let Some(label) = declared_encoding(text.as_bytes()) {
+            if !encoding.is_labelled(label)? {
+                error!(
+                    "declared encoding '{}' does not match the detected encoding {}",
+                    label,
+                    encoding.name()
+                );
+                return Error::Malformed.into();
+            }
+        }
+    }
+    Ok((text, encoding))
+}
+
+///
+/// Decode `bytes` that are text rather than XML, such as a resource included with
+/// `parse="text"`. A byte order mark takes precedence over `label`, and in the absence of
+/// either the text is UTF-8.
+///
+pub(crate) fn decode_text<'a>(bytes: &'a [u8], label: Option<&str>) -> Result<Cow<'a, str>> {
+    let encoding = match (bytes, label) {
+        ([0xEF, 0xBB, 0xBF, ..], _) => Encoding::Utf8,
+        ([0xFE, 0xFF, ..], _) => Encoding::Utf16Be,
+        ([0xFF, 0xFE, ..], _) => Encoding::Utf16Le,
+        (_, None) => Encoding::Utf8,
+        (_, Some(label)) => match label.to_ascii_uppercase().as_str() {
+            "UTF-16" | "UTF-16BE" => Encoding::Utf16Be,
+            "UTF-16LE" => Encoding::Utf16Le,
+            _ => Encoding::from_label(label)?,
+        },
+    };
+    decode_as(bytes, encoding)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Encoding {
+    ///
+    /// The name of this encoding, as it would appear in an XML declaration.
+    ///
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::UsAscii => "US-ASCII",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Iso8859(part) => ISO_8859_NAMES[usize::from(*part - 1)],
+        }
+    }
+
+    ///
+    /// Returns `true` if `label`, from an XML declaration, names this encoding; a label naming
+    /// UTF-16 without a byte order matches either.
+    ///
+    fn is_labelled(&self, label: &str) -> Result<bool> {
+        Ok(match label.to_ascii_uppercase().as_str() {
+            "UTF-16" => matches!(self, Encoding::Utf16Le | Encoding::Utf16Be),
+            "UTF-16LE" => *self == Encoding::Utf16Le,
+            "UTF-16BE" => *self == Encoding::Utf16Be,
+            _ => Encoding::from_label(label)? == *self,
+        })
+    }
+
+    fn from_label(label: &str) -> Result<Self> {
+        match label.to_ascii_uppercase().as_str() {
+            "UTF-8" | "UTF8" => Ok(Encoding::Utf8),
+            "US-ASCII" | "ASCII" => Ok(Encoding::UsAscii),
+            "LATIN1" | "L1" => Ok(Encoding::Iso8859(1)),
+            "LATIN2" | "L2" => Ok(Encoding::Iso8859(2)),
+            "LATIN3" | "L3" => Ok(Encoding::Iso8859(3)),
+            "LATIN4" | "L4" => Ok(Encoding::Iso8859(4)),
+            "CYRILLIC" => Ok(Encoding::Iso8859(5)),
+            "ARABIC" => Ok(Encoding::Iso8859(6)),
+            "GREEK" => Ok(Encoding::Iso8859(7)),
+            "HEBREW" => Ok(Encoding::Iso8859(8)),
+            "LATIN5" | "L5" => Ok(Encoding::Iso8859(9)),
+            "LATIN6" | "L6" => Ok(Encoding::Iso8859(10)),
+            "LATIN-9" | "LATIN9" => Ok(Encoding::Iso8859(15)),
+            "LATIN10" | "L10" => Ok(Encoding::Iso8859(16)),
+            upper => match ["ISO-8859-", "ISO_8859-", "ISO8859-"]
+                .iter()
+                .find_map(|prefix| upper.strip_prefix(prefix))
+                .and_then(|part| part.parse::<u8>().ok())
+            {
+                Some(part) if iso_8859::is_supported(part) => Ok(Encoding::Iso8859(part)),
+                _ => {
+                    error!("encoding '{}' is not supported", label);
+                    Error::UnsupportedEncoding(label.to_string()).into()
+                }
+            },
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn decode_as(bytes: &[u8], encoding: Encoding) -> Result<Cow<'_, str>> {
+    Ok(match encoding {
+        Encoding::Utf8 => {
+            let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+            Cow::Borrowed(std::str::from_utf8(bytes).map_err(|err| {
+                error!("input is not valid UTF-8: {}", err);
+                Error::InvalidCharacter
+            })?)
+        }
+        Encoding::UsAscii => {
+            if let Some(offset) = bytes.iter().position(|b| !b.is_ascii()) {
+                error!("input is not valid US-ASCII at byte {}", offset);
+                return Error::InvalidCharacter.into();
+            }
+            Cow::Borrowed(std::str::from_utf8(bytes).map_err(|_| Error::InvalidCharacter)?)
+        }
+        Encoding::Utf16Le => Cow::Owned(decode_utf16(bytes, u16::from_le_bytes)?),
+        Encoding::Utf16Be => Cow::Owned(decode_utf16(bytes, u16::from_be_bytes)?),
+        Encoding::Iso8859(part) => Cow::Owned(
+            bytes
+                .iter()
+                .map(|b| {
+                    iso_8859::decode_byte(part, *b).ok_or_else(|| {
+                        error!("byte {:#04X} is not defined in {}", b, encoding.name());
+                        Error::InvalidCharacter
+                    })
+                })
+                .collect::<Result<String>>()?,
+        ),
+    })
+}
+
+fn detect(bytes: &[u8]) -> Result<Encoding> {
+    match bytes {
+        [0x00, 0x00, 0xFE, 0xFF, ..]
+        | [0xFF, 0xFE, 0x00, 0x00, ..]
+        | [0x00, 0x00, 0x00, 0x3C, ..]
+        | [0x3C, 0x00, 0x00, 0x00, ..] => {
+            error!("UCS-4 input is not supported");
+            Error::UnsupportedEncoding("UCS-4".to_string()).into()
+        }
+        [0x4C, 0x6F, 0xA7, 0x94, ..] => {
+            error!("EBCDIC input is not supported");
+            Error::UnsupportedEncoding("EBCDIC".to_string()).into()
+        }
+        [0xEF, 0xBB, 0xBF, ..] => Ok(Encoding::Utf8),
+        [0xFE, 0xFF, ..] | [0x00, 0x3C, 0x00, 0x3F, ..] => Ok(Encoding::Utf16Be),
+        [0xFF, 0xFE, ..] | [0x3C, 0x00, 0x3F, 0x00, ..] => Ok(Encoding::Utf16Le),
+        [0x3C, 0x3F, 0x78, 0x6D, ..] => match declared_encoding(bytes) {
+            None => Ok(Encoding::Utf8),
+            Some(label) => Encoding::from_label(label),
+        },
+        _ => Ok(Encoding::Utf8),
+    }
+}
+
+///
+/// Returns the value of `encoding` in the XML declaration at the start of ASCII-compatible input.
+///
+fn declared_encoding(bytes: &[u8]) -> Option<&str> {
+    let end = bytes.windows(2).position(|window| window == b"?>")?;
+    let declaration = std::str::from_utf8(&bytes[..end]).ok()?;
+    let (_, rest) = declaration.split_once(XML_DECL_ENCODING)?;
+    let rest = rest
+        .trim_start_matches(is_xml_space)
+        .strip_prefix('=')?
+        .trim_start_matches(is_xml_space);
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &rest[1..];
+    value.find(quote).map(|end| &value[..end])
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> Result<String> {
+    let pairs = bytes.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        error!("UTF-16 input has an odd number of bytes");
+        return Error::InvalidCharacter.into();
+    }
+    let units = pairs.map(|pair| to_unit([pair[0], pair[1]]));
+    let text = char::decode_utf16(units)
+        .collect::<std::result::Result<String, _>>()
+        .map_err(|err| {
+            error!("input is not valid UTF-16: {}", err);
+            Error::InvalidCharacter
+        })?;
+    Ok(match text.strip_prefix(BYTE_ORDER_MARK) {
+        None => text,
+        Some(rest) => rest.to_string(),
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_utf8() {
+        let (text, encoding) = decode(b"\xEF\xBB\xBF<a>\xC3\xA9</a>").unwrap();
+        assert_eq!(encoding, Encoding::Utf8);
+        assert_eq!(text, "<a>é</a>");
+
+        let (_, encoding) = decode(b"<?xml version='1.0' encoding='us-ascii'?><a/>").unwrap();
+        assert_eq!(encoding, Encoding::UsAscii);
+        assert!(matches!(
+            decode(b"<?xml version='1.0' encoding='US-ASCII'?><a>\xC3\xA9</a>"),
+            Err(Error::InvalidCharacter)
+        ));
+
+        let (_, encoding) =
+            decode(b"\xEF\xBB\xBF<?xml version='1.0' encoding='utf-8'?><a/>").unwrap();
+        assert_eq!(encoding, Encoding::Utf8);
+
+        assert!(decode(b"<a>\xE9</a>").is_err());
+    }
+
+    #[test]
+    fn test_detect_utf16() {
+        let bytes = utf16("\u{FEFF}<a>é</a>", true);
+        let (text, encoding) = decode(&bytes).unwrap();
+        assert_eq!(encoding, Encoding::Utf16Be);
+        assert_eq!(text, "<a>é</a>");
+
+        let bytes = utf16("\u{FEFF}<a>é</a>", false);
+        let (text, encoding) = decode(&bytes).unwrap();
+        assert_eq!(encoding, Encoding::Utf16Le);
+        assert_eq!(text, "<a>é</a>");
+
+        let bytes = utf16("<?xml version='1.0'?><a/>", false);
+        let (text, encoding) = decode(&bytes).unwrap();
+        assert_eq!(encoding, Encoding::Utf16Le);
+        assert_eq!(text, "<?xml version='1.0'?><a/>");
+
+        assert!(decode(&[0xFE, 0xFF, 0x00, 0x3C, 0x00]).is_err());
+
+        for declared in ["UTF-16", "utf-16be"] {
+            let xml = format!("\u{FEFF}<?xml version='1.0' encoding='{}'?><a/>", declared);
+            let (_, encoding) = decode(&utf16(&xml, true)).unwrap();
+            assert_eq!(encoding, Encoding::Utf16Be);
+        }
+    }
+
+    #[test]
+    fn test_detect_mismatch() {
+        assert!(matches!(
+            decode(b"\xEF\xBB\xBF<?xml version='1.0' encoding='ISO-8859-1'?><a/>"),
+            Err(Error::Malformed)
+        ));
+        assert!(matches!(
+            decode(b"\xEF\xBB\xBF<?xml version='1.0' encoding='US-ASCII'?><a/>"),
+            Err(Error::Malformed)
+        ));
+        let bytes = utf16("\u{FEFF}<?xml version='1.0' encoding='UTF-8'?><a/>", false);
+        assert!(matches!(decode(&bytes), Err(Error::Malformed)));
+        let bytes = utf16(
+            "\u{FEFF}<?xml version='1.0' encoding='UTF-16BE'?><a/>",
+            false,
+        );
+        assert!(matches!(decode(&bytes), Err(Error::Malformed)));
+        let bytes = utf16("<?xml version='1.0' encoding='ISO-8859-1'?><a/>", true);
+        assert!(matches!(decode(&bytes), Err(Error::Malformed)));
+
+        // Only the XML declaration names the encoding.
+        let (_, encoding) =
+            decode(b"\xEF\xBB\xBF<?xml-stylesheet encoding='ISO-8859-1'?><a/>").unwrap();
+        assert_eq!(encoding, Encoding::Utf8);
+    }
+
+    #[test]
+    fn test_detect_declared() {
+        let (text, encoding) =
+            decode(b"<?xml version=\"1.0\" encoding = \"ISO-8859-1\"?><a>\xE9\xA4</a>").unwrap();
+        assert_eq!(encoding, Encoding::Iso8859(1));
+        assert!(text.ends_with("<a>é¤</a>"));
+
+        let (text, encoding) =
+            decode(b"<?xml version=\"1.0\" encoding=\"latin-9\"?><a>\xE9\xA4</a>").unwrap();
+        assert_eq!(encoding, Encoding::Iso8859(15));
+        assert!(text.ends_with("<a>é€</a>"));
+
+        let (text, encoding) =
+            decode(b"<?xml version=\"1.0\" encoding=\"ISO-8859-5\"?><a>\xB0\xD1\xF0</a>").unwrap();
+        assert_eq!(encoding, Encoding::Iso8859(5));
+        assert_eq!(encoding.name(), "ISO-8859-5");
+        assert!(text.ends_with("<a>\u{410}\u{431}\u{2116}</a>"));
+
+        let (text, encoding) =
+            decode(b"<?xml version=\"1.0\" encoding=\"latin2\"?><a>\xA1\xB9</a>").unwrap();
+        assert_eq!(encoding, Encoding::Iso8859(2));
+        assert!(text.ends_with("<a>\u{104}\u{161}</a>"));
+
+        // 0xA1 is not defined in ISO-8859-6.
+        assert!(matches!(
+            decode(b"<?xml version=\"1.0\" encoding=\"ISO-8859-6\"?><a>\xA1</a>"),
+            Err(Error::InvalidCharacter)
+        ));
+        assert!(matches!(
+            decode(b"<?xml version=\"1.0\" encoding=\"ISO-8859-12\"?><a/>"),
+            Err(Error::UnsupportedEncoding(_))
+        ));
+
+        assert!(matches!(
+            decode(b"<?xml version=\"1.0\" encoding=\"KOI8-R\"?><a/>"),
+            Err(Error::UnsupportedEncoding(label)) if label == "KOI8-R"
+        ));
+        assert!(matches!(
+            decode(&[0x00, 0x00, 0x00, 0x3C]),
+            Err(Error::UnsupportedEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_text() {
+        assert_eq!(decode_text(b"caf\xC3\xA9", None).unwrap(), "café");
+        assert_eq!(decode_text(b"caf\xE9", Some("iso-8859-1")).unwrap(), "café");
+        assert_eq!(
+            decode_text(&utf16("café", false), Some("UTF-16LE")).unwrap(),
+            "café"
+        );
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(utf16("café", true));
+        assert_eq!(decode_text(&bytes, Some("ISO-8859-1")).unwrap(), "café");
+        assert!(matches!(
+            decode_text(b"text", Some("KOI8-R")),
+            Err(Error::UnsupportedEncoding(_))
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+/*!
+The events reported by the parser, see
+[`read_xml_with_handler`](../fn.read_xml_with_handler.html).
+
+The parser performs all of the checks it performs when constructing a DOM before an event is
+reported; names are resolved against the namespace declarations in scope, references to entities
+are checked against the document type declaration, and the well-formedness of the document is
+verified. The DOM constructed by [`read_xml`](../fn.read_xml.html) is built by one implementation
+of [`ContentHandler`](trait.ContentHandler.html).
+*/
+
+use crate::level2::ext::{SourceLocation, XmlDecl};
+use crate::parser::dtd::{DocTypeDecl, EntityDecl, NotationDecl};
+use crate::parser::Result;
+use crate::shared::name::Name;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// An attribute of an element start tag, as reported to
+/// [`ContentHandler::start_element`](trait.ContentHandler.html#method.start_element).
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedAttribute {
+    /// The name of the attribute, including any resolved namespace URI.
+    pub name: Name,
+    /// The value of the attribute. Character references and references to the predefined
+    /// entities are replaced, references to general entities are retained as `&name;`.
+    pub value: String,
+    /// The location of the attribute in the source text, if locations are being tracked.
+    pub location: Option<SourceLocation>,
+}
+
+///
+/// Implemented to receive the content of a document as it is parsed. Each method has a default
+/// implementation that ignores the event; returning an error from any method stops the parser,
+/// which returns that error.
+///
+/// The `location` passed with an event is only present if the
+/// [`track_locations`](struct.ParserOptions.html#method.track_locations) option is set
+/// and the event is not within the replacement text of an entity.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::ext::SourceLocation;
+/// use xml_dom::level2::Name;
+/// use xml_dom::parser::{read_xml_with_handler, ContentHandler, ParsedAttribute, Result};
+///
+/// #[derive(Default)]
+/// struct Counter {
+///     elements: usize,
+///     namespaced: usize,
+/// }
+///
+/// impl ContentHandler for Counter {
+///     fn start_element(
+///         &mut self,
+///         name: &Name,
+///         _attributes: &[ParsedAttribute],
+///         _location: Option<SourceLocation>,
+///     ) -> Result<()> {
+///         self.elements += 1;
+///         if name.namespace_uri().is_some() {
+///             self.namespaced += 1;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut counter = Counter::default();
+/// read_xml_with_handler(
+///     r#"<a xmlns:p="urn:p"><p:b/><c/></a>"#,
+///     Default::default(),
+///     &mut counter,
+/// )
+/// .unwrap();
+/// assert_eq!(counter.elements, 3);
+/// assert_eq!(counter.namespaced, 1);
+/// ```
+///
+pub trait ContentHandler {
+    ///
+    /// The parser is about to report the content of a document.
+    ///
+    fn start_document(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// The end of the input has been reached, no more events will be reported.
+    ///
+    fn end_document(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// The XML declaration, this is reported before any other content.
+    ///
+    fn xml_declaration(&mut self, _declaration: &XmlDecl) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// The document type declaration, with the general entity and notation declarations in its
+    /// internal subset. Only the first declaration of an entity is included.
+    ///
+    fn document_type(
+        &mut self,
+        _declaration: &DocTypeDecl,
+        _entities: &[EntityDecl],
+        _notations: &[NotationDecl],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// An element start tag, or an empty element tag which is followed immediately by a call to
+    /// `end_element`.
+    ///
+    fn start_element(
+        &mut self,
+        _name: &Name,
+        _attributes: &[ParsedAttribute],
+        _location: Option<SourceLocation>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// An element end tag; for an empty element tag the location is that of the whole tag.
+    ///
+    fn end_element(&mut self, _name: &Name, _location: Option<SourceLocation>) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// Character data with all references to the predefined entities and character references
+    /// replaced. Text is trimmed as described by
+    /// [`trim_text`](struct.ParserOptions.html#method.trim_text), adjacent calls are not
+    /// merged.
+    ///
+    fn characters(&mut self, _text: &str, _location: Option<SourceLocation>) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// The content of a CDATA section, unless the
+    /// [`cdata_as_text`](struct.ParserOptions.html#method.cdata_as_text) option is set
+    /// in which case it is reported by `characters`.
+    ///
+    fn cdata(&mut self, _text: &str, _location: Option<SourceLocation>) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// A comment, unless the
+    /// [`keep_comments`](struct.ParserOptions.html#method.keep_comments) option is
+    /// `false`.
+    ///
+    fn comment(&mut self, _text: &str, _location: Option<SourceLocation>) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// A processing instruction, unless the
+    /// [`keep_processing_instructions`](struct.ParserOptions.html#method.keep_processing_instructions)
+    /// option is `false`.
+    ///
+    fn processing_instruction(
+        &mut self,
+        _target: &str,
+        _data: Option<&str>,
+        _location: Option<SourceLocation>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// A reference to a general entity; the events for its parsed replacement text follow, ending
+    /// with a call to `end_entity`. There are no events between the two for an external entity
+    /// unless it is loaded by the
+    /// [`external_resolver`](struct.ParserOptions.html#method.external_resolver).
+    ///
+    fn start_entity(&mut self, _name: &str, _location: Option<SourceLocation>) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// The end of the replacement text of the entity reported by the matching `start_entity`.
+    ///
+    fn end_entity(&mut self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+}
@@ -3,9 +3,48 @@ Provides a basic parser from text to DOM using the [quick-xml](https://crates.io
 crate.
 
 The parsing capability of quick-xml is limited in some ways, it does not support DTD handling other
-than returning the entire DTD content as a string; therefore entities, notations, or entity
-references are not constructed in the DOM. It does parse `Text`, `CDataSection`, and `Comment` nodes
-but does limited entity processing or escaping.
+than returning the entire DTD content as a string. The document type declaration is parsed into a
+`DocumentType` node, with its name, public and system identifiers, and internal subset; entity and
+notation declarations in the internal subset are added to the `DocumentType` as `Entity` and
+`Notation` nodes. It does parse `Text`, `CDataSection`, and `Comment` nodes.
+
+References to general entities in content become `EntityReference` nodes whose children are the
+parsed replacement text of the entity. If the document's `ProcessingOptions` has `parse_entities`
+set the references are instead expanded in place. Character references and references to the
+predefined entities are always replaced.
+
+External entities, and the external subset, are not loaded unless an
+[`ExternalResolver`](trait.ExternalResolver.html) is provided in the parser options; a
+[`Catalog`](struct.Catalog.html) may be used to map their identifiers to local files.
+
+Element and attribute names are resolved against the namespace declarations in scope, so that
+elements and attributes have the correct `namespace_uri`; a reference to an undeclared prefix is
+an error.
+
+By default leading and trailing whitespace is trimmed from text, and whitespace-only text is
+dropped, except within elements with `xml:space="preserve"` in scope; this and other behavior of
+the parser can be changed using [`ParserOptions`](struct.ParserOptions.html) with
+[`read_xml_with_options`](fn.read_xml_with_options.html) or
+[`read_reader_with_options`](fn.read_reader_with_options.html).
+
+The content of a document may also be processed without constructing a DOM, by implementing
+[`ContentHandler`](trait.ContentHandler.html) and using
+[`read_xml_with_handler`](fn.read_xml_with_handler.html) or
+[`read_reader_with_handler`](fn.read_reader_with_handler.html). The handler receives the same
+resolved names, and the input is subject to the same checks, as when constructing a DOM.
+
+Input that is not well-formed may instead be parsed with
+[`read_xml_recovering`](fn.read_xml_recovering.html) or
+[`read_reader_recovering`](fn.read_reader_recovering.html), which recover from unclosed elements,
+mismatched end tags, and ampersands that do not start a reference, returning the document with
+a [`Warning`](struct.Warning.html) for each problem rather than failing.
+
+The resources used by the parser are limited, to protect against input that expands to a
+document far larger than itself; by default entity references may be nested 20 deep and expand to
+at most 10 MiB in total, elements may be nested 256 deep, and an element may have at most 1024
+attributes. Exceeding a limit returns
+[`Error::LimitExceeded`](enum.Error.html#variant.LimitExceeded), the limits are changed using
+[`ParserOptions`](struct.ParserOptions.html).
 
 # Example
 
@@ -18,19 +57,71 @@ assert!(dom.is_ok());
 
 */
 
-use crate::level2::convert::as_document_mut;
-use crate::level2::ext::{XmlDecl, XmlVersion};
-use crate::level2::node_impl::Extension;
+use crate::level2::convert::{as_document, as_document_type, as_element, as_entity};
+use crate::level2::ext::{
+    DocumentDecl, Namespaced, SourceLocation, SpaceHandled, SpaceHandling, XmlDecl, XmlVersion,
+};
 use crate::level2::*;
 use crate::shared::error::Error as DOMError;
-use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use crate::shared::syntax::{
+    XMLNS_NS_ATTRIBUTE, XMLNS_NS_URI, XML_DECL_START, XML_ENTITYREF_END, XML_ENTITYREF_START,
+    XML_NS_ATTRIBUTE, XML_NS_ATTR_SPACE, XML_NS_SEPARATOR, XML_NS_URI, XML_PI_END,
+};
+use crate::shared::text::{
+    is_xml_chars, is_xml_literal_char, is_xml_name, is_xml_space, EntityLimits,
+};
+use quick_xml::events::attributes::AttrError;
+use quick_xml::events::{BytesCData, BytesDecl, BytesStart, BytesText, Event};
 use quick_xml::reader::Reader;
-use std::borrow::Borrow;
-use std::io::BufRead;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{BufRead, Read};
 use std::str::FromStr;
 
+use builder::DocumentBuilder;
+use position::{PositionReader, SourceRead};
 use thiserror::Error as E;
 
+// ------------------------------------------------------------------------------------------------
+// Public Modules
+// ------------------------------------------------------------------------------------------------
+
+pub mod xinclude;
+
+// ------------------------------------------------------------------------------------------------
+// Private Modules
+// ------------------------------------------------------------------------------------------------
+
+mod builder;
+
+mod catalog;
+pub use catalog::Catalog;
+
+mod dtd;
+pub use dtd::{DocTypeDecl, EntityDecl, NotationDecl};
+
+mod encoding;
+
+mod iso_8859;
+
+mod handler;
+pub use handler::{ContentHandler, ParsedAttribute};
+
+mod options;
+pub use options::{Limit, ParserOptions};
+
+mod position;
+pub use crate::level2::ext::Position;
+
+mod reference;
+
+mod resolver;
+pub use resolver::{ExternalResolver, FileResolver};
+
+mod stream;
+pub use stream::Subtrees;
+
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
@@ -40,18 +131,53 @@ use thiserror::Error as E;
 ///
 #[derive(Debug, E)]
 pub enum Error {
-    /// Usually a missing quote.
+    /// A character, or name, that is not allowed by the version of XML of the document, or a
+    /// missing quote.
     #[error("invalid character")]
     InvalidCharacter,
     /// Everything else.
     #[error("malformed")]
     Malformed,
+    /// The input uses a character encoding the parser cannot decode.
+    #[error("unsupported encoding {0:?}")]
+    UnsupportedEncoding(String),
+    /// One of the limits set in `ParserOptions` was exceeded.
+    #[error("limit on {0} exceeded")]
+    LimitExceeded(Limit),
     /// Errors passed through from DOMError
     #[error("DOM error: {0}")]
     DOMError(#[from] DOMError),
     /// Errors passed through from quick-xml
     #[error("quick-xml error: {0}")]
     QuickXMLError(#[from] quick_xml::Error),
+    /// Errors loading an external entity.
+    #[error("I/O error: {0}")]
+    IOError(#[from] std::io::Error),
+    /// An `xi:include` could not be processed, see the [`xinclude`](xinclude/index.html) module.
+    #[error("XInclude error: {0}")]
+    XInclude(String),
+    /// An error, with the location in the input at which it was detected.
+    #[error("{source} at {position}, near {snippet:?}")]
+    Positioned {
+        /// The error detected.
+        source: Box<Error>,
+        /// The location of the error.
+        position: Position,
+        /// The input surrounding `position`.
+        snippet: String,
+    },
+}
+
+///
+/// A problem in the input that the parser recovered from, see
+/// [`read_xml_recovering`](fn.read_xml_recovering.html).
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    /// A description of the problem, and how it was recovered from.
+    pub message: String,
+    /// The location in the input at which the problem was detected, if known.
+    pub position: Option<Position>,
 }
 
 ///
@@ -59,6 +185,57 @@ pub enum Error {
 ///
 pub type Result<T> = std::result::Result<T, Error>;
 
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The state inherited by the content of an element, linked to the scope of its parent element.
+///
+#[derive(Debug, Default)]
+struct Scope<'a> {
+    parent: Option<&'a Scope<'a>>,
+    space: SpaceHandling,
+    /// Namespace declarations on this element, an empty URI un-declares the default namespace.
+    namespaces: Vec<(Option<String>, String)>,
+    /// The element a fragment is parsed within, prefixes not declared within the fragment are
+    /// resolved against it.
+    context_element: Option<&'a RefNode>,
+}
+
+///
+/// The state of the parser that is independent of the nesting of elements.
+///
+#[derive(Debug)]
+struct Context {
+    options: ParserOptions,
+    /// The general entities declared in the internal subset, by name.
+    entities: HashMap<String, EntityDecl>,
+    /// The entities whose replacement text is being parsed, used to detect recursion.
+    open_entities: Vec<String>,
+    /// The total size of the replacement text of all entity references so far.
+    expanded_bytes: usize,
+    /// The number of elements currently open.
+    element_depth: usize,
+    /// The number of nodes reported so far.
+    node_count: usize,
+    /// The version of XML from the XML declaration, which determines the characters allowed.
+    version: XmlVersion,
+    /// The qualified names of the elements currently open.
+    open_elements: Vec<String>,
+    /// When recovering, the warnings reported so far; `None` if errors are not recovered from.
+    warnings: Option<Vec<Warning>>,
+    /// When recovering, the end tag of an enclosing element that was found before the end tag of
+    /// the current element.
+    pending_end: Option<String>,
+    /// The position in the input of the event, or reference, being processed; errors are reported
+    /// at this position.
+    error_position: Option<Position>,
+    /// The position in the input of the first byte in the event buffer, if the event reports
+    /// errors within itself; the error snippet is then taken from the event buffer.
+    event_bytes_start: Option<Position>,
+}
+
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
@@ -68,7 +245,27 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// can be safely assumed to be a `Document` node.
 ///
 pub fn read_xml(xml: impl AsRef<str>) -> Result<RefNode> {
-    inner_read(&mut Reader::from_str(xml.as_ref()))
+    read_xml_with_options(xml, Default::default())
+}
+
+///
+/// Parse the provided string into a DOM structure, as `read_xml`, using the provided parser
+/// options.
+///
+pub fn read_xml_with_options(xml: impl AsRef<str>, options: ParserOptions) -> Result<RefNode> {
+    inner_read(xml.as_ref().as_bytes(), options)
+}
+
+///
+/// Parse the provided string reporting its content to `handler`, rather than constructing a DOM
+/// structure. The `processing_options` and `implementation` in `options` are not used.
+///
+pub fn read_xml_with_handler<H: ContentHandler>(
+    xml: impl AsRef<str>,
+    options: ParserOptions,
+    handler: &mut H,
+) -> Result<()> {
+    inner_parse(xml.as_ref().as_bytes(), options, handler)
 }
 
 ///
@@ -76,7 +273,286 @@ pub fn read_xml(xml: impl AsRef<str>) -> Result<RefNode> {
 /// can be safely assumed to be a `Document` node.
 ///
 pub fn read_reader<B: BufRead>(reader: B) -> Result<RefNode> {
-    inner_read(&mut Reader::from_reader(reader))
+    read_reader_with_options(reader, Default::default())
+}
+
+///
+/// Parse the provided reader into a DOM structure, as `read_reader`, using the provided parser
+/// options.
+///
+pub fn read_reader_with_options<B: BufRead>(reader: B, options: ParserOptions) -> Result<RefNode> {
+    inner_read(reader, options)
+}
+
+///
+/// Parse the provided reader reporting its content to `handler`, as `read_xml_with_handler`.
+///
+pub fn read_reader_with_handler<B: BufRead, H: ContentHandler>(
+    reader: B,
+    options: ParserOptions,
+    handler: &mut H,
+) -> Result<()> {
+    inner_parse(reader, options, handler)
+}
+
+///
+/// Parse the provided string into a DOM structure, recovering from errors rather than failing;
+/// the document is returned with a warning for each problem recovered from.
+///
+/// Elements that are not closed are closed at the end tag of an enclosing element, or at the end
+/// of the input, end tags that do not match any open element are ignored, and an ampersand that
+/// does not start a reference is treated as text, as is a reference to an undeclared entity.
+/// Other errors are not recovered from.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::as_document;
+/// use xml_dom::level2::{Document, Node};
+/// use xml_dom::parser::read_xml_recovering;
+///
+/// let (dom, warnings) = read_xml_recovering("<feed><item>AT&T</feed>").unwrap();
+/// let feed = as_document(&dom).unwrap().document_element().unwrap();
+/// let item = feed.first_child().unwrap();
+/// assert_eq!(item.first_child().unwrap().node_value(), Some("AT&T".to_string()));
+/// assert_eq!(warnings.len(), 2);
+/// ```
+///
+pub fn read_xml_recovering(xml: impl AsRef<str>) -> Result<(RefNode, Vec<Warning>)> {
+    read_xml_recovering_with_options(xml, Default::default())
+}
+
+///
+/// Parse the provided string into a DOM structure, as `read_xml_recovering`, using the provided
+/// parser options.
+///
+pub fn read_xml_recovering_with_options(
+    xml: impl AsRef<str>,
+    options: ParserOptions,
+) -> Result<(RefNode, Vec<Warning>)> {
+    inner_read_recovering(xml.as_ref().as_bytes(), options)
+}
+
+///
+/// Parse the provided reader into a DOM structure, recovering from errors as
+/// `read_xml_recovering`.
+///
+pub fn read_reader_recovering<B: BufRead>(reader: B) -> Result<(RefNode, Vec<Warning>)> {
+    read_reader_recovering_with_options(reader, Default::default())
+}
+
+///
+/// Parse the provided reader into a DOM structure, as `read_reader_recovering`, using the
+/// provided parser options.
+///
+pub fn read_reader_recovering_with_options<B: BufRead>(
+    reader: B,
+    options: ParserOptions,
+) -> Result<(RefNode, Vec<Warning>)> {
+    inner_read_recovering(reader, options)
+}
+
+///
+/// Parse the provided reader, returning an iterator over the subtrees rooted at the elements that
+/// match `path`. Each subtree is parsed into a new `Document`, with the XML and document type
+/// declarations of the input, and is not retained by the iterator; this allows documents far
+/// larger than available memory to be processed a subtree at a time.
+///
+/// A path is a sequence of qualified element names, as they appear in the input, separated by
+/// `/`. A path starting with `/` is matched from the document element, otherwise it matches at any
+/// depth; elements within a matched subtree are not themselves matched. Names within each subtree
+/// are resolved against the namespace declarations of its enclosing elements.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::{as_document, as_element};
+/// use xml_dom::level2::{Document, Element};
+/// use xml_dom::parser::{read_subtrees, ParserOptions};
+///
+/// let xml = "<records><record id='1'/><record id='2'><name>Two</name></record></records>";
+/// let mut ids: Vec<String> = Vec::new();
+/// for document in read_subtrees(xml.as_bytes(), "/records/record", ParserOptions::new()).unwrap() {
+///     let document = document.unwrap();
+///     let record = as_document(&document).unwrap().document_element().unwrap();
+///     ids.push(as_element(&record).unwrap().get_attribute("id").unwrap());
+/// }
+/// assert_eq!(ids, vec!["1", "2"]);
+/// ```
+///
+pub fn read_subtrees<B: BufRead>(
+    reader: B,
+    path: &str,
+    options: ParserOptions,
+) -> Result<Subtrees<B>> {
+    Subtrees::new(reader, path, options)
+}
+
+///
+/// Parse the provided text as content of the element `context`, returning a new
+/// `DocumentFragment` owned by `document` that may then be inserted into the document. The text
+/// may contain any number of elements, and text, at the top level.
+///
+/// Prefixes that are not declared within the text are resolved using
+/// [`Namespaced::resolve_namespace`](../level2/ext/trait.Namespaced.html#tymethod.resolve_namespace)
+/// on `context`, which requires that the document was created with the `add_namespaces`
+/// processing option. References to entities are resolved against the document's
+/// `DocumentType`, and whitespace is handled according to the `xml:space` in scope at `context`.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::as_document;
+/// use xml_dom::level2::{Document, Node};
+/// use xml_dom::parser::{parse_fragment, read_xml};
+///
+/// let document = read_xml("<list><item>one</item></list>").unwrap();
+/// let mut list = as_document(&document).unwrap().document_element().unwrap();
+/// let fragment = parse_fragment(&document, &list, "<item>two</item><item>three</item>").unwrap();
+/// let _ = list.append_child(fragment).unwrap();
+/// assert_eq!(list.child_nodes().len(), 3);
+/// ```
+///
+pub fn parse_fragment(
+    document: &RefNode,
+    context: &RefNode,
+    xml: impl AsRef<str>,
+) -> Result<RefNode> {
+    parse_fragment_with_options(document, context, xml, Default::default())
+}
+
+///
+/// Parse the provided text into a `DocumentFragment`, as `parse_fragment`, using the provided
+/// parser options. The `processing_options` and `implementation` in `options` are not used, the
+/// document's own options apply.
+///
+pub fn parse_fragment_with_options(
+    document: &RefNode,
+    context: &RefNode,
+    xml: impl AsRef<str>,
+    options: ParserOptions,
+) -> Result<RefNode> {
+    let _safe_to_ignore = as_element(context)?;
+    if context.owner_document().as_ref() != Some(document) {
+        error!("context element is not owned by the document");
+        return Error::from(DOMError::WrongDocument).into();
+    }
+    let fragment = as_document(document)?.create_document_fragment()?;
+
+    let mut builder = DocumentBuilder::for_fragment(document, fragment.clone());
+    let mut reader = source_reader(xml.as_ref().as_bytes());
+    let mut event_buffer: Vec<u8> = Vec::new();
+    let mut parse_context = Context::new(options);
+    parse_context.entities = declared_entities(document);
+    if let Some(xml_decl) = document.xml_declaration() {
+        parse_context.version = xml_decl.version();
+    }
+    let scope = Scope {
+        space: context.space_handling(),
+        context_element: Some(context),
+        ..Default::default()
+    };
+
+    element(
+        &mut reader,
+        &mut event_buffer,
+        &mut parse_context,
+        &mut builder,
+        None,
+        &scope,
+    )
+    .map_err(|err| positioned(&mut reader, &parse_context, &event_buffer, err))?;
+    Ok(fragment)
+}
+
+///
+/// Parse the provided bytes into a DOM structure, detecting their character encoding as described
+/// in XML 1.0 [Appendix F](https://www.w3.org/TR/xml/#sec-guessing). UTF-8, US-ASCII, UTF-16,
+/// and ISO-8859 (other than ISO-8859-12) input is supported; other encodings return
+/// `Error::UnsupportedEncoding`. An encoding declared by the XML declaration that does not match a
+/// byte order mark, or the encoding detected from the first bytes, returns `Error::Malformed`.
+///
+/// The detected encoding is recorded on the document's XML declaration if the declaration does
+/// not specify one, a declaration is added for input that is not UTF-8 if there is none. Error
+/// positions refer to the decoded text, not the original bytes.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::ext::DocumentDecl;
+/// use xml_dom::parser::read_bytes;
+///
+/// let bytes: Vec<u8> = "\u{FEFF}<xml>é</xml>"
+///     .encode_utf16()
+///     .flat_map(u16::to_le_bytes)
+///     .collect();
+/// let dom = read_bytes(bytes).unwrap();
+/// let declaration = dom.xml_declaration().unwrap();
+/// assert_eq!(declaration.encoding(), Some("UTF-16LE".to_string()));
+/// ```
+///
+pub fn read_bytes(bytes: impl AsRef<[u8]>) -> Result<RefNode> {
+    read_bytes_with_options(bytes, Default::default())
+}
+
+///
+/// Parse the provided bytes into a DOM structure, as `read_bytes`, using the provided parser
+/// options.
+///
+pub fn read_bytes_with_options(bytes: impl AsRef<[u8]>, options: ParserOptions) -> Result<RefNode> {
+    let (text, encoding) = encoding::decode(bytes.as_ref())?;
+    let mut document = inner_read(text.as_bytes(), options)?;
+    record_encoding(&mut document, encoding)?;
+    Ok(document)
+}
+
+///
+/// Parse the content of the provided reader into a DOM structure, detecting the character
+/// encoding as `read_bytes`. The entire content is read before parsing.
+///
+pub fn read_encoded_reader<R: Read>(reader: R) -> Result<RefNode> {
+    read_encoded_reader_with_options(reader, Default::default())
+}
+
+///
+/// Parse the content of the provided reader into a DOM structure, as `read_encoded_reader`, using
+/// the provided parser options.
+///
+pub fn read_encoded_reader_with_options<R: Read>(
+    mut reader: R,
+    options: ParserOptions,
+) -> Result<RefNode> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let _safe_to_ignore = reader
+        .read_to_end(&mut bytes)
+        .map_err(quick_xml::Error::from)?;
+    read_bytes_with_options(bytes, options)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Error {
+    ///
+    /// Returns the location in the input at which this error was detected, if known.
+    ///
+    pub fn position(&self) -> Option<&Position> {
+        match self {
+            Self::Positioned { position, .. } => Some(position),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns the error detected without any location information.
+    ///
+    pub fn inner(&self) -> &Self {
+        match self {
+            Self::Positioned { source, .. } => source.inner(),
+            _ => self,
+        }
+    }
 }
 
 impl<T> From<Error> for Result<T> {
@@ -85,16 +561,410 @@ impl<T> From<Error> for Result<T> {
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.position {
+            None => write!(f, "{}", self.message),
+            Some(position) => write!(f, "{} at {}", self.message, position),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Scope<'_> {
+    ///
+    /// Returns the namespace URI `prefix` is bound to in this scope, if any.
+    ///
+    fn resolve_namespace(&self, prefix: Option<&str>) -> Option<Cow<'_, str>> {
+        match prefix {
+            Some(XML_NS_ATTRIBUTE) => return Some(Cow::Borrowed(XML_NS_URI)),
+            Some(XMLNS_NS_ATTRIBUTE) => return Some(Cow::Borrowed(XMLNS_NS_URI)),
+            _ => {}
+        }
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            if let Some((_, namespace_uri)) = current
+                .namespaces
+                .iter()
+                .find(|(declared, _)| declared.as_deref() == prefix)
+            {
+                return Some(Cow::Borrowed(namespace_uri.as_str())).filter(|uri| !uri.is_empty());
+            }
+            if let Some(context_element) = current.context_element {
+                return Namespaced::resolve_namespace(context_element, prefix).map(Cow::Owned);
+            }
+            scope = current.parent;
+        }
+        None
+    }
+
+    ///
+    /// Returns the namespace URI of an element or attribute name in this scope. Un-prefixed
+    /// attribute names, other than `xmlns` itself, have no namespace.
+    ///
+    fn namespace_for(
+        &self,
+        qualified_name: &str,
+        is_attribute: bool,
+    ) -> Result<Option<Cow<'_, str>>> {
+        match qualified_name.split_once(XML_NS_SEPARATOR) {
+            None if is_attribute => {
+                Ok(Some(Cow::Borrowed(XMLNS_NS_URI))
+                    .filter(|_| qualified_name == XMLNS_NS_ATTRIBUTE))
+            }
+            None => Ok(self.resolve_namespace(None)),
+            Some((prefix, _)) => match self.resolve_namespace(Some(prefix)) {
+                None => {
+                    error!("namespace prefix '{}' is not declared", prefix);
+                    Error::from(DOMError::Namespace).into()
+                }
+                found => Ok(found),
+            },
+        }
+    }
+
+    ///
+    /// Returns the name of an element or attribute, with the namespace URI it has in this scope if
+    /// the parser is namespace aware.
+    ///
+    fn name_for(
+        &self,
+        qualified_name: &str,
+        is_attribute: bool,
+        options: &ParserOptions,
+    ) -> Result<Name> {
+        if !is_xml_name(qualified_name) {
+            error!("invalid name {:?}", qualified_name);
+            return Error::InvalidCharacter.into();
+        }
+        let namespace_uri = if options.namespace_aware {
+            self.namespace_for(qualified_name, is_attribute)?
+        } else {
+            None
+        };
+        Ok(match namespace_uri {
+            None => Name::from_str(qualified_name)?,
+            Some(namespace_uri) => Name::new_ns(namespace_uri.as_ref(), qualified_name)?,
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Context {
+    fn new(options: ParserOptions) -> Self {
+        Self {
+            options,
+            entities: Default::default(),
+            open_entities: Default::default(),
+            expanded_bytes: 0,
+            element_depth: 0,
+            node_count: 0,
+            version: XmlVersion::V10,
+            open_elements: Default::default(),
+            warnings: None,
+            pending_end: None,
+            error_position: None,
+            event_bytes_start: None,
+        }
+    }
+
+    ///
+    /// Record the position of the event about to be read from `reader`, and return it; the
+    /// replacement text of an entity has no position, errors within it are reported at the
+    /// position of the reference.
+    ///
+    fn event_start<T: SourceRead>(&mut self, reader: &Reader<T>) -> Option<Position> {
+        let position = reader.get_ref().position_at(reader.buffer_position());
+        if position.is_some() {
+            self.error_position = position;
+            self.event_bytes_start = None;
+        }
+        position
+    }
+
+    ///
+    /// Report errors at `position`, within the current event whose content in the event buffer
+    /// starts at `bytes_start`. There are no positions within the replacement text of an entity,
+    /// so errors there remain at the position of the reference.
+    ///
+    fn error_within(&mut self, bytes_start: Option<Position>, position: Option<Position>) {
+        if let (Some(bytes_start), Some(position)) = (bytes_start, position) {
+            self.error_position = Some(position);
+            self.event_bytes_start = Some(bytes_start);
+        }
+    }
+
+    ///
+    /// The start of the location of a node, if locations are being tracked.
+    ///
+    fn location_start(&self, position: Option<Position>) -> Option<Position> {
+        position.filter(|_| self.options.track_locations)
+    }
+
+    fn is_recovering(&self) -> bool {
+        self.warnings.is_some()
+    }
+
+    ///
+    /// Report a problem with the input at the current position of `reader`; returns `true` if the
+    /// problem is recorded as a warning and parsing should continue, or `false` if it is an
+    /// error.
+    ///
+    fn recover<T: SourceRead>(&mut self, reader: &Reader<T>, message: String) -> bool {
+        match &mut self.warnings {
+            None => {
+                error!("{}", message);
+                false
+            }
+            Some(warnings) => {
+                warn!("{}", message);
+                warnings.push(Warning {
+                    message,
+                    position: reader.get_ref().position_at(reader.buffer_position()),
+                });
+                true
+            }
+        }
+    }
+
+    ///
+    /// Returns an error if `text`, as it appears in the input, contains a character that may not
+    /// appear literally in this version of XML.
+    ///
+    fn check_literal(&self, text: &str) -> Result<()> {
+        match text
+            .chars()
+            .find(|c| !is_xml_literal_char(*c, self.version))
+        {
+            None => Ok(()),
+            Some(c) => {
+                error!("character {:?} is not allowed in XML {}", c, self.version);
+                Error::InvalidCharacter.into()
+            }
+        }
+    }
+
+    ///
+    /// Returns an error if `text`, with any character references replaced, contains a character
+    /// that does not match the `Char` production of this version of XML.
+    ///
+    fn check_chars(&self, text: &str) -> Result<()> {
+        if is_xml_chars(text, self.version) {
+            Ok(())
+        } else {
+            error!(
+                "text contains a character not allowed in XML {}",
+                self.version
+            );
+            Error::InvalidCharacter.into()
+        }
+    }
+
+    ///
+    /// Returns an error if `value` is greater than the maximum allowed for `limit`.
+    ///
+    fn check_limit(&self, limit: Limit, value: usize) -> Result<()> {
+        let maximum = self.options.limit(limit);
+        if value > maximum {
+            error!("limit on {} of {} exceeded", limit, maximum);
+            Error::LimitExceeded(limit).into()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_nodes(&mut self, count: usize) -> Result<()> {
+        self.node_count = self.node_count.saturating_add(count);
+        self.check_limit(Limit::Nodes, self.node_count)
+    }
+
+    ///
+    /// Start expanding the replacement text of the entity `name`, checking for recursion and that
+    /// the limits on entity expansion are not exceeded.
+    ///
+    fn enter_entity(&mut self, name: &str, replacement: &str) -> Result<()> {
+        if self.open_entities.iter().any(|open| open == name) {
+            error!("recursive reference to entity '{}'", name);
+            return Error::Malformed.into();
+        }
+        self.check_limit(Limit::EntityDepth, self.open_entities.len() + 1)?;
+        self.expanded_bytes = self.expanded_bytes.saturating_add(replacement.len());
+        self.check_limit(Limit::EntityExpansion, self.expanded_bytes)?;
+        self.open_entities.push(name.to_string());
+        Ok(())
+    }
+
+    ///
+    /// References within attribute values are only expanded when the value is retrieved, so the
+    /// expansion of a reference to `name` is checked, but not performed, as the document is
+    /// parsed.
+    ///
+    fn check_attribute_entity(&mut self, name: &str) -> Result<()> {
+        let replacement = match self
+            .entities
+            .get(name)
+            .and_then(|entity| entity.value.clone())
+        {
+            None => {
+                error!(
+                    "attribute references undeclared or external entity '{}'",
+                    name
+                );
+                return Error::Malformed.into();
+            }
+            Some(replacement) => replacement,
+        };
+        self.enter_entity(name, &replacement)?;
+        for part in reference::split_references(&replacement, self.version)? {
+            match part {
+                reference::TextPart::Text(text) => self.check_chars(&text)?,
+                reference::TextPart::EntityRef(name) => self.check_attribute_entity(name)?,
+            }
+        }
+        let _safe_to_ignore = self.open_entities.pop();
+        Ok(())
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
-fn inner_read<T: BufRead>(reader: &mut Reader<T>) -> Result<RefNode> {
-    let _safe_to_ignore = reader.trim_text(true);
+fn inner_read<B: BufRead>(reader: B, options: ParserOptions) -> Result<RefNode> {
+    let mut builder = DocumentBuilder::new(&options)?;
+    inner_parse(reader, options, &mut builder)?;
+    Ok(builder.into_document())
+}
+
+fn inner_read_recovering<B: BufRead>(
+    reader: B,
+    options: ParserOptions,
+) -> Result<(RefNode, Vec<Warning>)> {
+    let mut builder = DocumentBuilder::new(&options)?;
+    //
+    // End tags are matched to start tags by `element`, so that mismatches can be recovered from.
+    //
+    let mut reader = source_reader(reader);
+    let _safe_to_ignore = reader.check_end_names(false);
+
+    let mut event_buffer: Vec<u8> = Vec::new();
+
+    let mut context = Context::new(options);
+    context.warnings = Some(Vec::new());
+
+    document(&mut reader, &mut event_buffer, &mut context, &mut builder)
+        .map_err(|err| positioned(&mut reader, &context, &event_buffer, err))?;
+    Ok((
+        builder.into_document(),
+        context.warnings.unwrap_or_default(),
+    ))
+}
+
+fn inner_parse<B: BufRead, H: ContentHandler>(
+    reader: B,
+    options: ParserOptions,
+    handler: &mut H,
+) -> Result<()> {
+    let mut reader = source_reader(reader);
 
     let mut event_buffer: Vec<u8> = Vec::new();
 
-    document(reader, &mut event_buffer)
+    let mut context = Context::new(options);
+
+    document(&mut reader, &mut event_buffer, &mut context, handler)
+        .map_err(|err| positioned(&mut reader, &context, &event_buffer, err))
+}
+
+fn source_reader<B: BufRead>(reader: B) -> Reader<PositionReader<B>> {
+    //
+    // Text is not trimmed by the reader, `handle_text` trims it (if required) so that text
+    // locations are exact.
+    //
+    Reader::from_reader(PositionReader::new(reader))
+}
+
+///
+/// Wrap `err` with the position of the event, or reference, being processed when it was detected,
+/// or if none the current position in the input.
+///
+fn positioned<B: BufRead>(
+    reader: &mut Reader<PositionReader<B>>,
+    context: &Context,
+    event_buffer: &[u8],
+    err: Error,
+) -> Error {
+    let byte_offset = reader.buffer_position();
+    let position_reader = reader.get_mut();
+    let position = context
+        .error_position
+        .or_else(|| position_reader.position_at(byte_offset))
+        .unwrap_or_default();
+    let event_offset = context
+        .event_bytes_start
+        .and_then(|bytes_start| position.byte_offset.checked_sub(bytes_start.byte_offset))
+        .filter(|offset| *offset <= event_buffer.len());
+    Error::Positioned {
+        source: Box::new(err),
+        position,
+        snippet: match event_offset {
+            None => position_reader.snippet(),
+            Some(offset) => position::snippet_at(event_buffer, offset),
+        },
+    }
+}
+
+///
+/// The general entities declared by the document's `DocumentType`, for parsing a fragment.
+///
+fn declared_entities(document: &RefNode) -> HashMap<String, EntityDecl> {
+    let doc_type = match as_document(document)
+        .ok()
+        .and_then(|document| document.doc_type())
+    {
+        None => return Default::default(),
+        Some(doc_type) => doc_type,
+    };
+    let entities = as_document_type(&doc_type).unwrap().entities();
+    entities
+        .iter()
+        .map(|(name, entity_node)| {
+            let entity = as_entity(entity_node).unwrap();
+            let system_id = entity.system_id();
+            let declaration = EntityDecl {
+                name: name.to_string(),
+                is_parameter: false,
+                value: entity_node.node_value().filter(|_| system_id.is_none()),
+                public_id: entity.public_id(),
+                system_id,
+                notation_name: entity.notation_name(),
+                base_uri: None,
+            };
+            (name.to_string(), declaration)
+        })
+        .collect()
+}
+
+///
+/// Record the encoding detected by `read_bytes` in the document's XML declaration.
+///
+fn record_encoding(document: &mut RefNode, encoding: encoding::Encoding) -> Result<()> {
+    let declaration = match document.xml_declaration() {
+        Some(declaration) if declaration.encoding().is_none() => XmlDecl::new(
+            declaration.version(),
+            Some(encoding.name().to_string()),
+            declaration.standalone(),
+        ),
+        None if encoding != encoding::Encoding::Utf8 => {
+            XmlDecl::new(XmlVersion::V10, Some(encoding.name().to_string()), None)
+        }
+        _ => return Ok(()),
+    };
+    document.set_xml_declaration(declaration)?;
+    Ok(())
 }
 
 ///
@@ -118,60 +988,85 @@ fn inner_read<T: BufRead>(reader: &mut Reader<T>) -> Result<RefNode> {
 /// S                 ::= (#x20 | #x9 | #xD | #xA)+
 /// ```
 ///
-fn document<T: BufRead>(reader: &mut Reader<T>, event_buffer: &mut Vec<u8>) -> Result<RefNode> {
-    let mut document = get_implementation()
-        .create_document(None, None, None)
-        .unwrap();
+fn document<T: SourceRead, H: ContentHandler>(
+    reader: &mut Reader<T>,
+    event_buffer: &mut Vec<u8>,
+    context: &mut Context,
+    handler: &mut H,
+) -> Result<()> {
+    let mut is_first_event = true;
+    let mut has_doc_type = false;
+    let mut has_element = false;
+    handler.start_document()?;
     loop {
+        event_buffer.clear();
+        let position = context.event_start(reader);
+        let start = context.location_start(position);
+        let is_first = std::mem::replace(&mut is_first_event, false);
         match reader.read_event_into(event_buffer) {
             Ok(Event::Decl(ev)) => {
-                let mut mut_document = document.borrow_mut();
-                if let Extension::Document {
-                    i_xml_declaration, ..
-                } = &mut mut_document.i_extension
-                {
-                    if i_xml_declaration.is_some() {
-                        error!("XML declaration must be first");
-                        return Error::Malformed.into();
-                    } else {
-                        let (version, encoding, standalone) = make_decl(reader, ev)?;
-                        *i_xml_declaration = Some(XmlDecl::new(
-                            XmlVersion::from_str(&version).unwrap(),
-                            encoding,
-                            standalone,
-                        ));
-                    }
+                if !is_first {
+                    error!("XML declaration must be first");
+                    return Error::Malformed.into();
                 }
+                let xml_decl = make_decl(reader, ev)?;
+                context.version = xml_decl.version();
+                handler.xml_declaration(&xml_decl)?;
+            }
+            Ok(Event::Start(_)) | Ok(Event::Empty(_)) if has_element => {
+                error!("only one document element allowed");
+                return Error::Malformed.into();
             }
             Ok(Event::Start(ev)) => {
-                let mut new_element = handle_start(reader, &mut document, None, ev)?;
-                let _safe_to_ignore =
-                    element(reader, event_buffer, &mut document, &mut new_element);
+                has_element = true;
+                let scope = Scope::default();
+                let (name, scope) = handle_start(reader, context, handler, ev, position, &scope)?;
+                context.element_depth += 1;
+                element(reader, event_buffer, context, handler, Some(&name), &scope)?;
+                context.element_depth -= 1;
             }
             Ok(Event::Empty(ev)) => {
-                let _safe_to_ignore = handle_start(reader, &mut document, None, ev)?;
+                has_element = true;
+                let scope = Scope::default();
+                let (name, _) = handle_start(reader, context, handler, ev, position, &scope)?;
+                handler.end_element(&name, source_location(reader, start))?;
             }
             Ok(Event::End(ev)) => {
-                let _safe_to_ignore = handle_end(reader, &mut document, None, ev)?;
+                let end_name = reader.decoder().decode(ev.name().into_inner())?;
+                let message = format!("ignoring end tag '{}' with no start tag", end_name);
+                if !context.recover(reader, message) {
+                    return Error::Malformed.into();
+                }
             }
             Ok(Event::Comment(ev)) => {
-                let _safe_to_ignore = handle_comment(&mut document, None, ev)?;
+                handle_comment(reader, context, handler, ev, start)?;
             }
             Ok(Event::PI(ev)) => {
-                let _safe_to_ignore = handle_pi(reader, &mut document, None, ev)?;
-            }
-            // Ok(Event::DocType(ev)) => {
-            //     if prolog_pre_nodes
-            //         .iter()
-            //         .find(|n| n.kind == PreNodeKind::DocType)
-            //         .is_some()
-            //     {
-            //         error!("only one document type allowed");
-            //         return Error::Malformed.into();
-            //     }
-            //     prolog_pre_nodes.push(make_doc_type(reader, ev)?);
-            // }
-            Ok(Event::Eof) => return Ok(document),
+                handle_pi(reader, context, handler, ev, start)?;
+            }
+            Ok(Event::Text(ev)) => {
+                if !ev.iter().all(|b| is_xml_space(*b as char)) {
+                    error!("text is not allowed outside the document element");
+                    return Error::Malformed.into();
+                }
+                //
+                // Leading whitespace is tolerated before the XML declaration.
+                //
+                is_first_event = is_first;
+            }
+            Ok(Event::DocType(ev)) => {
+                if has_doc_type {
+                    error!("only one document type allowed");
+                    return Error::Malformed.into();
+                }
+                if has_element {
+                    error!("document type must appear before the document element");
+                    return Error::Malformed.into();
+                }
+                has_doc_type = true;
+                handle_doctype(reader, context, handler, ev)?;
+            }
+            Ok(Event::Eof) => return handler.end_document(),
             Ok(ev) => {
                 error!("Unexpected parser event: {:?}", ev);
                 return Error::Malformed.into();
@@ -185,7 +1080,9 @@ fn document<T: BufRead>(reader: &mut Reader<T>, event_buffer: &mut Vec<u8>) -> R
 }
 
 ///
-/// Given a document that has been started, add to it.
+/// Report the content of the element `element_name` up to, and including, its end tag. This is
+/// also used to parse the replacement text of an entity, in which case `element_name` is `None`
+/// and the content ends with the replacement text rather than an end tag.
 ///
 /// ```ebnf
 /// element           ::= EmptyElemTag | STag content ETag
@@ -195,37 +1092,110 @@ fn document<T: BufRead>(reader: &mut Reader<T>, event_buffer: &mut Vec<u8>) -> R
 /// EmptyElemTag      ::= '<' Name (S Attribute)* S? '/>'
 /// ```
 ///
-fn element<T: BufRead>(
+fn element<T: SourceRead, H: ContentHandler>(
     reader: &mut Reader<T>,
     event_buffer: &mut Vec<u8>,
-    document: &mut RefNode,
-    parent_element: &mut RefNode,
-) -> Result<RefNode> {
+    context: &mut Context,
+    handler: &mut H,
+    element_name: Option<&Name>,
+    scope: &Scope<'_>,
+) -> Result<()> {
+    if let Some(element_name) = element_name {
+        context.open_elements.push(element_name.to_string());
+    }
     loop {
-        match reader.read_event_into(event_buffer) {
-            Ok(Event::Start(ev)) => {
-                let mut new_element = handle_start(reader, document, Some(parent_element), ev)?;
-                let _safe_to_ignore = element(reader, event_buffer, document, &mut new_element)?;
-            }
-            Ok(Event::Empty(ev)) => {
-                let _safe_to_ignore = handle_start(reader, document, Some(parent_element), ev)?;
+        if let Some(pending_end) = context.pending_end.take() {
+            match element_name {
+                None => {
+                    //
+                    // The replacement text of an entity is parsed by its own reader, and so
+                    // cannot end the elements enclosing the reference.
+                    //
+                    let message = format!("ignoring end tag '{}' within an entity", pending_end);
+                    let _safe_to_ignore = context.recover(reader, message);
+                }
+                Some(element_name) => {
+                    if pending_end != element_name.to_string() {
+                        let message = format!("element '{}' is not closed", element_name);
+                        let _safe_to_ignore = context.recover(reader, message);
+                        context.pending_end = Some(pending_end);
+                    }
+                    let _safe_to_ignore = context.open_elements.pop();
+                    return handler.end_element(element_name, None);
+                }
+            }
+        }
+        event_buffer.clear();
+        let position = context.event_start(reader);
+        let start = context.location_start(position);
+        match reader.read_event_into(event_buffer) {
+            Ok(Event::Start(ev)) => {
+                let (name, scope) = handle_start(reader, context, handler, ev, position, scope)?;
+                context.element_depth += 1;
+                element(reader, event_buffer, context, handler, Some(&name), &scope)?;
+                context.element_depth -= 1;
+            }
+            Ok(Event::Empty(ev)) => {
+                let (name, _) = handle_start(reader, context, handler, ev, position, scope)?;
+                handler.end_element(&name, source_location(reader, start))?;
             }
             Ok(Event::End(ev)) => {
-                let _safe_to_ignore = handle_end(reader, document, Some(parent_element), ev)?;
-                return Ok(parent_element.clone());
+                let end_name = reader.decoder().decode(ev.name().into_inner())?.to_string();
+                match element_name {
+                    Some(element_name) if end_name == element_name.to_string() => {
+                        let _safe_to_ignore = context.open_elements.pop();
+                        return handler.end_element(element_name, source_location(reader, start));
+                    }
+                    Some(element_name)
+                        if context
+                            .open_elements
+                            .iter()
+                            .rev()
+                            .skip(1)
+                            .any(|open| *open == end_name) =>
+                    {
+                        let message = format!(
+                            "element '{}' is not closed before end tag '{}'",
+                            element_name, end_name
+                        );
+                        if !context.recover(reader, message) {
+                            return Error::Malformed.into();
+                        }
+                        context.pending_end = Some(end_name);
+                        let _safe_to_ignore = context.open_elements.pop();
+                        return handler.end_element(element_name, None);
+                    }
+                    _ => {
+                        let message = format!("ignoring end tag '{}' with no start tag", end_name);
+                        if !context.recover(reader, message) {
+                            return Error::Malformed.into();
+                        }
+                    }
+                }
             }
             Ok(Event::Comment(ev)) => {
-                let _safe_to_ignore = handle_comment(document, Some(parent_element), ev)?;
+                handle_comment(reader, context, handler, ev, start)?;
             }
             Ok(Event::PI(ev)) => {
-                let _safe_to_ignore = handle_pi(reader, document, Some(parent_element), ev)?;
+                handle_pi(reader, context, handler, ev, start)?;
             }
             Ok(Event::Text(ev)) => {
-                let _safe_to_ignore = handle_text(document, Some(parent_element), ev)?;
+                handle_text(reader, context, handler, ev, position, scope)?;
             }
             Ok(Event::CData(ev)) => {
-                let _safe_to_ignore = handle_cdata(reader, document, Some(parent_element), ev)?;
+                handle_cdata(reader, context, handler, ev, start)?;
             }
+            Ok(Event::Eof) => match element_name {
+                None => return Ok(()),
+                Some(element_name) => {
+                    let message = format!("element '{}' is not closed", element_name);
+                    if !context.recover(reader, message) {
+                        return Error::Malformed.into();
+                    }
+                    let _safe_to_ignore = context.open_elements.pop();
+                    return handler.end_element(element_name, None);
+                }
+            },
             Ok(ev) => {
                 error!("Unexpected parser event: {:?}", ev);
                 return Error::Malformed.into();
@@ -240,166 +1210,630 @@ fn element<T: BufRead>(
 
 // ------------------------------------------------------------------------------------------------
 
-fn handle_start<T: BufRead>(
+///
+/// Report a start tag, returning the element's name with the scope for its content.
+///
+fn handle_start<'s, T: SourceRead, H: ContentHandler>(
     reader: &mut Reader<T>,
-    document: &mut RefNode,
-    parent_node: Option<&mut RefNode>,
+    context: &mut Context,
+    handler: &mut H,
     ev: BytesStart<'_>,
-) -> Result<RefNode> {
-    let mut element = {
-        let mut_document = as_document_mut(document).unwrap();
-        let name = reader.decoder().decode(ev.name().into_inner())?;
-        let new_node = mut_document.create_element(&name).unwrap();
-        let mut actual_parent = match parent_node {
-            None => document.clone(),
-            Some(actual) => actual.clone(),
-        };
-        actual_parent.append_child(new_node)?
+    position: Option<Position>,
+    scope: &'s Scope<'_>,
+) -> Result<(Name, Scope<'s>)> {
+    context.check_limit(Limit::ElementDepth, context.element_depth + 1)?;
+    let start = context.location_start(position);
+    let mut new_scope = Scope {
+        parent: Some(scope),
+        space: scope.space,
+        namespaces: Vec::new(),
+        context_element: None,
     };
 
+    //
+    // Namespace declarations apply to the element's own name and to those of all its attributes,
+    // so the attributes are gathered before resolving any names. The content of the tag follows
+    // the opening '<'; errors in an attribute are reported where it starts.
+    //
+    let tag: &[u8] = &ev;
+    let tag_start = position.map(|position| position.advanced(b"<"));
+    let (mut offset, mut attribute_start) = (0, tag_start);
+    let mut attributes: Vec<(String, String, Option<Position>, Option<SourceLocation>)> =
+        Vec::new();
     for attribute in ev.attributes() {
-        let attribute = attribute.unwrap();
-        let value = attribute.decode_and_unescape_value(reader)?;
-        let name = reader.decoder().decode(attribute.key.into_inner())?;
-        let attribute_node = document.create_attribute_with(&name, &value)?;
-        let _safe_to_ignore = element.set_attribute_node(attribute_node)?;
+        let attribute = match attribute {
+            Ok(attribute) => attribute,
+            Err(err) => {
+                let error_offset = attribute_error_offset(&err).clamp(offset, tag.len());
+                let position =
+                    attribute_start.map(|start| start.advanced(&tag[offset..error_offset]));
+                context.error_within(tag_start, position);
+                return Error::from(quick_xml::Error::from(err)).into();
+            }
+        };
+        let key_offset = offset_within(tag, attribute.key.into_inner());
+        attribute_start = attribute_start.map(|start| start.advanced(&tag[offset..key_offset]));
+        offset = key_offset;
+        context.error_within(tag_start, attribute_start);
+        context.check_limit(Limit::Attributes, attributes.len() + 1)?;
+        let value = make_attribute_value(reader, context, attribute.value.as_ref())?;
+        let name = reader
+            .decoder()
+            .decode(attribute.key.into_inner())?
+            .to_string();
+        let location = match (
+            attribute_start.filter(|_| start.is_some()),
+            &attribute.value,
+        ) {
+            (Some(start), Cow::Borrowed(value)) => {
+                //
+                // The attribute ends with the closing quote following the value.
+                //
+                let attribute_end = offset_within(tag, value) + value.len() + 1;
+                let end = start.advanced(&tag[key_offset..attribute_end]);
+                Some(SourceLocation { start, end })
+            }
+            _ => None,
+        };
+        if context.options.namespace_aware {
+            if name == XMLNS_NS_ATTRIBUTE {
+                check_namespace_declaration(None, &value)?;
+                new_scope.namespaces.push((None, value.clone()));
+            } else if let Some((XMLNS_NS_ATTRIBUTE, prefix)) = name.split_once(XML_NS_SEPARATOR) {
+                if value.is_empty() {
+                    error!("namespace prefix '{}' may not be un-declared", prefix);
+                    return Error::from(DOMError::Namespace).into();
+                }
+                check_namespace_declaration(Some(prefix), &value)?;
+                new_scope
+                    .namespaces
+                    .push((Some(prefix.to_string()), value.clone()));
+            }
+        }
+        if name.split_once(XML_NS_SEPARATOR) == Some((XML_NS_ATTRIBUTE, XML_NS_ATTR_SPACE)) {
+            match SpaceHandling::from_str(&value) {
+                Ok(space) => new_scope.space = space,
+                Err(_) => warn!("ignoring invalid value '{}' for xml:space", value),
+            }
+        }
+        attributes.push((name, value, attribute_start, location));
+    }
+
+    context.add_nodes(attributes.len() + 1)?;
+    context.error_within(tag_start, tag_start);
+    let element_name = {
+        let name = reader.decoder().decode(ev.name().into_inner())?;
+        new_scope.name_for(&name, false, &context.options)?
+    };
+
+    let mut parsed_attributes: Vec<ParsedAttribute> = Vec::with_capacity(attributes.len());
+    for (name, value, attribute_start, location) in attributes {
+        context.error_within(tag_start, attribute_start);
+        let attribute_name = new_scope.name_for(&name, true, &context.options)?;
+        if attribute_name.namespace_uri().is_some()
+            && parsed_attributes.iter().any(|parsed| {
+                parsed.name.namespace_uri() == attribute_name.namespace_uri()
+                    && parsed.name.local_name() == attribute_name.local_name()
+            })
+        {
+            error!("attribute '{}' duplicates a namespaced attribute", name);
+            return Error::Malformed.into();
+        }
+        parsed_attributes.push(ParsedAttribute {
+            name: attribute_name,
+            value,
+            location,
+        });
     }
 
-    Ok(element)
+    handler.start_element(
+        &element_name,
+        &parsed_attributes,
+        source_location(reader, start),
+    )?;
+    Ok((element_name, new_scope))
 }
 
-fn handle_end<T: BufRead>(
-    _reader: &mut Reader<T>,
-    document: &mut RefNode,
-    parent_node: Option<&mut RefNode>,
-    _ev: BytesEnd<'_>,
-) -> Result<RefNode> {
-    Ok(match parent_node {
-        None => document,
-        Some(actual) => actual,
+///
+/// Returns an error if a declaration binds `prefix` contrary to the reserved prefixes: `xml` may
+/// only be bound to the XML namespace, `xmlns` may not be declared, and no other prefix may be
+/// bound to either of their namespaces.
+///
+fn check_namespace_declaration(prefix: Option<&str>, namespace_uri: &str) -> Result<()> {
+    let is_allowed = match prefix {
+        Some(XML_NS_ATTRIBUTE) => namespace_uri == XML_NS_URI,
+        Some(XMLNS_NS_ATTRIBUTE) => false,
+        _ => namespace_uri != XML_NS_URI && namespace_uri != XMLNS_NS_URI,
+    };
+    if is_allowed {
+        Ok(())
+    } else {
+        error!(
+            "namespace prefix '{}' may not be bound to '{}'",
+            prefix.unwrap_or_default(),
+            namespace_uri
+        );
+        Error::from(DOMError::Namespace).into()
     }
-    .clone())
 }
 
-fn handle_comment(
-    document: &mut RefNode,
-    parent_node: Option<&mut RefNode>,
+fn handle_doctype<T: BufRead, H: ContentHandler>(
+    reader: &mut Reader<T>,
+    context: &mut Context,
+    handler: &mut H,
     ev: BytesText<'_>,
-) -> Result<RefNode> {
-    let mut_document = as_document_mut(document).unwrap();
-    let text = make_text(ev)?;
-    let new_node = mut_document.create_comment(&text);
-    let actual_parent = match parent_node {
-        None => document,
-        Some(actual) => actual,
+) -> Result<()> {
+    let content = reader.decoder().decode(ev.as_ref())?;
+    let decl = dtd::parse_doctype_decl(&content)?;
+
+    let (expanded_bytes, declarations) = {
+        let options = &context.options;
+        let base_uri = options.base_uri.as_deref();
+        let mut loader = |public_id: Option<&str>, system_id: &str, base_uri: Option<&str>| {
+            load_external(options, public_id, system_id, base_uri)
+        };
+        let limits = EntityLimits {
+            max_depth: options.max_entity_depth,
+            max_expansion: options.max_entity_expansion,
+        };
+        let mut subsets = dtd::SubsetParser::new(&mut loader, limits);
+        if let Some(internal_subset) = &decl.internal_subset {
+            subsets.parse_internal(internal_subset, base_uri)?;
+        }
+        if let Some(system_id) = &decl.system_id {
+            subsets.parse_external(decl.public_id.as_deref(), system_id, base_uri)?;
+        }
+        (subsets.expanded_bytes(), subsets.into_declarations())
     };
-    actual_parent.append_child(new_node).map_err(|e| e.into())
+    context.expanded_bytes = context.expanded_bytes.saturating_add(expanded_bytes);
+
+    let mut entities: Vec<EntityDecl> = Default::default();
+    let mut notations: Vec<NotationDecl> = Default::default();
+    for declaration in declarations {
+        match declaration {
+            dtd::MarkupDecl::Entity(entity) if !entity.is_parameter => {
+                if context.entities.contains_key(&entity.name) {
+                    //
+                    // The first declaration of an entity is binding, later ones are ignored.
+                    //
+                    warn!("entity '{}' declared more than once", entity.name);
+                    continue;
+                }
+                let _safe_to_ignore = context.entities.insert(entity.name.clone(), entity.clone());
+                entities.push(entity);
+            }
+            dtd::MarkupDecl::Entity(_) => {}
+            dtd::MarkupDecl::Notation(notation) => {
+                if notations
+                    .iter()
+                    .any(|declared| declared.name == notation.name)
+                {
+                    error!("notation '{}' declared more than once", notation.name);
+                    return Error::Malformed.into();
+                }
+                notations.push(notation);
+            }
+        }
+    }
+
+    handler.document_type(&decl, &entities, &notations)
 }
 
-fn handle_text(
-    document: &mut RefNode,
-    parent_node: Option<&mut RefNode>,
+fn handle_comment<T: SourceRead, H: ContentHandler>(
+    reader: &Reader<T>,
+    context: &mut Context,
+    handler: &mut H,
     ev: BytesText<'_>,
-) -> Result<RefNode> {
-    let mut_document = as_document_mut(document).unwrap();
-    let text = make_text(ev)?;
-    let new_node = mut_document.create_text_node(&text);
-    let actual_parent = match parent_node {
-        None => document,
-        Some(actual) => actual,
+    start: Option<Position>,
+) -> Result<()> {
+    //
+    // References are not recognized within a comment, its text is used as is.
+    //
+    let text = reader.decoder().decode(ev.as_ref())?;
+    context.check_literal(&text)?;
+    if context.options.keep_comments {
+        context.add_nodes(1)?;
+        let text = reference::normalize_line_ends(&text, context.version);
+        handler.comment(&text, source_location(reader, start))?;
+    }
+    Ok(())
+}
+
+fn handle_text<T: SourceRead, H: ContentHandler>(
+    reader: &mut Reader<T>,
+    context: &mut Context,
+    handler: &mut H,
+    ev: BytesText<'_>,
+    position: Option<Position>,
+    scope: &Scope<'_>,
+) -> Result<()> {
+    let raw_text = reader.decoder().decode(ev.as_ref())?;
+    context.check_literal(&raw_text)?;
+    let text = if context.options.trim_text && scope.space == SpaceHandling::Default {
+        raw_text.trim_matches(is_xml_space)
+    } else {
+        &raw_text
+    };
+    if text.is_empty() {
+        return Ok(());
+    }
+
+    //
+    // The position of each part is tracked so that errors are reported where the part starts.
+    //
+    let text_start = position;
+    let mut position = position.map(|start| {
+        start.advanced(&raw_text.as_bytes()[..offset_within(raw_text.as_bytes(), text.as_bytes())])
+    });
+    let parts = if context.is_recovering() {
+        recover_references(reader, context, text)
+    } else {
+        match reference::split_references(text, context.version) {
+            Ok(parts) => parts,
+            Err(err) => {
+                if let Some(offset) = reference::malformed_reference_offset(text) {
+                    let malformed =
+                        position.map(|start| start.advanced(&text.as_bytes()[..offset]));
+                    context.error_within(text_start, malformed);
+                }
+                return Err(err);
+            }
+        }
+    };
+    let mut offset = 0;
+    for (index, part) in parts.iter().enumerate() {
+        let end_offset = match part {
+            reference::TextPart::Text(_) => match parts.get(index + 1) {
+                Some(reference::TextPart::EntityRef(name)) => {
+                    offset_within(text.as_bytes(), name.as_bytes()) - XML_ENTITYREF_START.len()
+                }
+                _ => text.len(),
+            },
+            reference::TextPart::EntityRef(name) => {
+                offset_within(text.as_bytes(), name.as_bytes())
+                    + name.len()
+                    + XML_ENTITYREF_END.len()
+            }
+        };
+        let part_location = position.map(|start| SourceLocation {
+            start,
+            end: start.advanced(&text.as_bytes()[offset..end_offset]),
+        });
+        context.error_within(text_start, position);
+        let location = part_location.filter(|_| context.options.track_locations);
+        context.add_nodes(1)?;
+        match part {
+            reference::TextPart::Text(data) => {
+                context.check_chars(data)?;
+                handler.characters(data, location)?
+            }
+            reference::TextPart::EntityRef(name) => {
+                handle_entity_reference(context, handler, name, location, scope)?
+            }
+        }
+        position = part_location.map(|location| location.end);
+        offset = end_offset;
+    }
+    Ok(())
+}
+
+///
+/// Split text into text and general entity references when recovering, bare ampersands and
+/// references to undeclared entities become part of the text.
+///
+fn recover_references<'a, T: SourceRead>(
+    reader: &Reader<T>,
+    context: &mut Context,
+    text: &'a str,
+) -> Vec<reference::TextPart<'a>> {
+    let (parts, bare_ampersands) = reference::split_references_recovering(text, context.version);
+    if bare_ampersands > 0 {
+        let message = format!("{} bare ampersand(s) treated as text", bare_ampersands);
+        let _safe_to_ignore = context.recover(reader, message);
+    }
+    let mut recovered: Vec<reference::TextPart<'a>> = Vec::with_capacity(parts.len());
+    for part in parts {
+        let part = match part {
+            reference::TextPart::EntityRef(name) if !context.entities.contains_key(name) => {
+                let message = format!("reference to undeclared entity '{}' treated as text", name);
+                let _safe_to_ignore = context.recover(reader, message);
+                reference::TextPart::Text(format!(
+                    "{}{}{}",
+                    XML_ENTITYREF_START, name, XML_ENTITYREF_END
+                ))
+            }
+            part => part,
+        };
+        match (recovered.last_mut(), part) {
+            (Some(reference::TextPart::Text(previous)), reference::TextPart::Text(text)) => {
+                previous.push_str(&text)
+            }
+            (_, part) => recovered.push(part),
+        }
+    }
+    recovered
+}
+
+///
+/// Report a reference to the named entity, followed by the events for its parsed replacement
+/// text.
+///
+fn handle_entity_reference<H: ContentHandler>(
+    context: &mut Context,
+    handler: &mut H,
+    name: &str,
+    location: Option<SourceLocation>,
+    scope: &Scope<'_>,
+) -> Result<()> {
+    let entity = match context.entities.get(name) {
+        None => {
+            error!("reference to undeclared entity '{}'", name);
+            return Error::Malformed.into();
+        }
+        Some(entity) if entity.notation_name.is_some() => {
+            error!("reference to unparsed entity '{}'", name);
+            return Error::Malformed.into();
+        }
+        Some(entity) => entity.clone(),
     };
-    actual_parent.append_child(new_node).map_err(|e| e.into())
+
+    handler.start_entity(name, location)?;
+    let replacement = match (entity.value, &entity.system_id) {
+        (Some(value), _) => Some(value),
+        (None, Some(system_id)) => load_external(
+            &context.options,
+            entity.public_id.as_deref(),
+            system_id,
+            entity.base_uri.as_deref(),
+        )?,
+        (None, None) => None,
+    };
+    match replacement {
+        None => warn!("external entity '{}' is not loaded", name),
+        Some(replacement) => {
+            context.enter_entity(name, &replacement)?;
+            let mut reader = Reader::from_reader(replacement.as_bytes());
+            let mut event_buffer: Vec<u8> = Vec::new();
+            element(
+                &mut reader,
+                &mut event_buffer,
+                context,
+                handler,
+                None,
+                scope,
+            )?;
+            let _safe_to_ignore = context.open_entities.pop();
+        }
+    }
+    handler.end_entity(name)
 }
 
-fn handle_cdata<T: BufRead>(
+fn handle_cdata<T: SourceRead, H: ContentHandler>(
     reader: &mut Reader<T>,
-    document: &mut RefNode,
-    parent_node: Option<&mut RefNode>,
+    context: &mut Context,
+    handler: &mut H,
     ev: BytesCData<'_>,
-) -> Result<RefNode> {
-    let mut_document = as_document_mut(document).unwrap();
-    let text = make_cdata(reader, ev)?;
-    let new_node = mut_document.create_cdata_section(text.as_ref()).unwrap();
-    let actual_parent = match parent_node {
-        None => document,
-        Some(actual) => actual,
-    };
-    actual_parent.append_child(new_node).map_err(|e| e.into())
+    start: Option<Position>,
+) -> Result<()> {
+    context.add_nodes(1)?;
+    let text = make_cdata(reader, ev, context.version)?;
+    context.check_literal(&text)?;
+    if context.options.cdata_as_text {
+        handler.characters(&text, source_location(reader, start))
+    } else {
+        handler.cdata(&text, source_location(reader, start))
+    }
 }
 
-fn handle_pi<T: BufRead>(
-    _reader: &mut Reader<T>,
-    document: &mut RefNode,
-    parent_node: Option<&mut RefNode>,
+fn handle_pi<T: SourceRead, H: ContentHandler>(
+    reader: &Reader<T>,
+    context: &mut Context,
+    handler: &mut H,
     ev: BytesText<'_>,
-) -> Result<RefNode> {
-    let mut_document = as_document_mut(document).unwrap();
-    let text = ev.unescape()?;
-    let parts = text.splitn(2, ' ').collect::<Vec<&str>>();
-    let (target, data) = match parts.len() {
-        1 => (parts[0].to_string(), None),
-        2 => {
-            let data = parts[1].trim();
+    start: Option<Position>,
+) -> Result<()> {
+    //
+    // As for comments, references are not recognized within a processing instruction; the
+    // target is separated from the data by any white space.
+    //
+    let text = reader.decoder().decode(ev.as_ref())?;
+    context.check_literal(&text)?;
+    if !context.options.keep_processing_instructions {
+        return Ok(());
+    }
+    context.add_nodes(1)?;
+    let text = reference::normalize_line_ends(&text, context.version);
+    let (target, data) = match text.split_once(is_xml_space) {
+        None => (text.as_ref(), None),
+        Some((target, data)) => {
+            let data = data.trim_matches(is_xml_space);
             if data.is_empty() {
-                (parts[0].to_string(), None)
+                (target, None)
             } else {
-                (parts[0].to_string(), Some(data.to_string()))
+                (target, Some(data))
             }
         }
-        _ => return Error::Malformed.into(),
-    };
-    let new_node = match data {
-        None => mut_document
-            .create_processing_instruction(&target, None)
-            .unwrap(),
-        Some(s) => mut_document
-            .create_processing_instruction(&target, Some(s.as_str()))
-            .unwrap(),
     };
-    let actual_parent = match parent_node {
-        None => document,
-        Some(actual) => actual,
+    if !is_xml_name(target) {
+        error!("invalid processing instruction target {:?}", target);
+        return Error::InvalidCharacter.into();
+    }
+    handler.processing_instruction(target, data, source_location(reader, start))
+}
+
+///
+/// Load the text of an external entity, or of the external subset, using the resolver in
+/// `options`; returns `None` if there is no resolver or it refuses to load the entity. Any text
+/// declaration is removed.
+///
+fn load_external(
+    options: &ParserOptions,
+    public_id: Option<&str>,
+    system_id: &str,
+    base_uri: Option<&str>,
+) -> Result<Option<String>> {
+    let resolver = match &options.external_resolver {
+        None => return Ok(None),
+        Some(resolver) => resolver,
     };
-    actual_parent.append_child(new_node).map_err(|e| e.into())
+    match resolver.resolve(public_id, system_id, base_uri)? {
+        None => {
+            warn!("resolver refused to load '{}'", system_id);
+            Ok(None)
+        }
+        Some(bytes) => {
+            let (text, _) = encoding::decode(&bytes)?;
+            let text = match text.strip_prefix(XML_DECL_START) {
+                Some(rest) if rest.starts_with(is_xml_space) => match rest.find(XML_PI_END) {
+                    None => {
+                        error!("text declaration of '{}' is not closed", system_id);
+                        return Error::Malformed.into();
+                    }
+                    Some(end) => &rest[end + XML_PI_END.len()..],
+                },
+                _ => &text,
+            };
+            Ok(Some(text.to_string()))
+        }
+    }
 }
 
 // ------------------------------------------------------------------------------------------------
 
-fn make_text(ev: BytesText<'_>) -> Result<String> {
-    Ok(ev.unescape()?.to_string())
+///
+/// Returns the location from `start` to the current position in the input.
+///
+fn source_location<T: SourceRead>(
+    reader: &Reader<T>,
+    start: Option<Position>,
+) -> Option<SourceLocation> {
+    let start = start?;
+    let end = reader.get_ref().position_at(reader.buffer_position())?;
+    Some(SourceLocation { start, end })
 }
 
-fn make_cdata<T: BufRead>(reader: &mut Reader<T>, ev: BytesCData<'_>) -> Result<String> {
-    let cdata_bytes = ev.into_inner();
-    let decoded_string = reader.decoder().decode(cdata_bytes.as_ref())?;
-    Ok(decoded_string.to_string())
+///
+/// The offset of `inner` within `outer`, where `inner` is a sub-slice of `outer`.
+///
+///
+/// Returns the offset, within the content of the tag, at which quick-xml found `err`.
+///
+fn attribute_error_offset(err: &AttrError) -> usize {
+    match err {
+        AttrError::ExpectedEq(offset)
+        | AttrError::ExpectedValue(offset)
+        | AttrError::UnquotedValue(offset)
+        | AttrError::ExpectedQuote(offset, _)
+        | AttrError::Duplicated(offset, _) => *offset,
+    }
+}
+
+fn offset_within(outer: &[u8], inner: &[u8]) -> usize {
+    inner.as_ptr() as usize - outer.as_ptr() as usize
 }
 
-fn make_decl<T: BufRead>(
+///
+/// References to general entities are retained in the attribute value, they are replaced when the
+/// value is retrieved using the `EntityResolver` for the attribute. Literal white space, after
+/// line ends are normalized, is replaced by a space as described in
+/// [§3.3.3 Attribute-Value Normalization](https://www.w3.org/TR/xml/#AVNormalize); white space
+/// written as a character reference is kept.
+///
+fn make_attribute_value<T: SourceRead>(
     reader: &mut Reader<T>,
-    ev: BytesDecl<'_>,
-) -> Result<(String, Option<String>, Option<bool>)> {
-    let version = ev.version().unwrap();
-    let version = version.borrow();
-    let version = reader.decoder().decode(version).unwrap();
-    let version = unquote(version.to_string())?;
-    let encoding = if let Some(ev_value) = ev.encoding() {
-        let encoding = ev_value.unwrap();
-        let encoding = encoding.borrow();
-        let encoding = reader.decoder().decode(encoding).unwrap();
-        Some(encoding.to_string())
+    context: &mut Context,
+    raw_value: &[u8],
+) -> Result<String> {
+    let raw_value = reader.decoder().decode(raw_value)?;
+    context.check_literal(&raw_value)?;
+    let raw_value =
+        reference::normalize_line_ends(&raw_value, context.version).replace(is_xml_space, " ");
+    let parts = if context.is_recovering() {
+        let (parts, bare_ampersands) =
+            reference::split_references_recovering(&raw_value, context.version);
+        if bare_ampersands > 0 {
+            let message = format!(
+                "{} bare ampersand(s) in attribute value treated as text",
+                bare_ampersands
+            );
+            let _safe_to_ignore = context.recover(reader, message);
+        }
+        parts
     } else {
-        None
+        reference::split_references(&raw_value, context.version)?
     };
-    let standalone = if let Some(ev_value) = ev.standalone() {
-        let standalone = ev_value.unwrap();
-        let standalone = standalone.borrow();
-        let standalone = reader.decoder().decode(standalone).unwrap();
-        Some(standalone == "yes")
-    } else {
-        None
+    let mut value = String::with_capacity(raw_value.len());
+    for part in parts {
+        match part {
+            reference::TextPart::Text(text) => {
+                context.check_chars(&text)?;
+                value.push_str(&text)
+            }
+            reference::TextPart::EntityRef(name) => {
+                context.check_attribute_entity(name)?;
+                value.push_str(XML_ENTITYREF_START);
+                value.push_str(name);
+                value.push_str(XML_ENTITYREF_END);
+            }
+        }
+    }
+    Ok(value)
+}
+
+fn make_cdata<T: BufRead>(
+    reader: &mut Reader<T>,
+    ev: BytesCData<'_>,
+    version: XmlVersion,
+) -> Result<String> {
+    let cdata_bytes = ev.into_inner();
+    let decoded_string = reader.decoder().decode(cdata_bytes.as_ref())?;
+    Ok(reference::normalize_line_ends(&decoded_string, version).to_string())
+}
+
+fn make_decl<T: BufRead>(reader: &mut Reader<T>, ev: BytesDecl<'_>) -> Result<XmlDecl> {
+    let version = match decl_value(reader, "version", Some(ev.version()))? {
+        Some(version) => unquote(version)?,
+        None => {
+            error!("XML declaration has no version");
+            return Error::Malformed.into();
+        }
+    };
+    let version = match XmlVersion::from_str(&version) {
+        Ok(version) => version,
+        Err(_) => {
+            error!("unsupported XML version {:?}", version);
+            return Error::Malformed.into();
+        }
+    };
+    let encoding = decl_value(reader, "encoding", ev.encoding())?;
+    let standalone = decl_value(reader, "standalone", ev.standalone())?.map(|s| s == "yes");
+    Ok(XmlDecl::new(version, encoding, standalone))
+}
+
+///
+/// The decoded value of the pseudo-attribute `name` of an XML declaration, if present; a value
+/// that cannot be read, or decoded, is malformed.
+///
+fn decl_value<T: BufRead>(
+    reader: &Reader<T>,
+    name: &str,
+    value: Option<std::result::Result<Cow<'_, [u8]>, quick_xml::Error>>,
+) -> Result<Option<String>> {
+    let value = match value {
+        None => return Ok(None),
+        Some(Ok(value)) => value,
+        Some(Err(err)) => {
+            error!("XML declaration has an invalid {}: {}", name, err);
+            return Error::Malformed.into();
+        }
     };
-    Ok((version, encoding, standalone))
+    match reader.decoder().decode(&value) {
+        Ok(value) => Ok(Some(value.to_string())),
+        Err(err) => {
+            error!("XML declaration {} could not be decoded: {}", name, err);
+            Error::Malformed.into()
+        }
+    }
 }
 
 #[allow(clippy::if_same_then_else)]
@@ -422,6 +1856,9 @@ fn unquote(s: String) -> Result<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::level2::convert::{as_element_mut, as_notation, as_text};
+    use crate::level2::ext::ProcessingOptions;
+    use crate::level2::node_impl::Extension;
 
     fn test_good_xml(xml: &str) {
         let dom = read_xml(xml);
@@ -459,6 +1896,32 @@ mod tests {
         test_good_xml("<?xml-stylesheet type=\"text/xsl\" href=\"style.xsl\"?><xml/>");
     }
 
+    #[test]
+    fn test_comment_and_pi_round_trip() {
+        let round_trip = |xml: &str| read_xml(xml).unwrap().first_child().unwrap().to_string();
+        for xml in [
+            "<doc><!-- x &amp; y &lt; &undeclared; --></doc>",
+            "<doc><?pi x &amp; y?></doc>",
+        ] {
+            assert_eq!(round_trip(xml), xml);
+        }
+        assert_eq!(
+            round_trip("<doc><?pi\tdata?></doc>"),
+            "<doc><?pi data?></doc>"
+        );
+        assert_eq!(
+            round_trip("<doc><?pi\r\n data\n?></doc>"),
+            "<doc><?pi data?></doc>"
+        );
+
+        let document = read_xml("<doc><!-- x &amp; y --><?pi\tx &lt; y?></doc>").unwrap();
+        let element = document.first_child().unwrap();
+        let children = element.child_nodes();
+        assert_eq!(children[0].node_value(), Some(" x &amp; y ".to_string()));
+        assert_eq!(children[1].node_name().to_string(), "pi");
+        assert_eq!(children[1].node_value(), Some("x &lt; y".to_string()));
+    }
+
     #[test]
     fn test_nested_document() {
         test_good_xml("<xml><xslt/></xml>");
@@ -469,6 +1932,1406 @@ mod tests {
         test_good_xml("<xml id=\"11\"></xml>");
     }
 
+    #[test]
+    fn test_doctype() {
+        let dom = read_xml(
+            r#"<?xml version="1.0"?>
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN"
+  "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd">
+<html/>"#,
+        )
+        .unwrap();
+        let document = as_document(&dom).unwrap();
+        let doc_type_node = document.doc_type().unwrap();
+        let doc_type = as_document_type(&doc_type_node).unwrap();
+        assert_eq!(doc_type.node_name().to_string(), "html");
+        assert_eq!(
+            doc_type.public_id(),
+            Some("-//W3C//DTD XHTML 1.0 Strict//EN".to_string())
+        );
+        assert_eq!(
+            doc_type.system_id(),
+            Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd".to_string())
+        );
+        assert!(doc_type.internal_subset().is_none());
+        assert_eq!(doc_type.owner_document(), Some(dom.clone()));
+    }
+
+    #[test]
+    fn test_doctype_internal_subset() {
+        let dom = read_xml(
+            r#"<!DOCTYPE greeting [
+  <!ELEMENT greeting (#PCDATA)>
+]>
+<greeting>Hello, world!</greeting>"#,
+        )
+        .unwrap();
+        let document = as_document(&dom).unwrap();
+        let doc_type_node = document.doc_type().unwrap();
+        let doc_type = as_document_type(&doc_type_node).unwrap();
+        assert_eq!(doc_type.node_name().to_string(), "greeting");
+        assert!(doc_type.public_id().is_none());
+        assert!(doc_type.system_id().is_none());
+        assert_eq!(
+            doc_type.internal_subset(),
+            Some("\n  <!ELEMENT greeting (#PCDATA)>\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_doctype_entities() {
+        let dom = read_xml(
+            r#"<!DOCTYPE doc [
+  <!ENTITY copy "&#169; Example">
+  <!ENTITY copy "ignored">
+  <!ENTITY chap1 SYSTEM "chap1.xml">
+  <!ENTITY logo SYSTEM "logo.gif" NDATA gif>
+  <!ENTITY % param "not in the DOM">
+  <!NOTATION gif PUBLIC "-//Example//GIF">
+]>
+<doc/>"#,
+        )
+        .unwrap();
+        let document = as_document(&dom).unwrap();
+        let doc_type_node = document.doc_type().unwrap();
+        let doc_type = as_document_type(&doc_type_node).unwrap();
+
+        let entities = doc_type.entities();
+        assert_eq!(entities.len(), 3);
+        let copy = entities.get(&Name::from_str("copy").unwrap()).unwrap();
+        assert_eq!(copy.node_value(), Some("© Example".to_string()));
+        let chap1 = as_entity(entities.get(&Name::from_str("chap1").unwrap()).unwrap()).unwrap();
+        assert_eq!(chap1.system_id(), Some("chap1.xml".to_string()));
+        assert!(chap1.notation_name().is_none());
+        let logo = as_entity(entities.get(&Name::from_str("logo").unwrap()).unwrap()).unwrap();
+        assert_eq!(logo.notation_name(), Some("gif".to_string()));
+
+        let notations = doc_type.notations();
+        assert_eq!(notations.len(), 1);
+        let gif = as_notation(notations.get(&Name::from_str("gif").unwrap()).unwrap()).unwrap();
+        assert_eq!(gif.public_id(), Some("-//Example//GIF".to_string()));
+        assert!(gif.system_id().is_none());
+
+        let mut element_node = document.document_element().unwrap();
+        let element = as_element_mut(&mut element_node).unwrap();
+        element.set_attribute("owner", "&copy;").unwrap();
+        assert_eq!(
+            element.get_attribute("owner"),
+            Some("© Example".to_string())
+        );
+        assert_eq!(
+            element.set_attribute("owner", "&chap2;"),
+            Err(DOMError::NotFound)
+        );
+        assert_eq!(
+            element.get_attribute("owner"),
+            Some("© Example".to_string())
+        );
+    }
+
+    const ENTITY_XML: &str = r#"<!DOCTYPE doc [
+  <!ENTITY name "World">
+  <!ENTITY greeting "Hello, &name;!">
+  <!ENTITY signed "<b>&name;</b>">
+  <!ENTITY loop "&loop;">
+]>
+<doc title="&greeting;">&lt;&greeting; &#169; &signed;</doc>"#;
+
+    #[test]
+    fn test_entity_references() {
+        let dom = read_xml(ENTITY_XML).unwrap();
+        let document = as_document(&dom).unwrap();
+        let element_node = document.document_element().unwrap();
+        let element = as_element(&element_node).unwrap();
+        assert_eq!(
+            element.get_attribute("title"),
+            Some("Hello, World!".to_string())
+        );
+
+        let child_nodes = element.child_nodes();
+        assert_eq!(
+            child_nodes
+                .iter()
+                .map(|n| n.node_type())
+                .collect::<Vec<NodeType>>(),
+            vec![
+                NodeType::Text,
+                NodeType::EntityReference,
+                NodeType::Text,
+                NodeType::EntityReference,
+            ]
+        );
+        assert_eq!(
+            as_text(&child_nodes[0]).unwrap().node_value(),
+            Some("<".to_string())
+        );
+        assert_eq!(
+            as_text(&child_nodes[2]).unwrap().node_value(),
+            Some(" © ".to_string())
+        );
+
+        let greeting = &child_nodes[1];
+        assert_eq!(greeting.node_name().to_string(), "greeting");
+        let greeting_children = greeting.child_nodes();
+        assert_eq!(greeting_children.len(), 3);
+        assert_eq!(
+            greeting_children[0].node_value(),
+            Some("Hello, ".to_string())
+        );
+        assert_eq!(greeting_children[1].node_type(), NodeType::EntityReference);
+        assert_eq!(
+            greeting_children[1].first_child().unwrap().node_value(),
+            Some("World".to_string())
+        );
+
+        let signed_children = child_nodes[3].child_nodes();
+        assert_eq!(signed_children.len(), 1);
+        assert_eq!(signed_children[0].node_name().to_string(), "b");
+    }
+
+    #[test]
+    fn test_parse_entities() {
+        let mut processing_options = ProcessingOptions::new();
+        processing_options.set_parse_entities();
+        let options = ParserOptions::new().processing_options(processing_options);
+        let dom = read_xml_with_options(ENTITY_XML, options).unwrap();
+        let document = as_document(&dom).unwrap();
+        let element_node = document.document_element().unwrap();
+
+        let child_nodes = element_node.child_nodes();
+        assert_eq!(child_nodes.len(), 2);
+        assert_eq!(
+            child_nodes[0].node_value(),
+            Some("<Hello, World! © ".to_string())
+        );
+        assert_eq!(child_nodes[1].node_name().to_string(), "b");
+        assert_eq!(
+            child_nodes[1].first_child().unwrap().node_value(),
+            Some("World".to_string())
+        );
+    }
+
+    #[test]
+    fn test_bad_entity_references() {
+        assert!(read_xml("<doc>&undeclared;</doc>").is_err());
+        assert!(read_xml("<doc a='&undeclared;'/>").is_err());
+        assert!(read_xml(ENTITY_XML.replace("&signed;", "&loop;")).is_err());
+        assert!(read_xml(
+            r#"<!DOCTYPE doc [
+  <!NOTATION gif SYSTEM "image/gif">
+  <!ENTITY logo SYSTEM "logo.gif" NDATA gif>
+]>
+<doc>&logo;</doc>"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_error_position() {
+        let error = read_xml("<doc>\n  <a>\n    &undeclared;\n  </a>\n</doc>").unwrap_err();
+        assert!(matches!(error.inner(), Error::Malformed));
+        assert_eq!(
+            error.position(),
+            Some(&Position {
+                line: 3,
+                column: 5,
+                byte_offset: 16
+            })
+        );
+        if let Error::Positioned { snippet, .. } = &error {
+            assert!(snippet.contains("&undeclared;"));
+        } else {
+            panic!("expecting a positioned error");
+        }
+
+        let error = read_reader("<doc>\n  <a>\n  </b>\n</doc>".as_bytes()).unwrap_err();
+        assert!(matches!(error.inner(), Error::QuickXMLError(_)));
+        assert_eq!(error.position().map(|p| p.line), Some(3));
+
+        // Errors are reported where the bad input starts, however long the event is.
+        let text = "x".repeat(100);
+        let error = read_xml(format!("<doc>\n{}&undeclared;</doc>", text)).unwrap_err();
+        assert_eq!(
+            error.position(),
+            Some(&Position {
+                line: 2,
+                column: 101,
+                byte_offset: 106
+            })
+        );
+        let error = read_xml(format!("<doc>{}\n<a b=\"&undeclared;\"/></doc>", text)).unwrap_err();
+        assert_eq!(
+            error.position(),
+            Some(&Position {
+                line: 2,
+                column: 4,
+                byte_offset: 109
+            })
+        );
+
+        // Snippets are taken from the event around the bad input.
+        let snippet = |error: &Error| match error {
+            Error::Positioned { snippet, .. } => snippet.clone(),
+            _ => panic!("expecting a positioned error"),
+        };
+        let error = read_xml(format!("<doc>{}& x</doc>", text)).unwrap_err();
+        assert!(matches!(error.inner(), Error::Malformed));
+        assert_eq!(error.position().map(|p| p.byte_offset), Some(105));
+        assert_eq!(snippet(&error), format!("{}& x", "x".repeat(32)));
+        let attributes: String = (0..20).map(|i| format!("a{}='1' ", i)).collect();
+        let xml = format!("<doc><a {}b='1' c d='2'/></doc>", attributes);
+        let error = read_xml(&xml).unwrap_err();
+        assert!(matches!(error.inner(), Error::QuickXMLError(_)));
+        assert_eq!(error.position().map(|p| p.byte_offset), xml.find("d='2'"));
+        assert!(snippet(&error).ends_with("b='1' c d='2'/"));
+        assert!(!snippet(&error).contains("a1="));
+        let xml = format!("<doc><a {}b='&bad'/></doc>", attributes);
+        let error = read_xml(&xml).unwrap_err();
+        assert_eq!(
+            error.position().map(|p| p.byte_offset),
+            xml.find("b='&bad'")
+        );
+        assert!(snippet(&error).ends_with("a19='1' b='&bad'/"));
+        assert!(!snippet(&error).contains("a1="));
+    }
+
+    #[test]
+    fn test_source_locations() {
+        use crate::level2::ext::SourceLocated;
+        let location = |node: &RefNode| {
+            let location = node.source_location().unwrap();
+            (
+                (
+                    location.start.line,
+                    location.start.column,
+                    location.start.byte_offset,
+                ),
+                (
+                    location.end.line,
+                    location.end.column,
+                    location.end.byte_offset,
+                ),
+            )
+        };
+
+        let xml = "<doc>\n  <a id=\"x\">some &lt; text</a>\n  <!-- note -->\n  <?pi data?>\n</doc>";
+        let dom = read_xml(xml).unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert!(root.source_location().is_none());
+
+        let options = ParserOptions::new().track_locations(true);
+        let dom = read_xml_with_options(xml, options).unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert_eq!(location(&root), ((1, 1, 0), (5, 7, 73)));
+
+        let children = root.child_nodes();
+        assert_eq!(children.len(), 3);
+        let element = &children[0];
+        assert_eq!(location(element), ((2, 3, 8), (2, 31, 36)));
+        let attribute = as_element(element)
+            .unwrap()
+            .get_attribute_node("id")
+            .unwrap();
+        assert_eq!(location(&attribute), ((2, 6, 11), (2, 12, 17)));
+        let text = &element.child_nodes()[0];
+        assert_eq!(location(text), ((2, 13, 18), (2, 27, 32)));
+        assert_eq!(location(&children[1]), ((3, 3, 39), (3, 16, 52)));
+        assert_eq!(location(&children[2]), ((4, 3, 55), (4, 14, 66)));
+
+        let xml = "<!DOCTYPE d [<!ENTITY e \"E\">]><d>x&e;y</d>";
+        let offset = xml.find("&e;").unwrap();
+        let dom = read_xml_with_options(xml, ParserOptions::new().track_locations(true)).unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        let children = root.child_nodes();
+        assert_eq!(children.len(), 3);
+        assert_eq!(children[1].node_type(), NodeType::EntityReference);
+        assert_eq!(
+            location(&children[1]),
+            ((1, offset + 1, offset), (1, offset + 4, offset + 3))
+        );
+        assert_eq!(location(&children[2]).0, (1, offset + 4, offset + 3));
+    }
+
+    #[test]
+    fn test_parser_options() {
+        const XML: &str = "<p>Some <b>bold</b> text <!-- note --><![CDATA[<i>]]></p><?pi data?>";
+        let children = |options: ParserOptions| {
+            let dom = read_xml_with_options(XML, options).unwrap();
+            let document = as_document(&dom).unwrap();
+            let root = document.document_element().unwrap();
+            (document.child_nodes().len(), root.child_nodes())
+        };
+
+        let (top_level, child_nodes) = children(ParserOptions::new());
+        assert_eq!(top_level, 2);
+        assert_eq!(child_nodes.len(), 5);
+        assert_eq!(child_nodes[0].node_value(), Some("Some".to_string()));
+        assert_eq!(child_nodes[2].node_value(), Some("text".to_string()));
+        assert_eq!(child_nodes[3].node_type(), NodeType::Comment);
+        assert_eq!(child_nodes[4].node_type(), NodeType::CData);
+
+        let (top_level, child_nodes) = children(
+            ParserOptions::new()
+                .trim_text(false)
+                .keep_comments(false)
+                .keep_processing_instructions(false)
+                .cdata_as_text(true),
+        );
+        assert_eq!(top_level, 1);
+        assert_eq!(child_nodes.len(), 4);
+        assert_eq!(child_nodes[0].node_value(), Some("Some ".to_string()));
+        assert_eq!(child_nodes[2].node_value(), Some(" text ".to_string()));
+        assert_eq!(child_nodes[3].node_type(), NodeType::Text);
+        assert_eq!(child_nodes[3].node_value(), Some("<i>".to_string()));
+
+        let mut processing_options = ProcessingOptions::new();
+        processing_options.set_assume_ids();
+        let dom = read_xml_with_options(
+            "<p>\n  <b> </b>\n</p>",
+            ParserOptions::new()
+                .trim_text(false)
+                .processing_options(processing_options.clone()),
+        )
+        .unwrap();
+        if let Extension::Document { i_options, .. } = &dom.borrow().i_extension {
+            assert_eq!(i_options, &processing_options);
+        } else {
+            panic!("expecting a document");
+        }
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        let child_nodes = root.child_nodes();
+        assert_eq!(child_nodes.len(), 3);
+        assert_eq!(child_nodes[0].node_value(), Some("\n  ".to_string()));
+        assert_eq!(
+            child_nodes[1].first_child().unwrap().node_value(),
+            Some(" ".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parser_options_set_and_unset() {
+        use crate::level2::ext::dom_impl::get_implementation_ext;
+        const XML: &str = "<p> a <!--c--><![CDATA[ b ]]><?pi?></p>";
+        let children = |options: ParserOptions| {
+            let dom = read_xml_with_options(XML, options).unwrap();
+            let root = as_document(&dom).unwrap().document_element().unwrap();
+            root.child_nodes()
+                .iter()
+                .map(|child| (child.node_type(), child.node_value().unwrap_or_default()))
+                .collect::<Vec<(NodeType, String)>>()
+        };
+        let defaults = children(ParserOptions::new());
+        assert_eq!(
+            defaults,
+            vec![
+                (NodeType::Text, "a".to_string()),
+                (NodeType::Comment, "c".to_string()),
+                (NodeType::CData, " b ".to_string()),
+                (NodeType::ProcessingInstruction, String::new()),
+            ]
+        );
+
+        let options = ParserOptions::new().trim_text(false);
+        assert_eq!(children(options.clone())[0].1, " a ");
+        assert_eq!(children(options.trim_text(true)), defaults);
+
+        let options = ParserOptions::new().keep_comments(false);
+        assert!(children(options.clone())
+            .iter()
+            .all(|(node_type, _)| *node_type != NodeType::Comment));
+        assert_eq!(children(options.clone()).len(), 3);
+        assert_eq!(children(options.keep_comments(true)), defaults);
+
+        let options = ParserOptions::new().keep_processing_instructions(false);
+        assert!(children(options.clone())
+            .iter()
+            .all(|(node_type, _)| *node_type != NodeType::ProcessingInstruction));
+        assert_eq!(children(options.clone()).len(), 3);
+        assert_eq!(
+            children(options.keep_processing_instructions(true)),
+            defaults
+        );
+
+        let options = ParserOptions::new().cdata_as_text(true);
+        assert_eq!(
+            children(options.clone())[2],
+            (NodeType::Text, " b ".to_string())
+        );
+        assert_eq!(children(options.cdata_as_text(false)), defaults);
+
+        // An empty CDATA section as text adds no characters.
+        let dom = read_xml_with_options(
+            "<p><![CDATA[]]></p>",
+            ParserOptions::new().cdata_as_text(true),
+        )
+        .unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert!(root
+            .child_nodes()
+            .iter()
+            .all(|child| child.node_value().unwrap_or_default().is_empty()));
+
+        let mut processing_options = ProcessingOptions::new();
+        processing_options.set_parse_entities();
+        let document_options = |options: ParserOptions| {
+            let dom = read_xml_with_options(XML, options).unwrap();
+            let node = dom.borrow();
+            match &node.i_extension {
+                Extension::Document { i_options, .. } => i_options.clone(),
+                _ => panic!("expecting a document"),
+            }
+        };
+        assert_eq!(
+            document_options(ParserOptions::new()),
+            ProcessingOptions::default()
+        );
+        let options = ParserOptions::new().processing_options(processing_options.clone());
+        assert_eq!(document_options(options.clone()), processing_options);
+        assert_eq!(
+            document_options(options.processing_options(ProcessingOptions::default())),
+            ProcessingOptions::default()
+        );
+
+        let options = ParserOptions::new().implementation(get_implementation_ext());
+        assert_eq!(children(options), defaults);
+    }
+
+    #[test]
+    fn test_xml_space() {
+        use crate::level2::ext::SpaceHandled;
+        let dom = read_xml(
+            r#"<doc>
+  <pre xml:space="preserve"> a <b> </b><c xml:space="default"> c </c><d xml:space="other"> </d></pre>
+  <e> e </e>
+</doc>"#,
+        )
+        .unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert_eq!(root.space_handling(), SpaceHandling::Default);
+        let children = root.child_nodes();
+        assert_eq!(children.len(), 2);
+
+        let pre = &children[0];
+        assert_eq!(pre.space_handling(), SpaceHandling::Preserve);
+        let pre_children = pre.child_nodes();
+        assert_eq!(pre_children.len(), 4);
+        assert_eq!(pre_children[0].node_value(), Some(" a ".to_string()));
+        assert_eq!(pre_children[0].space_handling(), SpaceHandling::Preserve);
+        assert_eq!(
+            pre_children[1].first_child().unwrap().node_value(),
+            Some(" ".to_string())
+        );
+        assert_eq!(pre_children[2].space_handling(), SpaceHandling::Default);
+        assert_eq!(
+            pre_children[2].first_child().unwrap().node_value(),
+            Some("c".to_string())
+        );
+        let attribute = as_element(&pre_children[2])
+            .unwrap()
+            .get_attribute_node("xml:space")
+            .unwrap();
+        assert_eq!(attribute.space_handling(), SpaceHandling::Default);
+        assert_eq!(pre_children[3].space_handling(), SpaceHandling::Preserve);
+        assert_eq!(
+            pre_children[3].first_child().unwrap().node_value(),
+            Some(" ".to_string())
+        );
+
+        assert_eq!(
+            children[1].first_child().unwrap().node_value(),
+            Some("e".to_string())
+        );
+    }
+
+    #[test]
+    fn test_xml_space_nested_default() {
+        use crate::level2::ext::SpaceHandled;
+        let dom = read_xml(concat!(
+            "<doc xml:space=\"preserve\"> 1 ",
+            "<a xml:space=\"default\"> 2 <b>  </b><c> 3 <d xml:space=\"preserve\"> 4 </d></c></a>",
+            " 5 </doc>"
+        ))
+        .unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        let values = |node: &RefNode| {
+            node.child_nodes()
+                .iter()
+                .map(|child| child.node_value())
+                .collect::<Vec<Option<String>>>()
+        };
+        assert_eq!(
+            values(&root),
+            vec![Some(" 1 ".to_string()), None, Some(" 5 ".to_string())]
+        );
+
+        // The default handling of the nested element applies to all of its content.
+        let a = root.child_nodes()[1].clone();
+        assert_eq!(a.space_handling(), SpaceHandling::Default);
+        assert_eq!(values(&a), vec![Some("2".to_string()), None, None]);
+        let b = a.child_nodes()[1].clone();
+        assert_eq!(b.space_handling(), SpaceHandling::Default);
+        assert!(!b.has_child_nodes());
+        let c = a.child_nodes()[2].clone();
+        assert_eq!(c.space_handling(), SpaceHandling::Default);
+        assert_eq!(c.first_child().unwrap().node_value(), Some("3".to_string()));
+
+        // Preserve may be restored within it, and applies again after it ends.
+        let d = c.last_child().unwrap();
+        assert_eq!(d.space_handling(), SpaceHandling::Preserve);
+        assert_eq!(
+            d.first_child().unwrap().node_value(),
+            Some(" 4 ".to_string())
+        );
+        let last = root.last_child().unwrap();
+        assert_eq!(last.space_handling(), SpaceHandling::Preserve);
+    }
+
+    #[test]
+    fn test_namespaces() {
+        use crate::level2::ext::Namespaced;
+        const XML: &str = r#"<order xmlns="urn:order" xmlns:p="urn:product" id="1" p:id="2">
+  <p:item p:sku="A1"><note xmlns="">none</note></p:item>
+  <p:item p:sku="B2" xml:lang="en"/>
+</order>"#;
+        let mut processing_options = ProcessingOptions::new();
+        processing_options.set_add_namespaces();
+        let options = ParserOptions::new().processing_options(processing_options);
+        let dom = read_xml_with_options(XML, options).unwrap();
+        let document = as_document(&dom).unwrap();
+        let root = document.document_element().unwrap();
+        assert_eq!(root.namespace_uri(), Some("urn:order".to_string()));
+        assert_eq!(root.local_name(), "order".to_string());
+        assert_eq!(
+            root.resolve_namespace(Some("p")),
+            Some("urn:product".to_string())
+        );
+
+        let root_element = as_element(&root).unwrap();
+        assert_eq!(root_element.get_attribute("id"), Some("1".to_string()));
+        assert_eq!(
+            root_element.get_attribute_ns("urn:product", "id"),
+            Some("2".to_string())
+        );
+
+        let items = root_element.get_elements_by_tag_name_ns("urn:product", "item");
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            as_element(&items[0])
+                .unwrap()
+                .get_attribute_ns("urn:product", "sku"),
+            Some("A1".to_string())
+        );
+        assert_eq!(
+            as_element(&items[1])
+                .unwrap()
+                .get_attribute_ns(XML_NS_URI, "lang"),
+            Some("en".to_string())
+        );
+        let note = items[0].first_child().unwrap();
+        assert_eq!(note.namespace_uri(), None);
+
+        let error = read_xml("<a><b:c/></a>").unwrap_err();
+        assert!(matches!(
+            error.inner(),
+            Error::DOMError(DOMError::Namespace)
+        ));
+        let error = read_xml(r#"<a xmlns:b=""/>"#).unwrap_err();
+        assert!(matches!(
+            error.inner(),
+            Error::DOMError(DOMError::Namespace)
+        ));
+        let error =
+            read_xml(r#"<a xmlns:b="urn:x" xmlns:c="urn:x" b:d="1" c:d="2"/>"#).unwrap_err();
+        assert!(matches!(error.inner(), Error::Malformed));
+
+        let options = ParserOptions::new().namespace_aware(false);
+        let dom = read_xml_with_options("<a><b:c/></a>", options).unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert_eq!(root.first_child().unwrap().namespace_uri(), None);
+    }
+
+    #[test]
+    fn test_reserved_namespaces() {
+        let dom = read_xml(format!(
+            r#"<a xmlns:xml="{}" xml:lang="en"><xml:b/></a>"#,
+            XML_NS_URI
+        ))
+        .unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert_eq!(
+            as_element(&root)
+                .unwrap()
+                .get_attribute_ns(XML_NS_URI, "lang"),
+            Some("en".to_string())
+        );
+        assert_eq!(
+            root.first_child().unwrap().namespace_uri(),
+            Some(XML_NS_URI.to_string())
+        );
+
+        for xml in [
+            r#"<a xmlns:xml="urn:x"/>"#.to_string(),
+            format!(r#"<a xmlns:xmlns="{}"/>"#, XMLNS_NS_URI),
+            r#"<a xmlns:xmlns="urn:x"/>"#.to_string(),
+            format!(r#"<a xmlns:p="{}"/>"#, XMLNS_NS_URI),
+            format!(r#"<a xmlns:p="{}"/>"#, XML_NS_URI),
+            format!(r#"<a xmlns="{}"/>"#, XMLNS_NS_URI),
+            format!(r#"<a xmlns="{}"/>"#, XML_NS_URI),
+        ] {
+            let error = read_xml(&xml).unwrap_err();
+            assert!(
+                matches!(error.inner(), Error::DOMError(DOMError::Namespace)),
+                "expecting a namespace error for {}",
+                xml
+            );
+        }
+
+        // The reserved prefixes are not checked if the parser is not namespace aware.
+        let options = ParserOptions::new().namespace_aware(false);
+        assert!(read_xml_with_options(r#"<a xmlns:xmlns="urn:x"/>"#, options).is_ok());
+    }
+
+    #[test]
+    fn test_read_bytes() {
+        let bytes: Vec<u8> = "\u{FEFF}<?xml version=\"1.0\" encoding=\"UTF-16\"?><a>caf\u{E9}</a>"
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect();
+        let dom = read_bytes(&bytes).unwrap();
+        assert_eq!(
+            dom.xml_declaration().unwrap().encoding(),
+            Some("UTF-16".to_string())
+        );
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert_eq!(
+            root.first_child().unwrap().node_value(),
+            Some("caf\u{E9}".to_string())
+        );
+
+        let bytes = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<a>caf\xE9</a>";
+        let dom = read_encoded_reader(&bytes[..]).unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert_eq!(
+            root.first_child().unwrap().node_value(),
+            Some("caf\u{E9}".to_string())
+        );
+
+        let dom = read_bytes(b"<?xml version=\"1.0\"?><a/>").unwrap();
+        assert_eq!(
+            dom.xml_declaration().unwrap().encoding(),
+            Some("UTF-8".to_string())
+        );
+        let dom = read_bytes(b"<a/>").unwrap();
+        assert!(dom.xml_declaration().is_none());
+
+        assert!(matches!(
+            read_bytes(b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?><a/>"),
+            Err(Error::UnsupportedEncoding(_))
+        ));
+    }
+
+    #[derive(Default)]
+    struct EventRecorder {
+        events: Vec<String>,
+    }
+
+    impl ContentHandler for EventRecorder {
+        fn xml_declaration(&mut self, declaration: &XmlDecl) -> Result<()> {
+            self.events.push(format!("decl {}", declaration.version()));
+            Ok(())
+        }
+
+        fn document_type(
+            &mut self,
+            declaration: &DocTypeDecl,
+            entities: &[EntityDecl],
+            _notations: &[NotationDecl],
+        ) -> Result<()> {
+            self.events
+                .push(format!("doctype {} {}", declaration.name, entities.len()));
+            Ok(())
+        }
+
+        fn start_element(
+            &mut self,
+            name: &Name,
+            attributes: &[ParsedAttribute],
+            _location: Option<SourceLocation>,
+        ) -> Result<()> {
+            let attributes = attributes
+                .iter()
+                .map(|attribute| {
+                    format!(
+                        " {}{{{}}}={}",
+                        attribute.name.local_name(),
+                        attribute
+                            .name
+                            .namespace_uri()
+                            .as_deref()
+                            .unwrap_or_default(),
+                        attribute.value
+                    )
+                })
+                .collect::<String>();
+            self.events.push(format!(
+                "start {}{{{}}}{}",
+                name.local_name(),
+                name.namespace_uri().as_deref().unwrap_or_default(),
+                attributes
+            ));
+            Ok(())
+        }
+
+        fn end_element(&mut self, name: &Name, _location: Option<SourceLocation>) -> Result<()> {
+            self.events.push(format!("end {}", name));
+            Ok(())
+        }
+
+        fn characters(&mut self, text: &str, _location: Option<SourceLocation>) -> Result<()> {
+            self.events.push(format!("text {:?}", text));
+            Ok(())
+        }
+
+        fn start_entity(&mut self, name: &str, _location: Option<SourceLocation>) -> Result<()> {
+            self.events.push(format!("entity {}", name));
+            Ok(())
+        }
+
+        fn end_entity(&mut self, name: &str) -> Result<()> {
+            self.events.push(format!("end entity {}", name));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_content_handler() {
+        let mut recorder = EventRecorder::default();
+        read_xml_with_handler(
+            r#"<?xml version="1.0"?>
+<!DOCTYPE p:doc [<!ENTITY e "<i>E</i>">]>
+<p:doc xmlns:p="urn:p" p:a="1" b="&e;">x &e;<!-- note --></p:doc>"#,
+            ParserOptions::new().keep_comments(false),
+            &mut recorder,
+        )
+        .unwrap();
+        assert_eq!(
+            recorder.events,
+            vec![
+                "decl 1.0",
+                "doctype p:doc 1",
+                "start doc{urn:p} p{http://www.w3.org/2000/xmlns/}=urn:p a{urn:p}=1 b{}=&e;",
+                "text \"x \"",
+                "entity e",
+                "start i{}",
+                "text \"E\"",
+                "end i",
+                "end entity e",
+                "end p:doc",
+            ]
+        );
+
+        let mut recorder = EventRecorder::default();
+        let error =
+            read_xml_with_handler("<a/><b/>", ParserOptions::new(), &mut recorder).unwrap_err();
+        assert!(matches!(error.inner(), Error::Malformed));
+        assert_eq!(recorder.events, vec!["start a{}", "end a"]);
+
+        let mut recorder = EventRecorder::default();
+        let error = read_reader_with_handler(
+            "<a><b:c/></a>".as_bytes(),
+            ParserOptions::new(),
+            &mut recorder,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error.inner(),
+            Error::DOMError(DOMError::Namespace)
+        ));
+        assert_eq!(recorder.events, vec!["start a{}"]);
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        let mut processing_options = ProcessingOptions::new();
+        processing_options.set_add_namespaces();
+        let document = read_xml_with_options(
+            r#"<!DOCTYPE doc [<!ENTITY e "entity">]>
+<doc xmlns="urn:doc" xmlns:p="urn:p"><pre xml:space="preserve"/></doc>"#,
+            ParserOptions::new().processing_options(processing_options),
+        )
+        .unwrap();
+        let mut root = as_document(&document).unwrap().document_element().unwrap();
+
+        let fragment = parse_fragment(&document, &root, "<a p:b='1'/> text &e; <p:c/>").unwrap();
+        assert_eq!(fragment.node_type(), NodeType::DocumentFragment);
+        assert_eq!(fragment.owner_document(), Some(document.clone()));
+        let children = fragment.child_nodes();
+        assert_eq!(children.len(), 4);
+        assert_eq!(children[0].namespace_uri(), Some("urn:doc".to_string()));
+        assert_eq!(
+            as_element(&children[0])
+                .unwrap()
+                .get_attribute_ns("urn:p", "b"),
+            Some("1".to_string())
+        );
+        assert_eq!(children[1].node_value(), Some("text ".to_string()));
+        assert_eq!(children[2].node_type(), NodeType::EntityReference);
+        assert_eq!(children[3].namespace_uri(), Some("urn:p".to_string()));
+
+        let _safe_to_ignore = root.append_child(fragment).unwrap();
+        assert_eq!(root.child_nodes().len(), 5);
+
+        let pre = root.first_child().unwrap();
+        let fragment = parse_fragment(&document, &pre, " kept ").unwrap();
+        assert_eq!(
+            fragment.first_child().unwrap().node_value(),
+            Some(" kept ".to_string())
+        );
+
+        let error = parse_fragment(&document, &root, "<q:a/>").unwrap_err();
+        assert!(matches!(
+            error.inner(),
+            Error::DOMError(DOMError::Namespace)
+        ));
+        assert!(parse_fragment(&document, &root, "<a>").is_err());
+        assert!(parse_fragment(&document, &document, "<a/>").is_err());
+        let other = read_xml("<other/>").unwrap();
+        assert!(parse_fragment(&other, &root, "<a/>").is_err());
+    }
+
+    #[test]
+    fn test_parse_fragment_unbalanced() {
+        let document = read_xml("<doc><child/></doc>").unwrap();
+        let root = as_document(&document).unwrap().document_element().unwrap();
+
+        for xml in [
+            "<a>",
+            "</a>",
+            "<a></b>",
+            "<a><b></a></b>",
+            "text</doc>",
+            "<a/></a>",
+        ] {
+            let error = parse_fragment(&document, &root, xml).unwrap_err();
+            assert!(
+                matches!(error.inner(), Error::Malformed | Error::QuickXMLError(_)),
+                "expecting malformed input for {}",
+                xml
+            );
+            assert!(error.position().is_some());
+        }
+        // A failed parse leaves the context element unchanged.
+        assert_eq!(root.child_nodes().len(), 1);
+
+        // Balanced elements may follow one another at the top level.
+        let fragment = parse_fragment(&document, &root, "<a><b/></a><a></a>").unwrap();
+        assert_eq!(fragment.child_nodes().len(), 2);
+    }
+
+    struct MapResolver(HashMap<&'static str, &'static str>);
+
+    impl ExternalResolver for MapResolver {
+        fn resolve(
+            &self,
+            _public_id: Option<&str>,
+            system_id: &str,
+            base_uri: Option<&str>,
+        ) -> Result<Option<Vec<u8>>> {
+            let uri = resolver::resolve_relative(base_uri, system_id);
+            Ok(self
+                .0
+                .get(uri.as_str())
+                .map(|text| text.as_bytes().to_vec()))
+        }
+    }
+
+    #[test]
+    fn test_external_resolver() {
+        let xml = r#"<!DOCTYPE book SYSTEM "dtd/book.dtd" [
+  <!ENTITY title "Internal">
+]>
+<book>&title; &chapter; &license;</book>"#;
+        let resolver = || {
+            MapResolver(HashMap::from([
+                (
+                    "file:///docs/dtd/book.dtd",
+                    r#"<?xml version="1.0" encoding="UTF-8"?>
+<!ENTITY title "External">
+<!ENTITY % chapters SYSTEM "chapters.ent">
+<![ %draft; [ <!ENTITY license "Draft"> ]]>
+<![ IGNORE [ <![ INCLUDE [ <!ENTITY license "Ignored"> ]]> ]]>
+<![INCLUDE[ %chapters; ]]>
+<!ENTITY license "Final">"#,
+                ),
+                (
+                    "file:///docs/dtd/chapters.ent",
+                    r#"<!ENTITY chapter SYSTEM "chapter.xml">"#,
+                ),
+                ("file:///docs/dtd/chapter.xml", "<chapter>One</chapter>"),
+            ]))
+        };
+        let options = || {
+            let mut processing_options = ProcessingOptions::new();
+            processing_options.set_parse_entities();
+            ParserOptions::new()
+                .processing_options(processing_options)
+                .base_uri("file:///docs/book.xml")
+        };
+        let internal = "<!ENTITY % draft 'IGNORE'>";
+
+        let dom = read_xml_with_options(
+            xml.replace("<!ENTITY title", &format!("{}<!ENTITY title", internal)),
+            options().external_resolver(resolver()),
+        )
+        .unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        let children = root.child_nodes();
+        assert_eq!(children.len(), 3);
+        assert_eq!(children[0].node_value(), Some("Internal ".to_string()));
+        assert_eq!(children[1].node_name().to_string(), "chapter");
+        assert_eq!(children[2].node_value(), Some(" Final".to_string()));
+
+        let dom = read_xml_with_options(
+            xml.replace(
+                "<!ENTITY title",
+                "<!ENTITY % draft 'INCLUDE'><!ENTITY title",
+            ),
+            options().external_resolver(resolver()),
+        )
+        .unwrap();
+        let root = as_document(&dom).unwrap().document_element().unwrap();
+        assert_eq!(
+            root.last_child().unwrap().node_value(),
+            Some(" Draft".to_string())
+        );
+
+        //
+        // Without a resolver nothing is loaded, and the references are undeclared.
+        //
+        assert!(read_xml_with_options(xml, options()).is_err());
+        assert!(read_xml("<!DOCTYPE a SYSTEM 'a.dtd'><a/>").is_ok());
+        assert!(read_xml("<!DOCTYPE a [<![INCLUDE[ ]]>]><a/>").is_err());
+    }
+
+    fn limit_exceeded(result: Result<()>) -> Option<Limit> {
+        match result.unwrap_err().inner() {
+            Error::LimitExceeded(limit) => Some(*limit),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        struct Ignore;
+        impl ContentHandler for Ignore {}
+
+        let laughs = r#"<!DOCTYPE lolz [
+  <!ENTITY lol "lol">
+  <!ENTITY lol1 "&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;&lol;">
+  <!ENTITY lol2 "&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;&lol1;">
+  <!ENTITY lol3 "&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;&lol2;">
+  <!ENTITY lol4 "&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;&lol3;">
+  <!ENTITY lol5 "&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;&lol4;">
+  <!ENTITY lol6 "&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;&lol5;">
+]>"#;
+        let options = || ParserOptions::new().max_entity_expansion(100_000);
+        let content = format!("{}<lolz>&lol6;</lolz>", laughs);
+        assert_eq!(
+            limit_exceeded(read_xml_with_handler(&content, options(), &mut Ignore)),
+            Some(Limit::EntityExpansion)
+        );
+        let attribute = format!("{}<lolz a='&lol6;'/>", laughs);
+        assert_eq!(
+            limit_exceeded(read_xml_with_handler(&attribute, options(), &mut Ignore)),
+            Some(Limit::EntityExpansion)
+        );
+        let small = format!("{}<lolz a='&lol2;'>&lol2;</lolz>", laughs);
+        assert!(read_xml_with_handler(&small, options(), &mut Ignore).is_ok());
+        assert_eq!(
+            limit_exceeded(read_xml_with_handler(
+                &small,
+                ParserOptions::new().max_entity_depth(2),
+                &mut Ignore
+            )),
+            Some(Limit::EntityDepth)
+        );
+
+        // Character references hide the parameter entity references from the declarations.
+        let parameter_laughs = r#"<!DOCTYPE lolz [
+  <!ENTITY % lol "<!-- lol -->">
+  <!ENTITY % lol1 "&#37;lol;&#37;lol;&#37;lol;&#37;lol;&#37;lol;&#37;lol;&#37;lol;&#37;lol;&#37;lol;&#37;lol;">
+  <!ENTITY % lol2 "&#37;lol1;&#37;lol1;&#37;lol1;&#37;lol1;&#37;lol1;&#37;lol1;&#37;lol1;&#37;lol1;&#37;lol1;&#37;lol1;">
+  <!ENTITY % lol3 "&#37;lol2;&#37;lol2;&#37;lol2;&#37;lol2;&#37;lol2;&#37;lol2;&#37;lol2;&#37;lol2;&#37;lol2;&#37;lol2;">
+  <!ENTITY % lol4 "&#37;lol3;&#37;lol3;&#37;lol3;&#37;lol3;&#37;lol3;&#37;lol3;&#37;lol3;&#37;lol3;&#37;lol3;&#37;lol3;">
+  <!ENTITY % lol5 "&#37;lol4;&#37;lol4;&#37;lol4;&#37;lol4;&#37;lol4;&#37;lol4;&#37;lol4;&#37;lol4;&#37;lol4;&#37;lol4;">
+  <!ENTITY % lol6 "&#37;lol5;&#37;lol5;&#37;lol5;&#37;lol5;&#37;lol5;&#37;lol5;&#37;lol5;&#37;lol5;&#37;lol5;&#37;lol5;">
+  %lol6;
+]><lolz/>"#;
+        assert_eq!(
+            limit_exceeded(read_xml_with_handler(
+                parameter_laughs,
+                options(),
+                &mut Ignore
+            )),
+            Some(Limit::EntityExpansion)
+        );
+
+        let deep = format!("{}{}", "<a>".repeat(300), "</a>".repeat(300));
+        assert_eq!(
+            limit_exceeded(read_xml_with_handler(
+                &deep,
+                Default::default(),
+                &mut Ignore
+            )),
+            Some(Limit::ElementDepth)
+        );
+        assert!(read_xml_with_handler(
+            &deep,
+            ParserOptions::new().max_element_depth(300),
+            &mut Ignore
+        )
+        .is_ok());
+
+        let options = ParserOptions::new().max_attributes(2);
+        assert!(read_xml_with_handler("<a b='1' c='2'/>", options.clone(), &mut Ignore).is_ok());
+        assert_eq!(
+            limit_exceeded(read_xml_with_handler(
+                "<a b='1' c='2' d='3'/>",
+                options,
+                &mut Ignore
+            )),
+            Some(Limit::Attributes)
+        );
+
+        let options = ParserOptions::new().max_nodes(4);
+        assert!(read_xml_with_handler("<a b='1'>x<c/></a>", options.clone(), &mut Ignore).is_ok());
+        assert_eq!(
+            limit_exceeded(read_xml_with_handler(
+                "<a b='1'>x<c/><!-- -->",
+                options,
+                &mut Ignore
+            )),
+            Some(Limit::Nodes)
+        );
+    }
+
+    #[test]
+    fn test_limits_at_threshold() {
+        struct Ignore;
+        impl ContentHandler for Ignore {}
+
+        let at_and_over = |xml: &str, options: fn(usize) -> ParserOptions, threshold: usize| {
+            assert!(
+                read_xml_with_handler(xml, options(threshold), &mut Ignore).is_ok(),
+                "expecting {} to be within a limit of {}",
+                xml,
+                threshold
+            );
+            limit_exceeded(read_xml_with_handler(
+                xml,
+                options(threshold - 1),
+                &mut Ignore,
+            ))
+        };
+
+        let nested = r#"<!DOCTYPE a [
+  <!ENTITY e1 "12345">
+  <!ENTITY e2 "&e1;">
+  <!ENTITY e3 "&e2;">
+]><a>&e3;</a>"#;
+        assert_eq!(
+            at_and_over(nested, |max| ParserOptions::new().max_entity_depth(max), 3),
+            Some(Limit::EntityDepth)
+        );
+        // The replacement text of e3, e2, and e1 is 4 + 4 + 5 bytes.
+        assert_eq!(
+            at_and_over(
+                nested,
+                |max| ParserOptions::new().max_entity_expansion(max),
+                13
+            ),
+            Some(Limit::EntityExpansion)
+        );
+        let parameters = r#"<!DOCTYPE a [
+  <!ENTITY % p1 "<!ENTITY e '1'>">
+  <!ENTITY % p2 "&#37;p1;">
+  %p2;
+]><a>&e;</a>"#;
+        assert_eq!(
+            at_and_over(
+                parameters,
+                |max| ParserOptions::new().max_entity_depth(max),
+                2
+            ),
+            Some(Limit::EntityDepth)
+        );
+        // The replacement text of p2 and p1 is 4 + 15 bytes, and of e 1 byte.
+        assert_eq!(
+            at_and_over(
+                parameters,
+                |max| ParserOptions::new().max_entity_expansion(max),
+                20
+            ),
+            Some(Limit::EntityExpansion)
+        );
+        let attribute = r#"<!DOCTYPE a [<!ENTITY e1 "12345">]><a b="&e1;&e1;"/>"#;
+        assert_eq!(
+            at_and_over(
+                attribute,
+                |max| ParserOptions::new().max_entity_expansion(max),
+                10
+            ),
+            Some(Limit::EntityExpansion)
+        );
+
+        assert_eq!(
+            at_and_over(
+                "<a><b><c/></b></a>",
+                |max| ParserOptions::new().max_element_depth(max),
+                3
+            ),
+            Some(Limit::ElementDepth)
+        );
+        assert_eq!(
+            at_and_over(
+                "<a b='1' c='2' d='3'/>",
+                |max| ParserOptions::new().max_attributes(max),
+                3
+            ),
+            Some(Limit::Attributes)
+        );
+        // An element and its attributes, text, and the child element.
+        assert_eq!(
+            at_and_over(
+                "<a b='1'>x<c/></a>",
+                |max| ParserOptions::new().max_nodes(max),
+                4
+            ),
+            Some(Limit::Nodes)
+        );
+    }
+
+    fn invalid_character(xml: &str) -> bool {
+        matches!(
+            read_xml(xml).map_err(|err| err.inner().to_string()),
+            Err(message) if message == Error::InvalidCharacter.to_string()
+        )
+    }
+
+    #[test]
+    fn test_xml_version_characters() {
+        const XML_10: &str = r#"<?xml version="1.0"?>"#;
+        const XML_11: &str = r#"<?xml version="1.1"?>"#;
+
+        for content in [
+            "<doc>\u{1}</doc>",
+            "<doc>&#x1;</doc>",
+            "<doc>&#0;</doc>",
+            "<doc a='\u{1}'/>",
+            "<doc a='&#x1;'/>",
+            "<doc><!-- \u{1} --></doc>",
+            "<doc><?pi \u{1}?></doc>",
+            "<doc><![CDATA[\u{1}]]></doc>",
+            "<doc>\u{FFFE}</doc>",
+        ] {
+            assert!(invalid_character(content), "{:?}", content);
+            assert!(invalid_character(&format!("{}{}", XML_10, content)));
+        }
+        for content in ["<doc>\u{7F}\u{85}</doc>", "<doc a='\u{7F}'/>"] {
+            assert!(read_xml(format!("{}{}", XML_10, content)).is_ok());
+        }
+
+        for content in [
+            "<doc>\u{1}</doc>",
+            "<doc>\u{7F}</doc>",
+            "<doc>&#0;</doc>",
+            "<doc a='\u{1}'/>",
+            "<doc><!-- \u{1} --></doc>",
+            "<doc><?pi \u{1}?></doc>",
+            "<doc><![CDATA[\u{7F}]]></doc>",
+        ] {
+            assert!(invalid_character(&format!("{}{}", XML_11, content)));
+        }
+        let document =
+            read_xml(format!("{}<doc a='&#x1;'>&#x1;&#x7F;&#x85;</doc>", XML_11)).unwrap();
+        let element = document.first_child().unwrap();
+        assert_eq!(
+            element
+                .get_attribute_node("a")
+                .and_then(|attribute| attribute.first_child())
+                .and_then(|text| text.node_value()),
+            Some("\u{1}".to_string())
+        );
+        assert_eq!(
+            element.first_child().unwrap().node_value(),
+            Some("\u{1}\u{7F}\u{85}".to_string())
+        );
+
+        assert!(invalid_character("<1doc/>"));
+        assert!(invalid_character("<doc 1a='a'/>"));
+        assert!(invalid_character("<doc><?1pi?></doc>"));
+        assert!(matches!(
+            read_xml(r#"<?xml version="1.2"?><doc/>"#).map_err(|err| err.inner().to_string()),
+            Err(message) if message == Error::Malformed.to_string()
+        ));
+    }
+
+    #[test]
+    fn test_xml_11_line_ends() {
+        let text = |xml: &str| {
+            let document =
+                read_xml_with_options(xml, ParserOptions::new().trim_text(false)).unwrap();
+            let element = document.first_child().unwrap();
+            (
+                element.get_attribute("a"),
+                element.first_child().and_then(|child| child.node_value()),
+            )
+        };
+        assert_eq!(
+            text("<?xml version='1.1'?><a>x\u{85}y</a>"),
+            (None, Some("x\ny".to_string()))
+        );
+        assert_eq!(
+            text("<?xml version='1.1'?><a a='1\r\u{85}2'>x\r\u{85}y\u{2028}z&#x2028;</a>"),
+            (Some("1 2".to_string()), Some("x\ny\nz\u{2028}".to_string()))
+        );
+        assert_eq!(
+            text("<?xml version='1.1'?><a><![CDATA[x\u{85}y]]></a>"),
+            (None, Some("x\ny".to_string()))
+        );
+        assert_eq!(
+            text("<a>x\r\u{85}y\u{2028}</a>"),
+            (None, Some("x\n\u{85}y\u{2028}".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_recovering() {
+        use crate::level2::convert::as_element;
+
+        let recover = |xml: &str| {
+            let (document, warnings) = read_xml_recovering(xml).unwrap();
+            let messages: Vec<String> = warnings.into_iter().map(|w| w.message).collect();
+            (document.to_string(), messages)
+        };
+
+        assert_eq!(
+            recover("<a><b>text</a>"),
+            (
+                "<a><b>text</b></a>".to_string(),
+                vec!["element 'b' is not closed before end tag 'a'".to_string()]
+            )
+        );
+        assert_eq!(
+            recover("<a><b><c></a>"),
+            (
+                "<a><b><c></c></b></a>".to_string(),
+                vec![
+                    "element 'c' is not closed before end tag 'a'".to_string(),
+                    "element 'b' is not closed".to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            recover("<a><b></c></b></a>"),
+            (
+                "<a><b></b></a>".to_string(),
+                vec!["ignoring end tag 'c' with no start tag".to_string()]
+            )
+        );
+        assert_eq!(
+            recover("</x><a/>"),
+            (
+                "<a></a>".to_string(),
+                vec!["ignoring end tag 'x' with no start tag".to_string()]
+            )
+        );
+        assert_eq!(
+            recover("<a><b>"),
+            (
+                "<a><b></b></a>".to_string(),
+                vec![
+                    "element 'b' is not closed".to_string(),
+                    "element 'a' is not closed".to_string()
+                ]
+            )
+        );
+
+        let (document, warnings) = read_xml_recovering("<a x='1 & 2'>AT&T &nbsp;</a>").unwrap();
+        assert_eq!(warnings.len(), 3);
+        let element = document.first_child().unwrap();
+        assert_eq!(
+            element.first_child().unwrap().node_value(),
+            Some("AT&T &nbsp;".to_string())
+        );
+        let attribute = as_element(&element)
+            .unwrap()
+            .get_attribute_node("x")
+            .unwrap();
+        assert_eq!(
+            attribute.first_child().unwrap().node_value(),
+            Some("1 & 2".to_string())
+        );
+
+        let (_, warnings) = read_xml_recovering("<a>\n  <b>\n</a>").unwrap();
+        assert_eq!(warnings[0].position.map(|p| p.line), Some(3));
+        assert!(warnings[0].to_string().contains(" at line 3, "));
+
+        let (_, warnings) = read_xml_recovering("<a><b/>text</a>").unwrap();
+        assert!(warnings.is_empty());
+
+        assert!(read_xml("<a><b>text</a>").is_err());
+        assert!(read_xml("<a><b>").is_err());
+        assert!(read_xml("</x><a/>").is_err());
+        assert!(read_xml("<a>AT&T</a>").is_err());
+        assert!(read_xml_recovering("<a x='1></a>").is_err());
+    }
+
+    #[test]
+    fn test_doctype_misplaced() {
+        assert!(read_xml("<!DOCTYPE a><!DOCTYPE a><a/>").is_err());
+        assert!(read_xml("<a/><!DOCTYPE a>").is_err());
+    }
+
+    #[test]
+    fn test_xml_declaration_misplaced() {
+        assert!(read_xml(r#"<?xml version="1.0"?><a/>"#).is_ok());
+        assert!(read_xml("\u{FEFF}<?xml version=\"1.0\"?><a/>").is_ok());
+        assert!(read_bytes(b"\xEF\xBB\xBF<?xml version=\"1.0\"?><a/>").is_ok());
+        assert!(read_xml("\n<?xml version=\"1.0\"?><a/>").is_ok());
+        for xml in [
+            r#"<?xml version="1.0"?><?xml version="1.0"?><a/>"#,
+            r#"<!-- c --><?xml version="1.1"?><a/>"#,
+            r#"<?pi?><?xml version="1.1"?><a/>"#,
+            r#"<!DOCTYPE a><?xml version="1.1"?><a/>"#,
+            r#"<a/><?xml version="1.0"?>"#,
+        ] {
+            let error = read_xml(xml).unwrap_err();
+            assert!(
+                matches!(error.inner(), Error::Malformed),
+                "expecting malformed for {}",
+                xml
+            );
+        }
+    }
+
+    #[test]
+    fn test_xml_declaration_without_version() {
+        for xml in [
+            r#"<?xml encoding="UTF-8"?><a/>"#,
+            r#"<?xml standalone="yes"?><a/>"#,
+        ] {
+            let error = read_xml(xml).unwrap_err();
+            assert!(
+                matches!(error.inner(), Error::Malformed),
+                "expecting malformed for {}",
+                xml
+            );
+            assert_eq!(error.position().unwrap().line, 1);
+            assert!(read_xml_recovering(xml).is_err());
+        }
+    }
+
     #[test]
     fn test_its_complicated() {
         test_good_xml(
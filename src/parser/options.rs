@@ -0,0 +1,332 @@
+/*!
+Options that control the behavior of the parser, see
+[`read_xml_with_options`](../fn.read_xml_with_options.html).
+*/
+
+use crate::level2::ext::dom_impl::get_implementation_ext;
+use crate::level2::ext::{DOMImplementation, ProcessingOptions};
+use crate::level2::RefNode;
+use crate::parser::resolver::ExternalResolver;
+use crate::shared::text::{DEFAULT_MAX_ENTITY_DEPTH, DEFAULT_MAX_ENTITY_EXPANSION};
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::rc::Rc;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Options that control the behavior of the parser. The default options are those used by
+/// [`read_xml`](../fn.read_xml.html) and [`read_reader`](../fn.read_reader.html).
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::as_document;
+/// use xml_dom::level2::ext::SourceLocated;
+/// use xml_dom::level2::Document;
+/// use xml_dom::parser::{read_xml_with_options, ParserOptions};
+///
+/// let options = ParserOptions::new().track_locations(true);
+/// let dom = read_xml_with_options("<xml>\n  <element/>\n</xml>", options).unwrap();
+/// let root = as_document(&dom).unwrap().document_element().unwrap();
+/// let location = root.source_location().unwrap();
+/// assert_eq!(location.end.line, 3);
+/// ```
+///
+/// Whitespace is significant in mixed content, in which case text should not be trimmed.
+///
+/// ```rust
+/// use xml_dom::level2::convert::as_document;
+/// use xml_dom::level2::{Document, Node};
+/// use xml_dom::parser::{read_xml_with_options, ParserOptions};
+///
+/// let options = ParserOptions::new().trim_text(false);
+/// let dom = read_xml_with_options("<p>Some <b>bold</b> text</p>", options).unwrap();
+/// let root = as_document(&dom).unwrap().document_element().unwrap();
+/// let first_text = root.first_child().unwrap();
+/// assert_eq!(first_text.node_value(), Some("Some ".to_string()));
+/// ```
+///
+#[derive(Clone)]
+pub struct ParserOptions {
+    pub(crate) track_locations: bool,
+    pub(crate) trim_text: bool,
+    pub(crate) namespace_aware: bool,
+    pub(crate) keep_comments: bool,
+    pub(crate) keep_processing_instructions: bool,
+    pub(crate) cdata_as_text: bool,
+    pub(crate) processing_options: ProcessingOptions,
+    pub(crate) implementation: &'static dyn DOMImplementation<NodeRef = RefNode>,
+    pub(crate) max_entity_depth: usize,
+    pub(crate) max_entity_expansion: usize,
+    pub(crate) max_element_depth: usize,
+    pub(crate) max_attributes: usize,
+    pub(crate) max_nodes: usize,
+    pub(crate) base_uri: Option<String>,
+    pub(crate) external_resolver: Option<Rc<dyn ExternalResolver>>,
+}
+
+///
+/// The limits on the resources used to parse a document, these protect against input such as the
+/// "billion laughs" attack that expands a small document into one that exhausts memory. When a
+/// limit is exceeded the parser returns
+/// [`Error::LimitExceeded`](../enum.Error.html#variant.LimitExceeded).
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// The depth to which references to entities are nested, see
+    /// [`ParserOptions::max_entity_depth`](struct.ParserOptions.html#method.max_entity_depth).
+    EntityDepth,
+    /// The total size of the replacement text of all entity references, see
+    /// [`ParserOptions::max_entity_expansion`](struct.ParserOptions.html#method.max_entity_expansion).
+    EntityExpansion,
+    /// The depth to which elements are nested, see
+    /// [`ParserOptions::max_element_depth`](struct.ParserOptions.html#method.max_element_depth).
+    ElementDepth,
+    /// The number of attributes of a single element, see
+    /// [`ParserOptions::max_attributes`](struct.ParserOptions.html#method.max_attributes).
+    Attributes,
+    /// The total number of nodes, see
+    /// [`ParserOptions::max_nodes`](struct.ParserOptions.html#method.max_nodes).
+    Nodes,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            track_locations: false,
+            trim_text: true,
+            namespace_aware: true,
+            keep_comments: true,
+            keep_processing_instructions: true,
+            cdata_as_text: false,
+            processing_options: Default::default(),
+            implementation: get_implementation_ext(),
+            max_entity_depth: DEFAULT_MAX_ENTITY_DEPTH,
+            max_entity_expansion: DEFAULT_MAX_ENTITY_EXPANSION,
+            max_element_depth: 256,
+            max_attributes: 1024,
+            max_nodes: usize::MAX,
+            base_uri: None,
+            external_resolver: None,
+        }
+    }
+}
+
+impl Debug for ParserOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ParserOptions")
+            .field("track_locations", &self.track_locations)
+            .field("trim_text", &self.trim_text)
+            .field("namespace_aware", &self.namespace_aware)
+            .field("keep_comments", &self.keep_comments)
+            .field(
+                "keep_processing_instructions",
+                &self.keep_processing_instructions,
+            )
+            .field("cdata_as_text", &self.cdata_as_text)
+            .field("processing_options", &self.processing_options)
+            .field("max_entity_depth", &self.max_entity_depth)
+            .field("max_entity_expansion", &self.max_entity_expansion)
+            .field("max_element_depth", &self.max_element_depth)
+            .field("max_attributes", &self.max_attributes)
+            .field("max_nodes", &self.max_nodes)
+            .field("base_uri", &self.base_uri)
+            .field("external_resolver", &self.external_resolver.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{}",
+            match self {
+                Limit::EntityDepth => "entity depth",
+                Limit::EntityExpansion => "entity expansion",
+                Limit::ElementDepth => "element depth",
+                Limit::Attributes => "attributes per element",
+                Limit::Nodes => "number of nodes",
+            }
+        )
+    }
+}
+
+impl ParserOptions {
+    ///
+    /// Construct the default set of options.
+    ///
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    ///
+    /// Record the location in the source text of each element, attribute, text, comment, and
+    /// processing instruction node. The location of a node is available using the
+    /// [`SourceLocated`](../../level2/ext/trait.SourceLocated.html) trait. The default is `false`
+    /// in which case no location information is computed or stored.
+    ///
+    pub fn track_locations(mut self, track_locations: bool) -> Self {
+        self.track_locations = track_locations;
+        self
+    }
+
+    ///
+    /// Trim leading and trailing whitespace from text, text that is only whitespace is dropped
+    /// entirely. The default is `true`; set this to `false` to preserve whitespace in mixed
+    /// content, in which case all text within the document element is kept as-is.
+    ///
+    pub fn trim_text(mut self, trim_text: bool) -> Self {
+        self.trim_text = trim_text;
+        self
+    }
+
+    ///
+    /// Resolve the prefixes of element and attribute names against the `xmlns` declarations in
+    /// scope, creating nodes with `create_element_ns` and `create_attribute_ns`. A reference to an
+    /// undeclared prefix is reported as a DOM `Namespace` error. The default is `true`; if `false`
+    /// all names are created without a namespace URI.
+    ///
+    pub fn namespace_aware(mut self, namespace_aware: bool) -> Self {
+        self.namespace_aware = namespace_aware;
+        self
+    }
+
+    ///
+    /// Keep comments in the resulting document. The default is `true`.
+    ///
+    pub fn keep_comments(mut self, keep_comments: bool) -> Self {
+        self.keep_comments = keep_comments;
+        self
+    }
+
+    ///
+    /// Keep processing instructions in the resulting document. The default is `true`.
+    ///
+    pub fn keep_processing_instructions(mut self, keep_processing_instructions: bool) -> Self {
+        self.keep_processing_instructions = keep_processing_instructions;
+        self
+    }
+
+    ///
+    /// Create `Text` nodes rather than `CDATASection` nodes for CDATA sections. The default is
+    /// `false`. Note that the resulting text is not merged with any adjacent text nodes, see
+    /// [`Node::normalize`](../../level2/trait.Node.html#tymethod.normalize).
+    ///
+    pub fn cdata_as_text(mut self, cdata_as_text: bool) -> Self {
+        self.cdata_as_text = cdata_as_text;
+        self
+    }
+
+    ///
+    /// The options the resulting document is created with, see
+    /// [`ProcessingOptions`](../../level2/ext/options/struct.ProcessingOptions.html). The default
+    /// is that none of the options are set.
+    ///
+    pub fn processing_options(mut self, processing_options: ProcessingOptions) -> Self {
+        self.processing_options = processing_options;
+        self
+    }
+
+    ///
+    /// The implementation used to create the resulting document and its document type. The
+    /// default is the one returned by
+    /// [`get_implementation_ext`](../../level2/ext/dom_impl/fn.get_implementation_ext.html).
+    ///
+    pub fn implementation(
+        mut self,
+        implementation: &'static dyn DOMImplementation<NodeRef = RefNode>,
+    ) -> Self {
+        self.implementation = implementation;
+        self
+    }
+
+    ///
+    /// The maximum depth to which references to entities may be nested, within content, within
+    /// an attribute value, or within the document type declaration. The default is 20.
+    ///
+    pub fn max_entity_depth(mut self, max_entity_depth: usize) -> Self {
+        self.max_entity_depth = max_entity_depth;
+        self
+    }
+
+    ///
+    /// The maximum total size, in bytes, of the replacement text of all the entity references in
+    /// a document, including parameter entity references, counting each reference separately.
+    /// The default is 10 MiB.
+    ///
+    pub fn max_entity_expansion(mut self, max_entity_expansion: usize) -> Self {
+        self.max_entity_expansion = max_entity_expansion;
+        self
+    }
+
+    ///
+    /// The maximum depth to which elements may be nested, the document element has a depth of
+    /// one. The default is 256; as the parser is recursive a much larger limit risks exhausting
+    /// the stack.
+    ///
+    pub fn max_element_depth(mut self, max_element_depth: usize) -> Self {
+        self.max_element_depth = max_element_depth;
+        self
+    }
+
+    ///
+    /// The maximum number of attributes, including namespace declarations, on a single element.
+    /// The default is 1024.
+    ///
+    pub fn max_attributes(mut self, max_attributes: usize) -> Self {
+        self.max_attributes = max_attributes;
+        self
+    }
+
+    ///
+    /// The maximum number of elements, attributes, text, CDATA section, comment, processing
+    /// instruction, and entity reference nodes in a document; for
+    /// [`read_subtrees`](../fn.read_subtrees.html) this applies to each subtree. The default is
+    /// no limit.
+    ///
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    ///
+    /// The URI of the document, against which the relative system identifiers of entities
+    /// declared in its internal subset are resolved; it is recorded as the `document_uri` of
+    /// the parsed document. The default is `None`.
+    ///
+    pub fn base_uri(mut self, base_uri: impl Into<String>) -> Self {
+        self.base_uri = Some(base_uri.into());
+        self
+    }
+
+    ///
+    /// The resolver used to load external parsed entities and the external subset of the
+    /// document type declaration, see
+    /// [`ExternalResolver`](trait.ExternalResolver.html). The default is that no external
+    /// resources are loaded, references to external entities are reported without any content.
+    ///
+    pub fn external_resolver(mut self, resolver: impl ExternalResolver + 'static) -> Self {
+        self.external_resolver = Some(Rc::new(resolver));
+        self
+    }
+
+    ///
+    /// Returns the maximum value for `limit`.
+    ///
+    pub(crate) fn limit(&self, limit: Limit) -> usize {
+        match limit {
+            Limit::EntityDepth => self.max_entity_depth,
+            Limit::EntityExpansion => self.max_entity_expansion,
+            Limit::ElementDepth => self.max_element_depth,
+            Limit::Attributes => self.max_attributes,
+            Limit::Nodes => self.max_nodes,
+        }
+    }
+}
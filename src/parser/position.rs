@@ -0,0 +1,210 @@
+/*!
+Support for reporting the location of parser errors. The quick-xml reader only reports a byte
+offset, so the underlying input is wrapped in a reader that counts lines and columns as bytes are
+consumed. The most recently consumed bytes are retained, both for use as an error snippet and
+because the quick-xml offset may be slightly behind the bytes consumed from the input. Positions
+further back than that window are not known here, the parser finds them from the start of the
+event containing them and takes the snippet from the bytes of that event, see `snippet_at`.
+*/
+
+use crate::level2::ext::Position;
+use std::io::{BufRead, Read};
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Implemented by the readers used by the parser, only readers over the original input track
+/// positions.
+///
+pub(crate) trait SourceRead: BufRead {
+    ///
+    /// Returns the position at `byte_offset`, or `None` if it is not within the last
+    /// `SNIPPET_CONTEXT` bytes of the consumed input.
+    ///
+    fn position_at(&self, byte_offset: usize) -> Option<Position>;
+}
+
+///
+/// Wraps the reader passed to quick-xml to track the position of consumed input.
+///
+#[derive(Debug)]
+pub(crate) struct PositionReader<B: BufRead> {
+    inner: B,
+    window_start: Position,
+    recent: Vec<u8>,
+}
+
+const SNIPPET_CONTEXT: usize = 32;
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<B: BufRead> PositionReader<B> {
+    pub(crate) fn new(inner: B) -> Self {
+        Self {
+            inner,
+            window_start: Position::start(),
+            recent: Vec::with_capacity(SNIPPET_CONTEXT * 2),
+        }
+    }
+
+    ///
+    /// Returns the input surrounding the current position, the bytes most recently consumed and
+    /// those that follow.
+    ///
+    pub(crate) fn snippet(&mut self) -> String {
+        let mut bytes = self.recent.clone();
+        if let Ok(following) = self.inner.fill_buf() {
+            bytes.extend(following.iter().take(SNIPPET_CONTEXT));
+        }
+        snippet_of(&bytes)
+    }
+}
+
+impl<B: BufRead> SourceRead for PositionReader<B> {
+    fn position_at(&self, byte_offset: usize) -> Option<Position> {
+        let count = byte_offset.checked_sub(self.window_start.byte_offset)?;
+        let recent = self.recent.get(..count)?;
+        Some(self.window_start.advanced(recent))
+    }
+}
+
+impl SourceRead for &[u8] {
+    fn position_at(&self, _byte_offset: usize) -> Option<Position> {
+        None
+    }
+}
+
+impl<B: BufRead> Read for PositionReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        track(&mut self.window_start, &mut self.recent, &buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<B: BufRead> BufRead for PositionReader<B> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        //
+        // The buffer is still filled, so this only returns the bytes about to be consumed.
+        //
+        if let Ok(buffer) = self.inner.fill_buf() {
+            track(
+                &mut self.window_start,
+                &mut self.recent,
+                &buffer[..amt.min(buffer.len())],
+            );
+        }
+        self.inner.consume(amt);
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Returns the input surrounding `offset` within `bytes`, the bytes of a single event.
+///
+pub(crate) fn snippet_at(bytes: &[u8], offset: usize) -> String {
+    let start = offset.saturating_sub(SNIPPET_CONTEXT);
+    let end = offset.saturating_add(SNIPPET_CONTEXT).min(bytes.len());
+    snippet_of(&bytes[start.min(end)..end])
+}
+
+fn snippet_of(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_matches(char::REPLACEMENT_CHARACTER)
+        .to_string()
+}
+
+fn track(window_start: &mut Position, recent: &mut Vec<u8>, consumed: &[u8]) {
+    //
+    // Bytes that fall out of the retained window are counted into its start position.
+    //
+    let overflow = (recent.len() + consumed.len()).saturating_sub(SNIPPET_CONTEXT);
+    let from_recent = overflow.min(recent.len());
+    let from_consumed = overflow - from_recent;
+    *window_start = window_start
+        .advanced(&recent[..from_recent])
+        .advanced(&consumed[..from_consumed]);
+    recent.copy_within(from_recent.., 0);
+    recent.truncate(recent.len() - from_recent);
+    recent.extend_from_slice(&consumed[from_consumed..]);
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_tracking() {
+        let mut reader = PositionReader::new("<a>\n  <b>é</b>\n</a>".as_bytes());
+        reader.consume(4);
+        assert_eq!(
+            reader.position_at(4),
+            Some(Position {
+                line: 2,
+                column: 1,
+                byte_offset: 4
+            })
+        );
+        reader.consume(7);
+        assert_eq!(reader.position_at(12), None);
+        assert_eq!(
+            reader.position_at(11),
+            Some(Position {
+                line: 2,
+                column: 7,
+                byte_offset: 11
+            })
+        );
+        assert_eq!(
+            reader.position_at(3),
+            Some(Position {
+                line: 1,
+                column: 4,
+                byte_offset: 3
+            })
+        );
+        assert_eq!(reader.snippet(), "<a>\n  <b>é</b>\n</a>");
+    }
+
+    #[test]
+    fn test_snippet_window() {
+        let input = format!("{}<bad>{}", "x".repeat(100), "y".repeat(100));
+        let mut reader = PositionReader::new(input.as_bytes());
+        reader.consume(50);
+        reader.consume(55);
+        assert_eq!(reader.position_at(100).unwrap().column, 101);
+        assert_eq!(reader.position_at(73).unwrap().byte_offset, 73);
+        assert_eq!(reader.position_at(10), None);
+        assert_eq!(
+            reader.snippet(),
+            format!("{}<bad>{}", "x".repeat(27), "y".repeat(32))
+        );
+    }
+
+    #[test]
+    fn test_snippet_at() {
+        let input = format!("{}<bad>{}", "x".repeat(100), "y".repeat(100));
+        assert_eq!(
+            snippet_at(input.as_bytes(), 100),
+            format!("{}<bad>{}", "x".repeat(32), "y".repeat(27))
+        );
+        assert_eq!(snippet_at(b"<bad>", 0), "<bad>");
+        // Characters split by the edges of the snippet are dropped.
+        assert_eq!(snippet_at("é".repeat(40).as_bytes(), 41), "é".repeat(31));
+    }
+}
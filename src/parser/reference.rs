@@ -0,0 +1,307 @@
+/*!
+Support for references in character data. Character references and references to the predefined
+entities are always replaced by the parser, references to general entities are returned so that
+the parser may either construct an `EntityReference` node or expand the reference in place. Line
+ends in the text around references are normalized, a line end written as a character reference is
+not.
+
+```ebnf
+Reference         ::= EntityRef | CharRef
+
+EntityRef         ::= '&' Name ';'
+
+CharRef           ::= '&#' [0-9]+ ';'
+                    | '&#x' [0-9a-fA-F]+ ';'
+```
+
+*/
+
+use crate::level2::ext::XmlVersion;
+use crate::parser::{Error, Result};
+use crate::shared::syntax::{
+    XML_ENTITYREF_END, XML_ENTITYREF_START, XML_HEX_NUMBERED_ENTITYREF_START,
+    XML_NUMBERED_ENTITYREF_START,
+};
+use crate::shared::text::{is_xml_name, predefined_entity};
+use std::borrow::Cow;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A part of character data, either text with all character references replaced, or the name of
+/// a referenced general entity.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TextPart<'a> {
+    Text(String),
+    EntityRef(&'a str),
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Split raw character data into text and general entity references.
+///
+pub(crate) fn split_references(raw: &str, version: XmlVersion) -> Result<Vec<TextPart<'_>>> {
+    split(raw, false, version).map(|(parts, _)| parts)
+}
+
+///
+/// Returns the offset in `raw` of the first ampersand that does not start a well-formed
+/// reference, that is the reference `split_references` fails on.
+///
+pub(crate) fn malformed_reference_offset(raw: &str) -> Option<usize> {
+    //
+    // Offsets are within `raw`, so are not affected by line end normalization.
+    //
+    split(raw, true, XmlVersion::V10)
+        .ok()
+        .and_then(|(_, bare_ampersands)| bare_ampersands.first().copied())
+}
+
+///
+/// Split raw character data as `split_references` does, except that an ampersand that does not
+/// start a well-formed reference is kept as text rather than being an error. Also returns the
+/// number of such bare ampersands.
+///
+pub(crate) fn split_references_recovering(
+    raw: &str,
+    version: XmlVersion,
+) -> (Vec<TextPart<'_>>, usize) {
+    let (parts, bare_ampersands) =
+        split(raw, true, version).expect("recovering split does not fail");
+    (parts, bare_ampersands.len())
+}
+
+///
+/// Return the character for a complete character reference such as `&#169;` or `&#xA9;`.
+///
+pub(crate) fn char_from_reference(reference: &str) -> Result<char> {
+    let digits = reference
+        .strip_suffix(XML_ENTITYREF_END)
+        .unwrap_or(reference);
+    //
+    // The integer parsing functions also accept a leading sign, which the grammar does not.
+    //
+    let code_point = if let Some(digits) = digits.strip_prefix(XML_HEX_NUMBERED_ENTITYREF_START) {
+        Some(digits)
+            .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+    } else if let Some(digits) = digits.strip_prefix(XML_NUMBERED_ENTITYREF_START) {
+        Some(digits)
+            .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse::<u32>().ok())
+    } else {
+        None
+    };
+    match code_point.and_then(char::from_u32) {
+        None => {
+            error!("invalid character reference {:?}", reference);
+            Error::InvalidCharacter.into()
+        }
+        Some(c) => Ok(c),
+    }
+}
+
+///
+/// Normalize the line ends in literal character data, as described in
+/// [§2.11 End-of-Line Handling](https://www.w3.org/TR/xml/#sec-line-ends); each `#xD #xA` pair,
+/// and each `#xD` not followed by `#xA`, is replaced by `#xA`. XML 1.1
+/// [also replaces](https://www.w3.org/TR/xml11/#sec-line-ends) each `#xD #x85` pair, and each
+/// `#x85` or `#x2028`.
+///
+pub(crate) fn normalize_line_ends(literal: &str, version: XmlVersion) -> Cow<'_, str> {
+    let is_line_end = |c: char| c == '\r' || (version == XmlVersion::V11 && is_xml_11_line_end(c));
+    if !literal.contains(is_line_end) {
+        return Cow::Borrowed(literal);
+    }
+    let mut normalized = String::with_capacity(literal.len());
+    let mut chars = literal.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\r' {
+            let _safe_to_ignore = chars.next_if(|next| {
+                *next == '\n' || (version == XmlVersion::V11 && *next == XML_11_NEL)
+            });
+            normalized.push('\n');
+        } else if is_line_end(c) {
+            normalized.push('\n');
+        } else {
+            normalized.push(c);
+        }
+    }
+    Cow::Owned(normalized)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const XML_11_NEL: char = '\u{85}';
+const XML_11_LINE_SEPARATOR: char = '\u{2028}';
+
+fn is_xml_11_line_end(c: char) -> bool {
+    c == XML_11_NEL || c == XML_11_LINE_SEPARATOR
+}
+
+///
+/// When recovering, the offsets of bare ampersands are returned with the parts.
+///
+fn split(raw: &str, recover: bool, version: XmlVersion) -> Result<(Vec<TextPart<'_>>, Vec<usize>)> {
+    let mut parts: Vec<TextPart<'_>> = Default::default();
+    let mut text = String::new();
+    let mut bare_ampersands: Vec<usize> = Default::default();
+    let mut rest = raw;
+    while let Some(start) = rest.find(XML_ENTITYREF_START) {
+        text.push_str(&normalize_line_ends(&rest[..start], version));
+        rest = &rest[start..];
+        let reference = rest
+            .find(XML_ENTITYREF_END)
+            .map(|end| (&rest[..=end], &rest[XML_ENTITYREF_START.len()..end]));
+        let result = match reference {
+            None => {
+                error!("reference is not closed: {:?}", rest);
+                Error::Malformed.into()
+            }
+            Some((reference, _)) if reference.starts_with(XML_NUMBERED_ENTITYREF_START) => {
+                char_from_reference(reference).map(|c| {
+                    text.push(c);
+                    reference.len()
+                })
+            }
+            Some((reference, name)) => match predefined_entity(name) {
+                Some(c) => {
+                    text.push(c);
+                    Ok(reference.len())
+                }
+                None if is_xml_name(name) => {
+                    if !text.is_empty() {
+                        parts.push(TextPart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(TextPart::EntityRef(name));
+                    Ok(reference.len())
+                }
+                None => {
+                    error!("invalid entity reference: {:?}", reference);
+                    Error::Malformed.into()
+                }
+            },
+        };
+        match result {
+            Ok(length) => rest = &rest[length..],
+            Err(_) if recover => {
+                bare_ampersands.push(raw.len() - rest.len());
+                text.push_str(XML_ENTITYREF_START);
+                rest = &rest[XML_ENTITYREF_START.len()..];
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    text.push_str(&normalize_line_ends(rest, version));
+    if !text.is_empty() {
+        parts.push(TextPart::Text(text));
+    }
+    Ok((parts, bare_ampersands))
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_references() {
+        assert_eq!(split_references("", XmlVersion::V10).unwrap(), vec![]);
+        assert_eq!(
+            split_references("plain text", XmlVersion::V10).unwrap(),
+            vec![TextPart::Text("plain text".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_replaced_references() {
+        assert_eq!(
+            split_references("&lt;a&gt; &amp; &quot;&apos; &#169;&#xA9;", XmlVersion::V10).unwrap(),
+            vec![TextPart::Text("<a> & \"' ©©".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_entity_references() {
+        assert_eq!(
+            split_references("&copy; 2020 &amp; &owner;", XmlVersion::V10).unwrap(),
+            vec![
+                TextPart::EntityRef("copy"),
+                TextPart::Text(" 2020 & ".to_string()),
+                TextPart::EntityRef("owner"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_ends() {
+        assert_eq!(
+            split_references("a\r\nb\rc\n&#xD;&#13;\r\r\n", XmlVersion::V10).unwrap(),
+            vec![TextPart::Text("a\nb\nc\n\r\r\n\n".to_string())]
+        );
+        assert_eq!(
+            split_references("\r&e;\r\n", XmlVersion::V10).unwrap(),
+            vec![
+                TextPart::Text("\n".to_string()),
+                TextPart::EntityRef("e"),
+                TextPart::Text("\n".to_string()),
+            ]
+        );
+
+        let xml_11 = "a\r\u{85}b\u{85}c\u{2028}d&#x85;";
+        assert_eq!(
+            split_references(xml_11, XmlVersion::V11).unwrap(),
+            vec![TextPart::Text("a\nb\nc\nd\u{85}".to_string())]
+        );
+        assert_eq!(
+            split_references(xml_11, XmlVersion::V10).unwrap(),
+            vec![TextPart::Text(
+                "a\n\u{85}b\u{85}c\u{2028}d\u{85}".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_bad_references() {
+        assert!(split_references("a & b", XmlVersion::V10).is_err());
+        assert!(split_references("&copy", XmlVersion::V10).is_err());
+        assert!(split_references("&#xD800;", XmlVersion::V10).is_err());
+        assert!(split_references("&#12a;", XmlVersion::V10).is_err());
+        assert!(split_references("&#x+41;", XmlVersion::V10).is_err());
+        assert!(split_references("&#+65;", XmlVersion::V10).is_err());
+        assert!(split_references("&#x;", XmlVersion::V10).is_err());
+        assert!(split_references("&#;", XmlVersion::V10).is_err());
+
+        assert_eq!(malformed_reference_offset("&amp; &e; & &bad"), Some(10));
+        assert_eq!(malformed_reference_offset("&amp; &e;"), None);
+    }
+
+    #[test]
+    fn test_recovering() {
+        assert_eq!(
+            split_references_recovering("AT&T &amp; a & b; &#12a; &owner;", XmlVersion::V10),
+            (
+                vec![
+                    TextPart::Text("AT&T & a & b; &#12a; ".to_string()),
+                    TextPart::EntityRef("owner"),
+                ],
+                3
+            )
+        );
+        assert_eq!(
+            split_references_recovering("&copy", XmlVersion::V10),
+            (vec![TextPart::Text("&copy".to_string())], 1)
+        );
+    }
+}
@@ -0,0 +1,252 @@
+/*!
+Loading of external entities, and of the external subset of a document type declaration, see
+[`ParserOptions::external_resolver`](struct.ParserOptions.html#method.external_resolver).
+
+No external resources are loaded unless a resolver is provided. A resolver is asked for each
+external parsed entity that is referenced in content, for the external subset, and for each
+external parameter entity referenced in either subset; if it refuses the parser continues as if no
+resolver was provided. The loaded text may start with a text declaration, which is used to detect
+its encoding as for the document itself, and is otherwise discarded.
+
+[`FileResolver`](struct.FileResolver.html) loads entities from files within a single directory.
+*/
+
+use crate::parser::Result;
+use crate::shared::uri::{has_scheme, resolve_reference};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Implemented to provide the content of external entities to the parser.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::as_document;
+/// use xml_dom::level2::{Document, Node};
+/// use xml_dom::parser::{read_xml_with_options, ExternalResolver, ParserOptions, Result};
+///
+/// struct Chapters;
+///
+/// impl ExternalResolver for Chapters {
+///     fn resolve(
+///         &self,
+///         _public_id: Option<&str>,
+///         system_id: &str,
+///         _base_uri: Option<&str>,
+///     ) -> Result<Option<Vec<u8>>> {
+///         Ok(match system_id {
+///             "chapter1.xml" => Some(b"<chapter>One</chapter>".to_vec()),
+///             _ => None,
+///         })
+///     }
+/// }
+///
+/// let options = ParserOptions::new().external_resolver(Chapters);
+/// let document = read_xml_with_options(
+///     r#"<!DOCTYPE book [<!ENTITY chapter1 SYSTEM "chapter1.xml">]><book>&chapter1;</book>"#,
+///     options,
+/// )
+/// .unwrap();
+/// let book = as_document(&document).unwrap().document_element().unwrap();
+/// let reference = book.first_child().unwrap();
+/// assert_eq!(reference.first_child().unwrap().node_name().to_string(), "chapter");
+/// ```
+///
+pub trait ExternalResolver {
+    ///
+    /// Returns the content of the external entity with the given identifiers, or `None` to refuse
+    /// to load it. A relative `system_id` is relative to `base_uri`, which is the URI of the
+    /// entity containing the declaration, if known. Returning an error stops the parser.
+    ///
+    fn resolve(
+        &self,
+        public_id: Option<&str>,
+        system_id: &str,
+        base_uri: Option<&str>,
+    ) -> Result<Option<Vec<u8>>>;
+}
+
+///
+/// An [`ExternalResolver`](trait.ExternalResolver.html) that loads files from the local file
+/// system. Only files within the directory the resolver is created with, or its subdirectories,
+/// are loaded; any other system identifier, including one that is not a file path or `file:` URI,
+/// is refused. A relative system identifier with no base URI is relative to that directory.
+///
+#[derive(Clone, Debug)]
+pub struct FileResolver {
+    directory: PathBuf,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl FileResolver {
+    ///
+    /// Create a resolver that loads files within `directory`, which must exist.
+    ///
+    pub fn new(directory: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            directory: directory.as_ref().canonicalize()?,
+        })
+    }
+}
+
+impl ExternalResolver for FileResolver {
+    fn resolve(
+        &self,
+        _public_id: Option<&str>,
+        system_id: &str,
+        base_uri: Option<&str>,
+    ) -> Result<Option<Vec<u8>>> {
+        let uri = resolve_relative(base_uri, system_id);
+        let path = match file_path(&uri) {
+            None => {
+                warn!("system identifier '{}' is not a file", uri);
+                return Ok(None);
+            }
+            Some(path) => self.directory.join(path),
+        };
+        //
+        // The path is checked before it is accessed, so that the existence of files outside the
+        // directory is not revealed, and again once any symbolic links are resolved.
+        //
+        let path = normalize_path(&path);
+        if !path.starts_with(&self.directory) {
+            warn!("'{}' is outside the allowed directory", uri);
+            return Ok(None);
+        }
+        let path = path.canonicalize()?;
+        if !path.starts_with(&self.directory) {
+            warn!("'{}' is outside the allowed directory", uri);
+            return Ok(None);
+        }
+        Ok(Some(fs::read(path)?))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Returns `reference` resolved against `base_uri`, if there is one, following RFC 3986.
+///
+pub(crate) fn resolve_relative(base_uri: Option<&str>, reference: &str) -> String {
+    match base_uri {
+        None => reference.to_string(),
+        Some(base_uri) => resolve_reference(base_uri, reference),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const FILE_SCHEME: &str = "file://";
+
+pub(crate) fn file_path(uri: &str) -> Option<PathBuf> {
+    match uri.strip_prefix(FILE_SCHEME) {
+        Some(path) => Some(PathBuf::from(
+            path.strip_prefix("localhost").unwrap_or(path),
+        )),
+        None if has_scheme(uri) => None,
+        None => Some(PathBuf::from(uri)),
+    }
+}
+
+///
+/// Remove `.` and `..` components without accessing the file system.
+///
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                let _safe_to_ignore = normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Error;
+
+    #[test]
+    fn test_resolve_relative() {
+        assert_eq!(resolve_relative(None, "a.dtd"), "a.dtd");
+        assert_eq!(
+            resolve_relative(Some("file:///docs/book.xml"), "dtd/a.dtd"),
+            "file:///docs/dtd/a.dtd"
+        );
+        assert_eq!(resolve_relative(Some("book.xml"), "a.dtd"), "a.dtd");
+        assert_eq!(
+            resolve_relative(Some("docs/book.xml"), "/dtd/a.dtd"),
+            "/dtd/a.dtd"
+        );
+        assert_eq!(
+            resolve_relative(Some("docs/book.xml"), "http://example.com/a.dtd"),
+            "http://example.com/a.dtd"
+        );
+        assert_eq!(
+            resolve_relative(Some("file:///docs/xml/book.xml"), "../dtd/./a.dtd"),
+            "file:///docs/dtd/a.dtd"
+        );
+    }
+
+    #[test]
+    fn test_file_resolver() {
+        let root = std::env::temp_dir().join(format!("xml_dom_resolver_{}", std::process::id()));
+        let allowed = root.join("allowed");
+        fs::create_dir_all(allowed.join("dtd")).unwrap();
+        fs::write(allowed.join("dtd/a.dtd"), "<!ENTITY a 'a'>").unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+
+        let resolver = FileResolver::new(&allowed).unwrap();
+        assert_eq!(
+            resolver.resolve(None, "dtd/a.dtd", None).unwrap(),
+            Some(b"<!ENTITY a 'a'>".to_vec())
+        );
+        assert!(resolver
+            .resolve(None, "a.dtd", Some("dtd/book.xml"))
+            .unwrap()
+            .is_some());
+        let absolute = format!("file://{}", allowed.join("dtd/a.dtd").display());
+        assert!(resolver.resolve(None, &absolute, None).unwrap().is_some());
+
+        assert!(resolver
+            .resolve(None, "../secret.txt", None)
+            .unwrap()
+            .is_none());
+        assert!(resolver
+            .resolve(None, "../../missing.txt", Some("dtd/a.dtd"))
+            .unwrap()
+            .is_none());
+        let outside = format!("{}", root.join("secret.txt").display());
+        assert!(resolver.resolve(None, &outside, None).unwrap().is_none());
+        assert!(resolver
+            .resolve(None, "http://example.com/a.dtd", None)
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            resolver.resolve(None, "missing.dtd", None),
+            Err(Error::IOError(_))
+        ));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
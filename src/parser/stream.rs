@@ -0,0 +1,435 @@
+/*!
+Streaming of the subtrees of a document, see [`read_subtrees`](../fn.read_subtrees.html).
+
+Outside of a matched subtree the parser only tracks the elements enclosing the current position,
+with their namespace declarations and `xml:space` handling; other content is checked by quick-xml
+but is otherwise discarded. Each matched subtree is parsed into a new document exactly as
+`read_reader` would parse it, so memory use is bounded by the size of the largest subtree rather
+than by the size of the input.
+*/
+
+use crate::level2::ext::{SpaceHandling, XmlDecl};
+use crate::level2::{Name, RefNode};
+use crate::parser::builder::DocumentBuilder;
+use crate::parser::dtd::{DocTypeDecl, EntityDecl, NotationDecl};
+use crate::parser::handler::ContentHandler;
+use crate::parser::position::PositionReader;
+use crate::parser::{
+    element, handle_doctype, handle_start, make_decl, positioned, source_location, source_reader,
+    Context, Error, ParserOptions, Result, Scope,
+};
+use crate::shared::text::is_xml_space;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::io::BufRead;
+use std::iter::FusedIterator;
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// An iterator over the subtrees of a document that match a path, returned by
+/// [`read_subtrees`](fn.read_subtrees.html). Each item is a new `Document` whose document element
+/// is a matched element. Once an error has been returned the iterator returns `None`.
+///
+pub struct Subtrees<B: BufRead> {
+    reader: Reader<PositionReader<B>>,
+    event_buffer: Vec<u8>,
+    context: Context,
+    path: Vec<String>,
+    anchored: bool,
+    open_elements: Vec<OpenElement>,
+    prolog: Prolog,
+    started: bool,
+    finished: bool,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// An element enclosing the current position, outside of any matched subtree.
+///
+#[derive(Debug)]
+struct OpenElement {
+    qualified_name: String,
+    space: SpaceHandling,
+    namespaces: Vec<(Option<String>, String)>,
+}
+
+///
+/// Records the XML and document type declarations so that they may be repeated in the document
+/// created for each subtree, all other events are ignored.
+///
+#[derive(Debug, Default)]
+struct Prolog {
+    xml_declaration: Option<XmlDecl>,
+    doc_type: Option<(DocTypeDecl, Vec<EntityDecl>, Vec<NotationDecl>)>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<B: BufRead> Debug for Subtrees<B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Subtrees")
+            .field("path", &self.path)
+            .field("anchored", &self.anchored)
+            .field("open_elements", &self.open_elements)
+            .field("prolog", &self.prolog)
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B: BufRead> Iterator for Subtrees<B> {
+    type Item = Result<RefNode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.next_subtree() {
+            Ok(Some(document)) => Some(Ok(document)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(positioned(
+                    &mut self.reader,
+                    &self.context,
+                    &self.event_buffer,
+                    err,
+                )))
+            }
+        }
+    }
+}
+
+impl<B: BufRead> FusedIterator for Subtrees<B> {}
+
+impl<B: BufRead> Subtrees<B> {
+    ///
+    /// A path is a sequence of element names separated by `/`; with a leading `/` the first name
+    /// is that of the document element, otherwise the path may match at any depth.
+    ///
+    pub(crate) fn new(reader: B, path: &str, options: ParserOptions) -> Result<Self> {
+        let (anchored, path) = match path.strip_prefix(XML_PATH_SEPARATOR) {
+            Some(path) => (true, path),
+            None => (false, path),
+        };
+        let path = path
+            .split(XML_PATH_SEPARATOR)
+            .map(|step| Name::from_str(step).map(|_| step.to_string()))
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        Ok(Self {
+            reader: source_reader(reader),
+            event_buffer: Vec::new(),
+            context: Context::new(options),
+            path,
+            anchored,
+            open_elements: Vec::new(),
+            prolog: Default::default(),
+            started: false,
+            finished: false,
+        })
+    }
+
+    fn next_subtree(&mut self) -> Result<Option<RefNode>> {
+        loop {
+            self.event_buffer.clear();
+            let position = self.context.event_start(&self.reader);
+            let start = self.context.location_start(position);
+            let is_first = !std::mem::replace(&mut self.started, true);
+            let (ev, is_empty) = match self.reader.read_event_into(&mut self.event_buffer) {
+                Ok(Event::Start(ev)) => (ev, false),
+                Ok(Event::Empty(ev)) => (ev, true),
+                Ok(Event::End(_)) => {
+                    let _safe_to_ignore = self.open_elements.pop();
+                    continue;
+                }
+                Ok(Event::Decl(ev)) => {
+                    if !is_first {
+                        error!("XML declaration must be first");
+                        return Error::Malformed.into();
+                    }
+                    let xml_decl = make_decl(&mut self.reader, ev)?;
+                    self.context.version = xml_decl.version();
+                    self.prolog.xml_declaration(&xml_decl)?;
+                    continue;
+                }
+                Ok(Event::DocType(ev)) => {
+                    if self.prolog.doc_type.is_some() {
+                        error!("only one document type allowed");
+                        return Error::Malformed.into();
+                    }
+                    handle_doctype(&mut self.reader, &mut self.context, &mut self.prolog, ev)?;
+                    continue;
+                }
+                Ok(Event::Eof) => return Ok(None),
+                Ok(Event::Text(ev)) if is_first && ev.iter().all(|b| is_xml_space(*b as char)) => {
+                    //
+                    // Leading whitespace is tolerated before the XML declaration.
+                    //
+                    self.started = false;
+                    continue;
+                }
+                Ok(_) => continue,
+                Err(err) => {
+                    error!("Unexpected parser error: {:?}", err);
+                    return Error::from(err).into();
+                }
+            };
+
+            let qualified_name = self
+                .reader
+                .decoder()
+                .decode(ev.name().into_inner())?
+                .to_string();
+            let scope = enclosing_scope(&self.open_elements);
+            if is_match(
+                &self.path,
+                self.anchored,
+                &self.open_elements,
+                &qualified_name,
+            ) {
+                let mut builder = self.prolog.builder(&self.context.options)?;
+                self.context.element_depth = self.open_elements.len();
+                self.context.node_count = 0;
+                let (name, scope) = handle_start(
+                    &mut self.reader,
+                    &mut self.context,
+                    &mut builder,
+                    ev,
+                    position,
+                    &scope,
+                )?;
+                if is_empty {
+                    builder.end_element(&name, source_location(&self.reader, start))?;
+                } else {
+                    self.context.element_depth += 1;
+                    element(
+                        &mut self.reader,
+                        &mut self.event_buffer,
+                        &mut self.context,
+                        &mut builder,
+                        Some(&name),
+                        &scope,
+                    )?;
+                }
+                return Ok(Some(builder.into_document()));
+            }
+
+            self.context.element_depth = self.open_elements.len();
+            let (_, scope) = handle_start(
+                &mut self.reader,
+                &mut self.context,
+                &mut self.prolog,
+                ev,
+                position,
+                &scope,
+            )?;
+            if !is_empty {
+                self.open_elements.push(OpenElement {
+                    qualified_name,
+                    space: scope.space,
+                    namespaces: scope.namespaces,
+                });
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl ContentHandler for Prolog {
+    fn xml_declaration(&mut self, declaration: &XmlDecl) -> Result<()> {
+        self.xml_declaration = Some(declaration.clone());
+        Ok(())
+    }
+
+    fn document_type(
+        &mut self,
+        declaration: &DocTypeDecl,
+        entities: &[EntityDecl],
+        notations: &[NotationDecl],
+    ) -> Result<()> {
+        self.doc_type = Some((declaration.clone(), entities.to_vec(), notations.to_vec()));
+        Ok(())
+    }
+}
+
+impl Prolog {
+    ///
+    /// Returns a builder for a new document with the declarations recorded so far.
+    ///
+    fn builder(&self, options: &ParserOptions) -> Result<DocumentBuilder> {
+        let mut builder = DocumentBuilder::new(options)?;
+        if let Some(declaration) = &self.xml_declaration {
+            builder.xml_declaration(declaration)?;
+        }
+        if let Some((declaration, entities, notations)) = &self.doc_type {
+            builder.document_type(declaration, entities, notations)?;
+        }
+        Ok(builder)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const XML_PATH_SEPARATOR: char = '/';
+
+///
+/// The scope of the content of the innermost open element, as a single scope.
+///
+fn enclosing_scope(open_elements: &[OpenElement]) -> Scope<'static> {
+    Scope {
+        parent: None,
+        space: open_elements
+            .last()
+            .map(|open_element| open_element.space)
+            .unwrap_or_default(),
+        //
+        // A prefix resolves to the first declaration found, so the innermost come first.
+        //
+        namespaces: open_elements
+            .iter()
+            .rev()
+            .flat_map(|open_element| open_element.namespaces.iter().cloned())
+            .collect(),
+        context_element: None,
+    }
+}
+
+fn is_match(
+    path: &[String],
+    anchored: bool,
+    open_elements: &[OpenElement],
+    qualified_name: &str,
+) -> bool {
+    let (last, ancestors) = path.split_last().unwrap();
+    if last != qualified_name
+        || open_elements.len() < ancestors.len()
+        || (anchored && open_elements.len() != ancestors.len())
+    {
+        return false;
+    }
+    open_elements[open_elements.len() - ancestors.len()..]
+        .iter()
+        .zip(ancestors)
+        .all(|(open_element, step)| &open_element.qualified_name == step)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::level2::convert::{as_document, as_element};
+    use crate::level2::ext::DocumentDecl;
+    use crate::level2::Node;
+    use crate::parser::{read_subtrees, Error, ParserOptions};
+
+    const RECORDS_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE records [<!ENTITY org "Example Org">]>
+<records xmlns="urn:records" xmlns:x="urn:extra">
+  <header><record id="not-a-record"/></header>
+  <record id="1">&org;</record>
+  <record id="2" x:flag="yes"><name>two</name></record>
+  <record id="3"/>
+</records>"#;
+
+    fn record_ids(path: &str) -> Vec<String> {
+        read_subtrees(RECORDS_XML.as_bytes(), path, ParserOptions::new())
+            .unwrap()
+            .map(|document| {
+                let document = document.unwrap();
+                let record = as_document(&document).unwrap().document_element().unwrap();
+                as_element(&record).unwrap().get_attribute("id").unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_subtree_paths() {
+        assert_eq!(record_ids("record"), vec!["not-a-record", "1", "2", "3"]);
+        assert_eq!(record_ids("records/record"), vec!["1", "2", "3"]);
+        assert_eq!(record_ids("/records/record"), vec!["1", "2", "3"]);
+        assert_eq!(record_ids("/record"), Vec::<String>::new());
+        assert_eq!(record_ids("header/record"), vec!["not-a-record"]);
+
+        assert!(read_subtrees(RECORDS_XML.as_bytes(), "", ParserOptions::new()).is_err());
+        assert!(read_subtrees(RECORDS_XML.as_bytes(), "a//b", ParserOptions::new()).is_err());
+    }
+
+    #[test]
+    fn test_subtree_documents() {
+        let documents = read_subtrees(
+            RECORDS_XML.as_bytes(),
+            "/records/record",
+            ParserOptions::new(),
+        )
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(documents.len(), 3);
+
+        let first = &documents[0];
+        assert!(first.xml_declaration().is_some());
+        let record = as_document(first).unwrap().document_element().unwrap();
+        assert_eq!(record.namespace_uri(), Some("urn:records".to_string()));
+        let reference = record.first_child().unwrap();
+        assert_eq!(reference.node_name().to_string(), "org");
+        assert_eq!(
+            reference.first_child().unwrap().node_value(),
+            Some("Example Org".to_string())
+        );
+
+        let record = as_document(&documents[1])
+            .unwrap()
+            .document_element()
+            .unwrap();
+        assert_eq!(
+            as_element(&record)
+                .unwrap()
+                .get_attribute_ns("urn:extra", "flag"),
+            Some("yes".to_string())
+        );
+        let name = record.first_child().unwrap();
+        assert_eq!(name.namespace_uri(), Some("urn:records".to_string()));
+        assert_eq!(record.parent_node(), Some(documents[1].clone()));
+    }
+
+    #[test]
+    fn test_subtree_errors() {
+        let mut subtrees = read_subtrees(
+            "<records><record/><record><bad:name/></record><record/></records>".as_bytes(),
+            "record",
+            ParserOptions::new(),
+        )
+        .unwrap();
+        assert!(subtrees.next().unwrap().is_ok());
+        let error = subtrees.next().unwrap().unwrap_err();
+        assert!(error.position().is_some());
+        assert!(subtrees.next().is_none());
+
+        let mut subtrees = read_subtrees(
+            "<!-- c --><?xml version=\"1.1\"?><records><record/></records>".as_bytes(),
+            "record",
+            ParserOptions::new(),
+        )
+        .unwrap();
+        let error = subtrees.next().unwrap().unwrap_err();
+        assert!(matches!(error.inner(), Error::Malformed));
+    }
+}
@@ -0,0 +1,826 @@
+/*!
+[XInclude 1.0](https://www.w3.org/TR/xinclude/) processing of a parsed document, replacing each
+`xi:include` element with the content it references.
+
+Resources are loaded using an [`ExternalResolver`](../trait.ExternalResolver.html), so that
+documents may only include what the resolver allows; relative references are resolved against the
+base URI of the `xi:include` element, as reported by
+[`XmlBase::base_uri`](../../level2/ext/trait.XmlBase.html). Included XML documents are parsed
+with the same `ParserOptions`, and their own `xi:include` elements are processed in turn.
+
+* `parse="xml"`, the default, includes the children of the referenced document, other than its
+  document type declaration, or the element identified by the `xpointer` attribute. Only the
+  shorthand (an ID) and `element()` schemes of XPointer are supported.
+* `parse="text"` includes the referenced resource as a single text node, decoded using the
+  `encoding` attribute if there is no byte order mark, and otherwise as UTF-8.
+* An `href` that is empty, or absent, refers to the document containing the `xi:include`.
+
+If a resource cannot be loaded, or the `xpointer` does not identify an element, the content of the
+`xi:fallback` child of the `xi:include` is used instead; without one this is an error, as is an
+inclusion loop. Elements included from another document are given an `xml:base` attribute so
+that their base URI is unchanged.
+
+# Example
+
+```rust
+use xml_dom::level2::convert::as_document;
+use xml_dom::level2::{Document, Node};
+use xml_dom::parser::{read_xml, xinclude, ExternalResolver, Result};
+
+struct Chapters;
+
+impl ExternalResolver for Chapters {
+    fn resolve(
+        &self,
+        _public_id: Option<&str>,
+        system_id: &str,
+        _base_uri: Option<&str>,
+    ) -> Result<Option<Vec<u8>>> {
+        Ok(match system_id {
+            "chapter1.xml" => Some(b"<chapter>One</chapter>".to_vec()),
+            _ => None,
+        })
+    }
+}
+
+let mut document = read_xml(
+    r#"<book xmlns:xi="http://www.w3.org/2001/XInclude">
+  <xi:include href="chapter1.xml"/>
+  <xi:include href="chapter2.xml"><xi:fallback><missing/></xi:fallback></xi:include>
+</book>"#,
+)
+.unwrap();
+xinclude::process(&mut document, Chapters).unwrap();
+
+let book = as_document(&document).unwrap().document_element().unwrap();
+assert_eq!(book.to_string(), r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><chapter xml:base="chapter1.xml">One</chapter><missing></missing></book>"#);
+```
+*/
+
+use crate::level2::convert::{as_attribute_mut, as_document, as_element, as_element_mut};
+use crate::level2::ext::{DocumentDecl, XmlBase};
+use crate::level2::node_impl::Extension;
+use crate::level2::*;
+use crate::parser::resolver::resolve_relative;
+use crate::parser::{read_bytes_with_options, Error, ExternalResolver, ParserOptions, Result};
+use crate::shared::syntax::{XML_NS_ATTRIBUTE, XML_NS_ATTR_BASE, XML_NS_SEPARATOR, XML_NS_URI};
+use crate::shared::text;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Replace the `xi:include` elements within `node`, a document or an element, loading the
+/// resources they reference using `resolver`.
+///
+pub fn process(node: &mut RefNode, resolver: impl ExternalResolver + 'static) -> Result<()> {
+    process_with_options(node, ParserOptions::new().external_resolver(resolver))
+}
+
+///
+/// Replace the `xi:include` elements within `node`, a document or an element, loading the
+/// resources they reference using the `external_resolver` in `options`, which are also used to
+/// parse included documents. If the document containing `node` has no `document_uri` the
+/// `base_uri` in `options`, if any, is used in its place.
+///
+pub fn process_with_options(node: &mut RefNode, options: ParserOptions) -> Result<()> {
+    let document = match node.node_type() {
+        NodeType::Document => node.clone(),
+        _ => match node.owner_document() {
+            None => {
+                error!("node to process has no owner document");
+                return Error::Malformed.into();
+            }
+            Some(document) => document,
+        },
+    };
+    let source = Source {
+        uri: document.document_uri().or_else(|| options.base_uri.clone()),
+        document,
+    };
+    let mut processor = Processor {
+        open_resources: vec![(source.uri.clone(), None)],
+        options,
+    };
+    processor.process_children(node, &source)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A document within which inclusions are being processed.
+///
+#[derive(Debug)]
+struct Source {
+    document: RefNode,
+    uri: Option<String>,
+}
+
+#[derive(Debug)]
+struct Processor {
+    options: ParserOptions,
+    /// The URI, and `xpointer`, of each resource being included, used to detect loops.
+    open_resources: Vec<(Option<String>, Option<String>)>,
+}
+
+const XINCLUDE_NS_URI: &str = "http://www.w3.org/2001/XInclude";
+
+const INCLUDE_ELEMENT: &str = "include";
+const FALLBACK_ELEMENT: &str = "fallback";
+
+const HREF_ATTRIBUTE: &str = "href";
+const PARSE_ATTRIBUTE: &str = "parse";
+const XPOINTER_ATTRIBUTE: &str = "xpointer";
+const ENCODING_ATTRIBUTE: &str = "encoding";
+
+const PARSE_XML: &str = "xml";
+const PARSE_TEXT: &str = "text";
+
+const XPOINTER_ELEMENT_SCHEME: &str = "element";
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Processor {
+    fn process_children(&mut self, parent: &RefNode, source: &Source) -> Result<()> {
+        for child in parent.child_nodes() {
+            if is_xinclude_element(&child, INCLUDE_ELEMENT) {
+                let mut parent = parent.clone();
+                for node in self.include(&child, source)? {
+                    let _safe_to_ignore = parent.insert_before(node, Some(child.clone()))?;
+                }
+                let _safe_to_ignore = parent.remove_child(child)?;
+            } else if is_xinclude_element(&child, FALLBACK_ELEMENT) {
+                error!("xi:fallback must be a child of xi:include");
+                return Error::XInclude("xi:fallback outside xi:include".to_string()).into();
+            } else if child.node_type() == NodeType::Element {
+                self.process_children(&child, source)?;
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Process the nodes selected from a resource, any that are themselves `xi:include` elements
+    /// are replaced.
+    ///
+    fn process_nodes(&mut self, nodes: Vec<RefNode>, source: &Source) -> Result<Vec<RefNode>> {
+        let mut processed: Vec<RefNode> = Default::default();
+        for node in nodes {
+            if is_xinclude_element(&node, INCLUDE_ELEMENT) {
+                processed.extend(self.include(&node, source)?);
+            } else {
+                if node.node_type() == NodeType::Element {
+                    self.process_children(&node, source)?;
+                }
+                processed.push(node);
+            }
+        }
+        Ok(processed)
+    }
+
+    ///
+    /// Returns the nodes, owned by the source document, that replace the `xi:include` element.
+    ///
+    fn include(&mut self, element: &RefNode, source: &Source) -> Result<Vec<RefNode>> {
+        let include = as_element(element).unwrap();
+        let href = include.get_attribute(HREF_ATTRIBUTE).unwrap_or_default();
+        let xpointer = include.get_attribute(XPOINTER_ATTRIBUTE);
+        let is_text = match include.get_attribute(PARSE_ATTRIBUTE).as_deref() {
+            None | Some(PARSE_XML) => false,
+            Some(PARSE_TEXT) => true,
+            Some(other) => {
+                error!("xi:include has an invalid parse attribute '{}'", other);
+                return Error::XInclude(format!("invalid parse attribute '{}'", other)).into();
+            }
+        };
+        if href.contains('#') {
+            error!("xi:include href '{}' has a fragment identifier", href);
+            return Error::XInclude(format!("fragment identifier in href '{}'", href)).into();
+        }
+        if is_text && (href.is_empty() || xpointer.is_some()) {
+            error!("xi:include with parse=\"text\" requires an href, and no xpointer");
+            return Error::XInclude("invalid text inclusion".to_string()).into();
+        }
+        if href.is_empty() && xpointer.is_none() {
+            error!("xi:include requires an href or an xpointer");
+            return Error::XInclude("missing href and xpointer".to_string()).into();
+        }
+        let fallback = fallback(element)?;
+
+        let base_uri = base_uri_of(element, source.uri.as_deref());
+        let included = if href.is_empty() {
+            self.include_local(element, xpointer.unwrap(), source)?
+        } else if is_text {
+            self.include_text(
+                &href,
+                base_uri.as_deref(),
+                include.get_attribute(ENCODING_ATTRIBUTE).as_deref(),
+                source,
+            )?
+        } else {
+            let parent_base_uri = element
+                .parent_node()
+                .and_then(|parent| base_uri_of(&parent, source.uri.as_deref()));
+            self.include_xml(
+                &href,
+                base_uri.as_deref(),
+                parent_base_uri.as_deref(),
+                xpointer,
+                source,
+            )?
+        };
+
+        match (included, fallback) {
+            (Some(nodes), _) => Ok(nodes),
+            (None, Some(mut fallback)) => {
+                self.process_children(&fallback, source)?;
+                let mut nodes: Vec<RefNode> = Default::default();
+                for child in fallback.child_nodes() {
+                    nodes.push(fallback.remove_child(child)?);
+                }
+                Ok(nodes)
+            }
+            (None, None) => {
+                error!("could not include '{}' and there is no xi:fallback", href);
+                Error::XInclude(format!("could not include '{}'", href)).into()
+            }
+        }
+    }
+
+    ///
+    /// Include a copy of the element identified by `xpointer` in the source document itself.
+    ///
+    fn include_local(
+        &mut self,
+        element: &RefNode,
+        xpointer: String,
+        source: &Source,
+    ) -> Result<Option<Vec<RefNode>>> {
+        let selected = match select(&source.document, &xpointer) {
+            None => {
+                warn!("xpointer '{}' does not identify an element", xpointer);
+                return Ok(None);
+            }
+            Some(selected) => selected,
+        };
+        let mut ancestor = Some(element.clone());
+        while let Some(node) = ancestor {
+            if node == selected {
+                error!("xi:include includes its own ancestor '{}'", xpointer);
+                return Error::XInclude(format!("inclusion loop at '{}'", xpointer)).into();
+            }
+            ancestor = node.parent_node();
+        }
+        let resource = (source.uri.clone(), Some(xpointer));
+        if self.open_resources.contains(&resource) {
+            error!(
+                "xi:include includes its own ancestor '{}'",
+                resource.1.unwrap()
+            );
+            return Error::XInclude("inclusion loop".to_string()).into();
+        }
+
+        let copies = import(&source.document, &selected)?;
+        self.open_resources.push(resource);
+        let included = self.process_nodes(copies, source);
+        let _safe_to_ignore = self.open_resources.pop();
+        Ok(Some(included?))
+    }
+
+    fn include_text(
+        &self,
+        href: &str,
+        base_uri: Option<&str>,
+        encoding: Option<&str>,
+        source: &Source,
+    ) -> Result<Option<Vec<RefNode>>> {
+        Ok(match self.load(href, base_uri)? {
+            None => None,
+            Some(bytes) => {
+                let text = super::encoding::decode_text(&bytes, encoding)?;
+                let document = as_document(&source.document).unwrap();
+                Some(vec![document.create_text_node(&text)?])
+            }
+        })
+    }
+
+    fn include_xml(
+        &mut self,
+        href: &str,
+        base_uri: Option<&str>,
+        parent_base_uri: Option<&str>,
+        xpointer: Option<String>,
+        source: &Source,
+    ) -> Result<Option<Vec<RefNode>>> {
+        let uri = resolve_relative(base_uri, href);
+        if self.open_resources.iter().any(|(open_uri, open_xpointer)| {
+            open_uri.as_deref() == Some(&uri)
+                && (open_xpointer.is_none() || *open_xpointer == xpointer)
+        }) {
+            error!("xi:include includes its own ancestor '{}'", uri);
+            return Error::XInclude(format!("inclusion loop at '{}'", uri)).into();
+        }
+        let bytes = match self.load(href, base_uri)? {
+            None => return Ok(None),
+            Some(bytes) => bytes,
+        };
+        let included = Source {
+            document: read_bytes_with_options(bytes, self.options.clone().base_uri(uri.clone()))?,
+            uri: Some(uri),
+        };
+        let selected = match &xpointer {
+            None => included
+                .document
+                .child_nodes()
+                .into_iter()
+                .filter(|node| node.node_type() != NodeType::DocumentType)
+                .collect(),
+            Some(xpointer) => match select(&included.document, xpointer) {
+                None => {
+                    warn!("xpointer '{}' does not identify an element", xpointer);
+                    return Ok(None);
+                }
+                Some(selected) => vec![selected],
+            },
+        };
+
+        self.open_resources.push((included.uri.clone(), xpointer));
+        let processed = self.process_nodes(selected, &included);
+        let _safe_to_ignore = self.open_resources.pop();
+
+        let mut nodes: Vec<RefNode> = Default::default();
+        for node in processed? {
+            let node_base_uri = base_uri_of(&node, included.uri.as_deref());
+            for mut imported in import(&source.document, &node)? {
+                //
+                // The base URI fixup of §4.5.5, so that relative references within the included
+                // element are still resolved against the resource it came from.
+                //
+                if imported.node_type() == NodeType::Element
+                    && node_base_uri.as_deref() != parent_base_uri
+                {
+                    if let Some(node_base_uri) = &node_base_uri {
+                        as_element_mut(&mut imported).unwrap().set_attribute_ns(
+                            XML_NS_URI,
+                            &format!(
+                                "{}{}{}",
+                                XML_NS_ATTRIBUTE, XML_NS_SEPARATOR, XML_NS_ATTR_BASE
+                            ),
+                            node_base_uri,
+                        )?;
+                    }
+                }
+                nodes.push(imported);
+            }
+        }
+        Ok(Some(nodes))
+    }
+
+    ///
+    /// Load a resource, returns `None` if it cannot be loaded so that any fallback is used.
+    ///
+    fn load(&self, href: &str, base_uri: Option<&str>) -> Result<Option<Vec<u8>>> {
+        let resolver = match &self.options.external_resolver {
+            None => {
+                warn!("no resolver to load '{}'", href);
+                return Ok(None);
+            }
+            Some(resolver) => resolver,
+        };
+        match resolver.resolve(None, href, base_uri) {
+            Ok(None) => {
+                warn!("resolver refused to load '{}'", href);
+                Ok(None)
+            }
+            Err(Error::IOError(err)) => {
+                warn!("could not load '{}': {}", href, err);
+                Ok(None)
+            }
+            result => result,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn is_xinclude_element(node: &RefNode, local_name: &str) -> bool {
+    node.node_type() == NodeType::Element
+        && node.namespace_uri().as_deref() == Some(XINCLUDE_NS_URI)
+        && node.local_name() == local_name
+}
+
+///
+/// Returns the `xi:fallback` child of an `xi:include`, there may be at most one and the
+/// `xi:include` may not contain another.
+///
+fn fallback(include: &RefNode) -> Result<Option<RefNode>> {
+    let mut fallback = None;
+    for child in include.child_nodes() {
+        if is_xinclude_element(&child, INCLUDE_ELEMENT) {
+            error!("xi:include may not contain xi:include");
+            return Error::XInclude("xi:include within xi:include".to_string()).into();
+        } else if is_xinclude_element(&child, FALLBACK_ELEMENT) {
+            if fallback.is_some() {
+                error!("xi:include may contain only one xi:fallback");
+                return Error::XInclude("more than one xi:fallback".to_string()).into();
+            }
+            fallback = Some(child);
+        }
+    }
+    Ok(fallback)
+}
+
+///
+/// Returns the base URI of `node`, resolved against `document_uri` if the document containing it
+/// has no URI of its own.
+///
+fn base_uri_of(node: &RefNode, document_uri: Option<&str>) -> Option<String> {
+    let base_uri = node.base_uri();
+    if owner_document_uri(node).is_some() {
+        base_uri
+    } else {
+        match base_uri {
+            None => document_uri.map(str::to_string),
+            Some(base_uri) => Some(resolve_relative(document_uri, &base_uri)),
+        }
+    }
+}
+
+fn owner_document_uri(node: &RefNode) -> Option<String> {
+    node.owner_document()
+        .and_then(|document| document.document_uri())
+}
+
+///
+/// Returns the element in `document` identified by `xpointer`, the first part that identifies an
+/// element is used.
+///
+fn select(document: &RefNode, xpointer: &str) -> Option<RefNode> {
+    let xpointer = xpointer.trim();
+    if !xpointer.contains('(') {
+        return as_document(document).unwrap().get_element_by_id(xpointer);
+    }
+    let mut rest = xpointer;
+    while let Some(start) = rest.find('(') {
+        let scheme = rest[..start].trim();
+        let data_end = scheme_data_end(&rest[start + 1..])?;
+        let data = &rest[start + 1..start + 1 + data_end];
+        if scheme == XPOINTER_ELEMENT_SCHEME {
+            if let Some(selected) = select_element(document, data) {
+                return Some(selected);
+            }
+        } else {
+            warn!("XPointer scheme '{}' is not supported", scheme);
+        }
+        rest = &rest[start + data_end + 2..];
+    }
+    None
+}
+
+///
+/// Returns the offset of the `)` that ends scheme data, allowing for balanced and escaped
+/// parentheses.
+///
+fn scheme_data_end(data: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (offset, c) in data.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '^' => escaped = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(offset),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    warn!("XPointer scheme data is not closed");
+    None
+}
+
+///
+/// The `element()` scheme, an optional ID followed by a child sequence such as `/1/3`.
+///
+fn select_element(document: &RefNode, data: &str) -> Option<RefNode> {
+    let mut steps = data.split('/');
+    let mut current = match steps.next() {
+        Some("") => document.clone(),
+        Some(id) => as_document(document).unwrap().get_element_by_id(id)?,
+        None => return None,
+    };
+    for step in steps {
+        let index: usize = step.parse().ok().filter(|index| *index > 0)?;
+        current = current
+            .child_nodes()
+            .into_iter()
+            .filter(|node| node.node_type() == NodeType::Element)
+            .nth(index - 1)?;
+    }
+    if current.node_type() == NodeType::Element {
+        Some(current)
+    } else {
+        None
+    }
+}
+
+///
+/// Returns copies of `node`, and its descendants, owned by `document`. Entity references are
+/// replaced by their content, and document type declarations are dropped.
+///
+fn import(document: &RefNode, node: &RefNode) -> Result<Vec<RefNode>> {
+    let owner = as_document(document).unwrap();
+    Ok(match node.node_type() {
+        NodeType::Element => {
+            let assume_ids = match &document.borrow().i_extension {
+                Extension::Document { i_options, .. } => i_options.has_assume_ids(),
+                _ => false,
+            };
+            let name = node.node_name();
+            let mut element = match name.namespace_uri() {
+                None => owner.create_element(&name.to_string())?,
+                Some(namespace_uri) => owner.create_element_ns(namespace_uri, &name.to_string())?,
+            };
+            for (name, attribute) in node.attributes() {
+                let qualified_name = name.to_string();
+                let value = expand_attribute_value(&attribute)?;
+                if name.is_id_attribute(assume_ids) && owner.get_element_by_id(&value).is_some() {
+                    //
+                    // The copy of an element with an ID, included twice or from the same
+                    // document, cannot have the same ID.
+                    //
+                    warn!("dropping duplicate ID '{}' from included element", value);
+                    continue;
+                }
+                let attribute_node = match name.namespace_uri() {
+                    None => owner.create_attribute_with(&qualified_name, &escape_value(&value))?,
+                    Some(namespace_uri) => {
+                        let mut attribute_node =
+                            owner.create_attribute_ns(namespace_uri, &qualified_name)?;
+                        as_attribute_mut(&mut attribute_node)
+                            .unwrap()
+                            .set_value(&escape_value(&value))?;
+                        attribute_node
+                    }
+                };
+                let _safe_to_ignore = element.set_attribute_node(attribute_node)?;
+            }
+            for child in node.child_nodes() {
+                for imported in import(document, &child)? {
+                    let _safe_to_ignore = element.append_child(imported)?;
+                }
+            }
+            vec![element]
+        }
+        NodeType::Text => vec![owner.create_text_node(&node.node_value().unwrap_or_default())?],
+        NodeType::CData => {
+            vec![owner.create_cdata_section(&node.node_value().unwrap_or_default())?]
+        }
+        NodeType::Comment => vec![owner.create_comment(&node.node_value().unwrap_or_default())?],
+        NodeType::ProcessingInstruction => vec![owner.create_processing_instruction(
+            &node.node_name().to_string(),
+            node.node_value().as_deref(),
+        )?],
+        NodeType::EntityReference | NodeType::Document | NodeType::DocumentFragment => {
+            let mut imported: Vec<RefNode> = Default::default();
+            for child in node.child_nodes() {
+                imported.extend(import(document, &child)?);
+            }
+            imported
+        }
+        _ => Default::default(),
+    })
+}
+
+///
+/// The value of `attribute` with any references expanded against the document it was parsed
+/// from, as the entities it declares are not known to the including document.
+///
+fn expand_attribute_value(attribute: &RefNode) -> Result<String> {
+    //
+    // The unescaped value, as `Attribute::value` would return before normalization.
+    //
+    let value: String = attribute
+        .child_nodes()
+        .iter()
+        .filter_map(|child| child.node_value())
+        .collect();
+    Ok(text::normalize_attribute_value(value, attribute, true)?)
+}
+
+///
+/// Escape an expanded value so that setting it on an attribute does not normalize it again.
+///
+fn escape_value(value: &str) -> String {
+    text::escape(value)
+        .chars()
+        .map(|c| match c {
+            '\u{09}' | '\u{0A}' | '\u{0D}' => text::to_entity(c),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::read_xml;
+    use std::collections::HashMap;
+
+    struct MapResolver(HashMap<&'static str, &'static str>);
+
+    impl ExternalResolver for MapResolver {
+        fn resolve(
+            &self,
+            _public_id: Option<&str>,
+            system_id: &str,
+            base_uri: Option<&str>,
+        ) -> Result<Option<Vec<u8>>> {
+            let uri = resolve_relative(base_uri, system_id);
+            Ok(self
+                .0
+                .get(uri.as_str())
+                .map(|text| text.as_bytes().to_vec()))
+        }
+    }
+
+    fn resolver() -> MapResolver {
+        MapResolver(
+            vec![
+                (
+                    "docs/chapter1.xml",
+                    r#"<?xml version="1.0"?><!DOCTYPE chapter><chapter xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="part/intro.xml"/></chapter>"#,
+                ),
+                ("docs/part/intro.xml", "<intro>Hello</intro>"),
+                (
+                    "docs/sections.xml",
+                    r#"<sections><section xml:id="s1">One</section><section xml:id="s2">Two</section></sections>"#,
+                ),
+                ("docs/code.txt", "a < b & c"),
+                (
+                    "docs/entities.xml",
+                    r#"<!DOCTYPE note [<!ENTITY who "the &amp; author">]><note by="&who; &lt;1&gt;">Hi</note>"#,
+                ),
+                (
+                    "docs/loop.xml",
+                    r#"<loop xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="loop.xml"/></loop>"#,
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+
+    fn process_xml(xml: &str) -> Result<RefNode> {
+        let mut document = read_xml(xml)?;
+        process_with_options(
+            &mut document,
+            ParserOptions::new()
+                .base_uri("docs/book.xml")
+                .external_resolver(resolver()),
+        )?;
+        Ok(as_document(&document).unwrap().document_element().unwrap())
+    }
+
+    fn attribute(node: &RefNode, namespace_uri: &str, local_name: &str) -> Option<String> {
+        as_element(node)
+            .unwrap()
+            .get_attribute_ns(namespace_uri, local_name)
+    }
+
+    #[test]
+    fn test_include_xml() {
+        let book = process_xml(
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="chapter1.xml"/></book>"#,
+        )
+        .unwrap();
+        let chapter = book.first_child().unwrap();
+        assert_eq!(chapter.local_name(), "chapter");
+        assert_eq!(
+            attribute(&chapter, XML_NS_URI, XML_NS_ATTR_BASE),
+            Some("docs/chapter1.xml".to_string())
+        );
+        assert_eq!(
+            chapter.first_child().unwrap().to_string(),
+            r#"<intro xml:base="docs/part/intro.xml">Hello</intro>"#
+        );
+        assert_eq!(chapter.child_nodes().len(), 1);
+    }
+
+    #[test]
+    fn test_include_entity_in_attribute() {
+        let book = process_xml(
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="entities.xml"/></book>"#,
+        )
+        .unwrap();
+        let note = book.first_child().unwrap();
+        assert_eq!(note.local_name(), "note");
+        assert_eq!(
+            as_element(&note).unwrap().get_attribute("by"),
+            Some("the &#38; author &#60;1&#62;".to_string())
+        );
+    }
+
+    #[test]
+    fn test_include_xpointer() {
+        let book = process_xml(
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="sections.xml" xpointer="s2"/><xi:include href="sections.xml" xpointer="element(/1/1)"/></book>"#,
+        )
+        .unwrap();
+        let sections = book.child_nodes();
+        assert_eq!(sections.len(), 2);
+        for (section, id, text) in [(&sections[0], "s2", "Two"), (&sections[1], "s1", "One")] {
+            assert_eq!(
+                attribute(section, XML_NS_URI, XML_NS_ATTR_BASE),
+                Some("docs/sections.xml".to_string())
+            );
+            assert_eq!(attribute(section, XML_NS_URI, "id"), Some(id.to_string()));
+            assert_eq!(
+                section.first_child().unwrap().node_value(),
+                Some(text.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_include_text() {
+        let mut document = read_xml(
+            r#"<pre xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="code.txt" parse="text"/></pre>"#,
+        )
+        .unwrap();
+        process_with_options(
+            &mut document,
+            ParserOptions::new()
+                .base_uri("docs/book.xml")
+                .external_resolver(resolver()),
+        )
+        .unwrap();
+        let pre = as_document(&document).unwrap().document_element().unwrap();
+        assert_eq!(pre.child_nodes().len(), 1);
+        assert_eq!(
+            pre.first_child().unwrap().node_value(),
+            Some("a < b & c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_include_local() {
+        assert_eq!(
+            process_xml(
+                r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><title xml:id="t">Title</title><xi:include xpointer="element(t)"/></book>"#
+            )
+            .unwrap()
+            .to_string(),
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><title xml:id="t">Title</title><title>Title</title></book>"#
+        );
+    }
+
+    #[test]
+    fn test_fallback() {
+        assert_eq!(
+            process_xml(
+                r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="missing.xml"><xi:fallback><xi:include href="part/intro.xml"/></xi:fallback></xi:include></book>"#
+            )
+            .unwrap()
+            .to_string(),
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><intro xml:base="docs/part/intro.xml">Hello</intro></book>"#
+        );
+        assert_eq!(
+            process_xml(
+                r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="sections.xml" xpointer="s9"><xi:fallback>none</xi:fallback></xi:include></book>"#
+            )
+            .unwrap()
+            .to_string(),
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude">none</book>"#
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        for xml in [
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="missing.xml"/></book>"#,
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="loop.xml"/></book>"#,
+            r#"<book xml:id="b" xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include xpointer="b"/></book>"#,
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="chapter1.xml" parse="html"/></book>"#,
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="chapter1.xml#intro"/></book>"#,
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include/></book>"#,
+            r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><xi:fallback/></book>"#,
+        ] {
+            assert!(
+                matches!(process_xml(xml), Err(Error::XInclude(_))),
+                "expected an error from {}",
+                xml
+            );
+        }
+    }
+}
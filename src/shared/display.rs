@@ -1,183 +1,593 @@
+/*!
+Serialization of nodes as XML text, used by the `Display` implementation of `RefNode` and by the
+[`writer`](../../writer/index.html) module.
+
+Character data and attribute values are escaped so that the output parses back to the same
+content; comments, processing instructions, and CDATA sections are written as they are, except
+that a CDATA section containing `]]>` is split in two.
+
+If the options include an indent, line breaks and indentation are added only where whitespace is
+not significant: between the nodes outside the document element, within tags, and within element
+content. An element that contains character data, or that has `xml:space="preserve"` in scope, is
+written as it is along with all of its descendants.
+*/
+
 use crate::level2::convert::*;
 use crate::level2::ext::convert::{as_document_decl, RefDocumentDecl};
+use crate::level2::ext::{DocumentDecl, SpaceHandled, SpaceHandling, XmlDecl, XmlVersion};
 use crate::level2::*;
 use crate::shared::syntax::*;
-use std::fmt::{Formatter, Result as FmtResult};
+use crate::shared::text::{is_xml_11_restricted_char, is_xml_name};
+use crate::writer::{Declaration, EmptyElements, Encoding, Quote, WriterOptions};
+use std::fmt::{Result as FmtResult, Write};
 
 // ------------------------------------------------------------------------------------------------
-// Public Functions
+// Public Types
 // ------------------------------------------------------------------------------------------------
 
-pub(crate) fn fmt_element(element: RefElement<'_>, f: &mut Formatter<'_>) -> FmtResult {
-    write!(f, "{}{}", XML_ELEMENT_START_START, element.node_name())?;
-    for attr in element.attributes().values() {
-        write!(f, " {}", attr)?;
-    }
-    write!(f, "{}", XML_ELEMENT_START_END)?;
-    for child in element.child_nodes() {
-        write!(f, "{}", child)?;
-    }
-    write!(
-        f,
-        "{}{}{}",
-        XML_ELEMENT_END_START,
-        element.node_name(),
-        XML_ELEMENT_END_END
-    )
+///
+/// Writes nodes according to a set of `WriterOptions`. If the output has an `encoding`, characters
+/// in text and attribute values that it cannot represent are written as character references, and
+/// it is named in any XML declaration written. In an XML 1.1 document, restricted characters are
+/// always written as character references.
+///
+pub(crate) struct Serializer<'a, W: Write> {
+    f: &'a mut W,
+    options: &'a WriterOptions,
+    encoding: Option<Encoding>,
+    version: Option<XmlVersion>,
+    depth: usize,
+    mixed: bool,
 }
 
-pub(crate) fn fmt_attribute(attribute: RefAttribute<'_>, f: &mut Formatter<'_>) -> FmtResult {
-    write!(
-        f,
-        "{}=\"{}\"",
-        attribute.node_name(),
-        attribute.value().unwrap_or_default()
-    )
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Counts the characters written, to measure output without keeping it.
+///
+struct Columns(usize);
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+pub(crate) fn fmt_node<W: Write>(node: &RefNode, f: &mut W) -> FmtResult {
+    let options = WriterOptions::default();
+    Serializer::new(f, &options, None).node(node)
 }
 
-pub(crate) fn fmt_text(character_data: RefCharacterData<'_>, f: &mut Formatter<'_>) -> FmtResult {
-    match character_data.data() {
-        None => Ok(()),
-        Some(data) => write!(f, "{}", data),
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<'a, W: Write> Serializer<'a, W> {
+    pub(crate) fn new(
+        f: &'a mut W,
+        options: &'a WriterOptions,
+        encoding: Option<Encoding>,
+    ) -> Self {
+        Self {
+            f,
+            options,
+            encoding,
+            version: None,
+            depth: 0,
+            mixed: false,
+        }
     }
-}
 
-pub(crate) fn fmt_cdata(character_data: RefCharacterData<'_>, f: &mut Formatter<'_>) -> FmtResult {
-    match character_data.data() {
-        None => Ok(()),
-        Some(data) => write!(f, "{} {} {}", XML_CDATA_START, data, XML_CDATA_END),
+    pub(crate) fn node(&mut self, node: &RefNode) -> FmtResult {
+        if self.version.is_none() {
+            self.version = Some(document_version(node));
+        }
+        match node.node_type() {
+            NodeType::Element => self.element(node),
+            NodeType::Attribute => self.attribute(as_attribute(node).unwrap()),
+            NodeType::Text => self.text(as_character_data(node).unwrap()),
+            NodeType::CData => self.cdata(as_character_data(node).unwrap()),
+            NodeType::ProcessingInstruction => {
+                self.processing_instruction(as_processing_instruction(node).unwrap())
+            }
+            NodeType::Comment => self.comment(as_character_data(node).unwrap()),
+            NodeType::Document => self.document(as_document_decl(node).unwrap()),
+            NodeType::DocumentType => self.document_type(as_document_type(node).unwrap()),
+            NodeType::DocumentFragment => {
+                self.document_fragment(as_document_fragment(node).unwrap())
+            }
+            NodeType::Entity => self.entity(as_entity(node).unwrap()),
+            NodeType::EntityReference => self.entity_reference(as_entity_reference(node).unwrap()),
+            NodeType::Notation => self.notation(as_notation(node).unwrap()),
+        }
     }
-}
 
-pub(crate) fn fmt_processing_instruction(
-    pi: RefProcessingInstruction<'_>,
-    f: &mut Formatter<'_>,
-) -> FmtResult {
-    match pi.data() {
-        None => write!(f, "{}{}{}", XML_PI_START, pi.target(), XML_PI_END),
-        Some(data) => write!(f, "{}{} {}{}", XML_PI_START, pi.target(), data, XML_PI_END),
+    ///
+    /// Attributes are written in the order they were added to the element, or sorted by name. If
+    /// the start tag would extend beyond the maximum line width each attribute is written on a
+    /// line of its own.
+    ///
+    fn element(&mut self, node: &RefNode) -> FmtResult {
+        let element = as_element(node).unwrap();
+        let indenting = self.is_indenting(node);
+        write!(self.f, "{}{}", XML_ELEMENT_START_START, element.node_name())?;
+        let mut attributes: Vec<RefNode> = element
+            .attributes()
+            .into_iter()
+            .map(|(_, attr)| attr)
+            .collect();
+        if self.options.sort_attributes {
+            attributes.sort_by_key(|attr| attr.node_name().to_string());
+        }
+        let wrap = indenting && self.is_too_wide(element, &attributes)?;
+        for attr in &attributes {
+            if wrap {
+                self.new_line(self.depth + 1)?;
+            } else {
+                self.f.write_char(' ')?;
+            }
+            self.attribute(as_attribute(attr).unwrap())?;
+        }
+        if !element.has_child_nodes() && self.options.empty_elements == EmptyElements::SelfClosing {
+            return write!(self.f, "{}", XML_EMPTY_ELEMENT_END);
+        }
+        write!(self.f, "{}", XML_ELEMENT_START_END)?;
+        self.content(&element.child_nodes(), indenting)?;
+        write!(
+            self.f,
+            "{}{}{}",
+            XML_ELEMENT_END_START,
+            element.node_name(),
+            XML_ELEMENT_END_END
+        )
     }
-}
 
-pub(crate) fn fmt_comment(
-    character_data: RefCharacterData<'_>,
-    f: &mut Formatter<'_>,
-) -> FmtResult {
-    match character_data.data() {
-        None => Ok(()),
-        Some(data) => write!(f, "{}{}{}", XML_COMMENT_START, data, XML_COMMENT_END),
+    ///
+    /// References to entities in the value of an attribute are written as references, all other
+    /// markup characters are escaped.
+    ///
+    fn attribute(&mut self, attribute: RefAttribute<'_>) -> FmtResult {
+        let quote = self.options.quote.as_char();
+        write!(self.f, "{}={}", attribute.node_name(), quote)?;
+        for child in attribute.child_nodes() {
+            match child.node_type() {
+                NodeType::EntityReference => self.node(&child)?,
+                _ => self.attribute_value(&child.node_value().unwrap_or_default())?,
+            }
+        }
+        self.f.write_char(quote)
     }
-}
 
-pub(crate) fn fmt_document(document: RefDocumentDecl<'_>, f: &mut Formatter<'_>) -> FmtResult {
-    if let Some(xml_declaration) = &document.xml_declaration() {
-        write!(f, "{}", xml_declaration)?;
+    fn text(&mut self, character_data: RefCharacterData<'_>) -> FmtResult {
+        match character_data.node_value() {
+            None => Ok(()),
+            Some(data) => self.text_value(&data),
+        }
     }
-    if let Some(doc_type) = &document.doc_type() {
-        write!(f, "{}", doc_type)?;
+
+    ///
+    /// The sequence `]]>` may not appear in a CDATA section, the section is split between the `]]`
+    /// and the `>`.
+    ///
+    fn cdata(&mut self, character_data: RefCharacterData<'_>) -> FmtResult {
+        match character_data.node_value() {
+            None => Ok(()),
+            Some(data) => {
+                let split = format!("]]{}{}>", XML_CDATA_END, XML_CDATA_START);
+                write!(
+                    self.f,
+                    "{}{}{}",
+                    XML_CDATA_START,
+                    data.replace(XML_CDATA_END, &split),
+                    XML_CDATA_END
+                )
+            }
+        }
     }
-    for child in document.child_nodes() {
-        write!(f, "{}", child)?;
+
+    fn processing_instruction(&mut self, pi: RefProcessingInstruction<'_>) -> FmtResult {
+        match pi.data() {
+            None => write!(self.f, "{}{}{}", XML_PI_START, pi.target(), XML_PI_END),
+            Some(data) => write!(
+                self.f,
+                "{}{} {}{}",
+                XML_PI_START,
+                pi.target(),
+                data,
+                XML_PI_END
+            ),
+        }
     }
-    Ok(())
-}
 
-pub(crate) fn fmt_document_type(doc_type: RefDocumentType<'_>, f: &mut Formatter<'_>) -> FmtResult {
-    write!(f, "{} {}", XML_DOCTYPE_START, doc_type.node_name())?;
-    if let Some(id) = &doc_type.public_id() {
-        write!(f, " {} \"{}\"", XML_DOCTYPE_PUBLIC, id)?;
+    fn comment(&mut self, character_data: RefCharacterData<'_>) -> FmtResult {
+        match character_data.node_value() {
+            None => Ok(()),
+            Some(data) => write!(self.f, "{}{}{}", XML_COMMENT_START, data, XML_COMMENT_END),
+        }
     }
-    if let Some(id) = &doc_type.system_id() {
-        write!(f, " {} \"{}\"", XML_DOCTYPE_SYSTEM, id)?;
+
+    fn document(&mut self, document: RefDocumentDecl<'_>) -> FmtResult {
+        let xml_declaration = match self.options.xml_declaration {
+            Declaration::AsDocument => document.xml_declaration(),
+            Declaration::Always => Some(document.xml_declaration().unwrap_or_default()),
+            Declaration::Never => None,
+        };
+        if let Some(xml_declaration) = &xml_declaration {
+            self.xml_declaration(xml_declaration)?;
+        }
+        let nodes: Vec<RefNode> = document
+            .doc_type()
+            .into_iter()
+            .chain(document.child_nodes())
+            .collect();
+        self.top_level(&nodes, xml_declaration.is_none())?;
+        if self.options.indent.is_some() {
+            self.f.write_char('\n')?;
+        }
+        Ok(())
     }
-    if (doc_type.entities().len() + doc_type.notations().len() > 0)
-        || doc_type.internal_subset().is_some()
-    {
-        write!(f, "{}", XML_DOCTYPE_ENTITY_START)?;
-        for (_, entity) in doc_type.entities() {
-            write!(f, "{}", entity)?;
+
+    ///
+    /// The declared encoding is replaced by that of the output, if any.
+    ///
+    fn xml_declaration(&mut self, xml_declaration: &XmlDecl) -> FmtResult {
+        let quote = self.options.quote.as_char();
+        write!(
+            self.f,
+            "{} {}={}{}{}",
+            XML_DECL_START,
+            XML_DECL_VERSION,
+            quote,
+            xml_declaration.version(),
+            quote
+        )?;
+        let encoding = match self.encoding {
+            None => xml_declaration.encoding(),
+            Some(encoding)
+                if xml_declaration.encoding().is_some() || encoding != Encoding::Utf8 =>
+            {
+                Some(encoding.name().to_string())
+            }
+            Some(_) => None,
+        };
+        if let Some(encoding) = encoding {
+            write!(
+                self.f,
+                " {}={}{}{}",
+                XML_DECL_ENCODING, quote, encoding, quote
+            )?;
         }
-        for (_, notation) in doc_type.notations() {
-            write!(f, "{}", notation)?;
+        if let Some(standalone) = xml_declaration.standalone() {
+            write!(
+                self.f,
+                " {}={}{}{}",
+                XML_DECL_STANDALONE,
+                quote,
+                if standalone {
+                    XML_DECL_STANDALONE_YES
+                } else {
+                    XML_DECL_STANDALONE_NO
+                },
+                quote
+            )?;
         }
+        write!(self.f, "{}", XML_DECL_END)
+    }
+
+    ///
+    /// The internal subset, if present, includes the declarations of the document type's entities
+    /// and notations; otherwise these are written individually.
+    ///
+    fn document_type(&mut self, doc_type: RefDocumentType<'_>) -> FmtResult {
+        write!(self.f, "{} {}", XML_DOCTYPE_START, doc_type.node_name())?;
+        write_external_id(doc_type.public_id(), doc_type.system_id(), self.f)?;
         if let Some(internal_subset) = doc_type.internal_subset() {
-            write!(f, "{}", internal_subset)?;
+            write!(
+                self.f,
+                " {}{}{}",
+                XML_DOCTYPE_ENTITY_START, internal_subset, XML_DOCTYPE_ENTITY_END
+            )?;
+        } else if doc_type.entities().len() + doc_type.notations().len() > 0 {
+            write!(self.f, " {}", XML_DOCTYPE_ENTITY_START)?;
+            for entity in doc_type.entities().values() {
+                self.node(entity)?;
+            }
+            for notation in doc_type.notations().values() {
+                self.node(notation)?;
+            }
+            write!(self.f, "{}", XML_DOCTYPE_ENTITY_END)?;
         }
-        write!(f, "{}", XML_DOCTYPE_ENTITY_END)?;
+        write!(self.f, "{}", XML_DOCTYPE_END)
     }
-    write!(f, "{}", XML_DOCTYPE_END)
-}
 
-pub(crate) fn fmt_document_fragment(
-    fragment: RefDocumentFragment<'_>,
-    f: &mut Formatter<'_>,
-) -> FmtResult {
-    write!(f, "{}{} ", XML_CDATA_START, fragment.node_name())?;
-    for child in fragment.child_nodes() {
-        write!(f, "{}", child)?;
+    fn document_fragment(&mut self, fragment: RefDocumentFragment<'_>) -> FmtResult {
+        let children = fragment.child_nodes();
+        if self.mixed || is_mixed(&children) {
+            self.content(&children, false)
+        } else {
+            self.top_level(&children, true)
+        }
+    }
+
+    fn entity(&mut self, entity: RefEntity<'_>) -> FmtResult {
+        write!(self.f, "{} {}", XML_ENTITY_START, entity.node_name())?;
+        if entity.public_id().is_none() && entity.system_id().is_none() {
+            self.f.write_str(" \"")?;
+            self.entity_value(&entity.node_value().unwrap_or_default())?;
+            self.f.write_char('"')?;
+        } else {
+            write_external_id(entity.public_id(), entity.system_id(), self.f)?;
+        }
+        if let Some(notation_name) = entity.notation_name() {
+            write!(self.f, " {} {}", XML_ENTITY_NOTATION, notation_name)?;
+        }
+        write!(self.f, "{}", XML_ENTITY_END)
+    }
+
+    fn entity_reference(&mut self, entity_ref: RefEntityReference<'_>) -> FmtResult {
+        write!(
+            self.f,
+            "{}{}{}",
+            XML_ENTITYREF_START,
+            entity_ref.node_name(),
+            XML_ENTITYREF_END
+        )
+    }
+
+    fn notation(&mut self, notation: RefNotation<'_>) -> FmtResult {
+        write!(self.f, "{} {}", XML_NOTATION_START, notation.node_name())?;
+        write_external_id(notation.public_id(), notation.system_id(), self.f)?;
+        write!(self.f, "{}", XML_NOTATION_END)
+    }
+
+    ///
+    /// Returns `true` if line breaks and indentation may be added to the content of `element`.
+    ///
+    fn is_indenting(&self, element: &RefNode) -> bool {
+        self.options.indent.is_some()
+            && !self.mixed
+            && element.space_handling() != SpaceHandling::Preserve
+    }
+
+    ///
+    /// Returns `true` if the start tag of `element`, written on one line at the current depth,
+    /// is wider than the maximum line width.
+    ///
+    fn is_too_wide(
+        &self,
+        element: RefElement<'_>,
+        attributes: &[RefNode],
+    ) -> std::result::Result<bool, std::fmt::Error> {
+        let max_line_width = match self.options.max_line_width {
+            Some(max_line_width) if attributes.len() > 1 => max_line_width,
+            _ => return Ok(false),
+        };
+        let mut columns = Columns(
+            self.indent_width(self.depth) + element.node_name().to_string().chars().count() + 2,
+        );
+        let mut serializer = Serializer::new(&mut columns, self.options, self.encoding);
+        serializer.version = self.version;
+        for attr in attributes {
+            serializer.f.write_char(' ')?;
+            serializer.attribute(as_attribute(attr).unwrap())?;
+        }
+        Ok(columns.0 > max_line_width)
+    }
+
+    ///
+    /// The children of an element; content that includes character data is mixed content, in
+    /// which whitespace is significant and so is never added.
+    ///
+    fn content(&mut self, children: &[RefNode], indenting: bool) -> FmtResult {
+        let mixed = is_mixed(children);
+        let was_mixed = self.mixed;
+        self.mixed = was_mixed || mixed;
+        self.depth += 1;
+        for child in children {
+            if indenting && !mixed {
+                self.new_line(self.depth)?;
+            }
+            self.node(child)?;
+        }
+        self.depth -= 1;
+        self.mixed = was_mixed;
+        if indenting && !mixed && !children.is_empty() {
+            self.new_line(self.depth)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Nodes outside any element, written on separate lines when indenting.
+    ///
+    fn top_level(&mut self, nodes: &[RefNode], at_start: bool) -> FmtResult {
+        for (index, node) in nodes.iter().enumerate() {
+            if self.options.indent.is_some() && !(at_start && index == 0) {
+                self.new_line(0)?;
+            }
+            self.node(node)?;
+        }
+        Ok(())
     }
-    write!(f, "{}", XML_CDATA_END)
-}
 
-pub(crate) fn fmt_entity(entity: RefEntity<'_>, f: &mut Formatter<'_>) -> FmtResult {
-    write!(f, "{} {}", XML_ENTITY_START, entity.node_name())?;
-    if entity.public_id().is_none() && entity.system_id().is_none() {
-        write!(f, " \"{}\"", entity.node_value().unwrap_or_default())?;
-    } else if let Some(public_id) = entity.public_id() {
-        write!(f, " {} \"{}\"", XML_DOCTYPE_PUBLIC, public_id)?;
-        if let Some(system_id) = entity.system_id() {
-            write!(f, " \"{}\"", system_id)?;
+    fn new_line(&mut self, depth: usize) -> FmtResult {
+        self.f.write_char('\n')?;
+        if let Some(indent) = &self.options.indent {
+            for _ in 0..depth {
+                self.f.write_str(indent)?;
+            }
         }
-    } else if let Some(system_id) = entity.system_id() {
-        write!(f, " {} \"{}\"", XML_DOCTYPE_SYSTEM, system_id)?;
+        Ok(())
     }
-    if let Some(entity_name) = entity.notation_name() {
-        write!(f, " {}", entity_name)?;
+
+    fn indent_width(&self, depth: usize) -> usize {
+        match &self.options.indent {
+            None => 0,
+            Some(indent) => indent.chars().count() * depth,
+        }
+    }
+
+    ///
+    /// Character data; `>` is escaped so that `]]>` never appears, and carriage returns so that
+    /// they are not removed by end-of-line handling.
+    ///
+    fn text_value(&mut self, data: &str) -> FmtResult {
+        for c in data.chars() {
+            match c {
+                XML_ESC_AMP_CHAR => self.f.write_str("&amp;")?,
+                XML_ESC_LT_CHAR => self.f.write_str("&lt;")?,
+                XML_ESC_GT_CHAR => self.f.write_str("&gt;")?,
+                '\r' => self.f.write_str("&#xD;")?,
+                c => self.character(c)?,
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// The value of an attribute, within quotes. White space other than spaces is escaped so that
+    /// it is not replaced by attribute value normalization. Entity and character references are
+    /// retained in the values of attributes, see `Attribute::value`, so an ampersand that starts a
+    /// reference is not escaped.
+    ///
+    fn attribute_value(&mut self, value: &str) -> FmtResult {
+        let quote = self.options.quote;
+        for (index, c) in value.char_indices() {
+            match c {
+                XML_ESC_AMP_CHAR if is_reference(&value[index..]) => self.f.write_char(c)?,
+                XML_ESC_AMP_CHAR => self.f.write_str("&amp;")?,
+                XML_ESC_LT_CHAR => self.f.write_str("&lt;")?,
+                XML_ESC_GT_CHAR => self.f.write_str("&gt;")?,
+                XML_ESC_QUOT_CHAR if quote == Quote::Double => self.f.write_str("&quot;")?,
+                XML_ESC_APOS_CHAR if quote == Quote::Single => self.f.write_str("&apos;")?,
+                '\t' => self.f.write_str("&#x9;")?,
+                '\n' => self.f.write_str("&#xA;")?,
+                '\r' => self.f.write_str("&#xD;")?,
+                c => self.character(c)?,
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// The replacement text of an internal entity, within double quotes; references are retained,
+    /// but a parameter entity reference may not appear in an entity value in the internal subset.
+    ///
+    fn entity_value(&mut self, value: &str) -> FmtResult {
+        for c in value.chars() {
+            match c {
+                XML_ESC_QUOT_CHAR => self.f.write_str("&#34;")?,
+                '%' => self.f.write_str("&#37;")?,
+                c => self.character(c)?,
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// A character that the output encoding cannot represent is written as a reference, as are
+    /// the characters that XML 1.1 only allows as references. The line ends NEL and LS are also
+    /// written as references in XML 1.1 so that they are not replaced by end-of-line handling.
+    ///
+    fn character(&mut self, c: char) -> FmtResult {
+        match self.encoding {
+            Some(encoding) if !encoding.can_encode(c) => write!(self.f, "&#x{:X};", c as u32),
+            _ if self.version == Some(XmlVersion::V11)
+                && (is_xml_11_restricted_char(c) || c == '\u{85}' || c == '\u{2028}') =>
+            {
+                write!(self.f, "&#x{:X};", c as u32)
+            }
+            _ => self.f.write_char(c),
+        }
     }
-    write!(f, "{}", XML_ENTITY_END)
 }
 
-pub(crate) fn fmt_entity_reference(
-    entity_ref: RefEntityReference<'_>,
-    f: &mut Formatter<'_>,
-) -> FmtResult {
-    write!(
-        f,
-        "{}{}{}",
-        XML_ENTITYREF_START,
-        entity_ref.node_name(),
-        XML_ENTITYREF_END
-    )
+// ------------------------------------------------------------------------------------------------
+
+impl Write for Columns {
+    fn write_str(&mut self, s: &str) -> FmtResult {
+        self.0 += s.chars().count();
+        Ok(())
+    }
 }
 
-pub(crate) fn fmt_notation(notation: RefNotation<'_>, f: &mut Formatter<'_>) -> FmtResult {
-    write!(f, "{} {}", XML_NOTATION_START, notation.node_name())?;
-    if let Some(public_id) = notation.public_id() {
-        write!(f, " {} \"{}\"", XML_DOCTYPE_PUBLIC, public_id)?;
-        if let Some(system_id) = notation.system_id() {
-            write!(f, " \"{}\"", system_id)?;
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The version declared by the document that owns `node`, or XML 1.0 if there is none.
+///
+fn document_version(node: &RefNode) -> XmlVersion {
+    let document = if node.node_type() == NodeType::Document {
+        Some(node.clone())
+    } else {
+        node.owner_document()
+    };
+    document
+        .and_then(|document| document.xml_declaration())
+        .map(|xml_decl| xml_decl.version())
+        .unwrap_or(XmlVersion::V10)
+}
+
+fn is_mixed(children: &[RefNode]) -> bool {
+    children.iter().any(|child| {
+        matches!(
+            child.node_type(),
+            NodeType::Text | NodeType::CData | NodeType::EntityReference
+        )
+    })
+}
+
+///
+/// ```ebnf
+/// ExternalID    ::= 'SYSTEM' S SystemLiteral | 'PUBLIC' S PubidLiteral S SystemLiteral
+/// PublicID      ::= 'PUBLIC' S PubidLiteral
+/// ```
+///
+fn write_external_id<W: Write>(
+    public_id: Option<String>,
+    system_id: Option<String>,
+    f: &mut W,
+) -> FmtResult {
+    match (public_id, system_id) {
+        (Some(public_id), system_id) => {
+            write!(f, " {} {}", XML_DOCTYPE_PUBLIC, quoted(&public_id))?;
+            if let Some(system_id) = system_id {
+                write!(f, " {}", quoted(&system_id))?;
+            }
+            Ok(())
         }
-    } else if let Some(system_id) = notation.system_id() {
-        write!(f, " {} \"{}\"", XML_DOCTYPE_SYSTEM, system_id)?;
+        (None, Some(system_id)) => write!(f, " {} {}", XML_DOCTYPE_SYSTEM, quoted(&system_id)),
+        (None, None) => Ok(()),
+    }
+}
+
+///
+/// A literal that cannot contain references, quoted with apostrophes if it contains a quote.
+///
+fn quoted(literal: &str) -> String {
+    if literal.contains(XML_ESC_QUOT_CHAR) {
+        format!("'{}'", literal)
+    } else {
+        format!("\"{}\"", literal)
     }
-    write!(f, "{}", XML_NOTATION_END)
 }
 
-pub(crate) fn fmt_node(node: &RefNode, f: &mut Formatter<'_>) -> FmtResult {
-    match node.node_type() {
-        NodeType::Element => fmt_element(as_element(node).unwrap(), f),
-        NodeType::Attribute => fmt_attribute(as_attribute(node).unwrap(), f),
-        NodeType::Text => fmt_text(as_character_data(node).unwrap(), f),
-        NodeType::CData => fmt_cdata(as_character_data(node).unwrap(), f),
-        NodeType::ProcessingInstruction => {
-            fmt_processing_instruction(as_processing_instruction(node).unwrap(), f)
-        }
-        NodeType::Comment => fmt_comment(as_character_data(node).unwrap(), f),
-        NodeType::Document => fmt_document(as_document_decl(node).unwrap(), f),
-        NodeType::DocumentType => fmt_document_type(as_document_type(node).unwrap(), f),
-        NodeType::DocumentFragment => fmt_document_fragment(as_document_fragment(node).unwrap(), f),
-        NodeType::Entity => fmt_entity(as_entity(node).unwrap(), f),
-        NodeType::EntityReference => fmt_entity_reference(as_entity_reference(node).unwrap(), f),
-        NodeType::Notation => fmt_notation(as_notation(node).unwrap(), f),
+///
+/// Returns `true` if `s` starts with a complete entity or character reference.
+///
+fn is_reference(s: &str) -> bool {
+    let end = match s.find(XML_ENTITYREF_END) {
+        None => return false,
+        Some(end) => end,
+    };
+    let reference = &s[XML_ENTITYREF_START.len()..end];
+    if let Some(digits) = reference.strip_prefix("#x") {
+        !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit())
+    } else if let Some(digits) = reference.strip_prefix('#') {
+        !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+    } else {
+        is_xml_name(reference)
     }
 }
@@ -20,3 +20,5 @@ pub(crate) mod rc_cell;
 pub(crate) mod syntax;
 
 pub(crate) mod text;
+
+pub(crate) mod uri;
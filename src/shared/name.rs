@@ -198,8 +198,9 @@ impl Name {
             }
         }
 
-        if (local == XML_NS_ATTRIBUTE && namespace_uri != XML_NS_URI)
-            || (local == XMLNS_NS_ATTRIBUTE && namespace_uri != XMLNS_NS_URI)
+        if prefix.is_none()
+            && ((local == XML_NS_ATTRIBUTE && namespace_uri != XML_NS_URI)
+                || (local == XMLNS_NS_ATTRIBUTE && namespace_uri != XMLNS_NS_URI))
         {
             return Err(Error::Namespace);
         }
@@ -485,6 +486,10 @@ mod tests {
         let name = Name::new_ns(RDF_NS, "xmlns:rdf");
         assert_eq!(name.err().unwrap(), Error::Namespace);
 
+        let name = Name::new_ns(XMLNS_NS_URI, "xmlns:xml");
+        assert!(name.is_ok());
+        assert!(name.unwrap().is_namespace_attribute());
+
         let name = Name::from_str("x:hello").unwrap();
         assert!(!name.is_namespace_attribute());
     }
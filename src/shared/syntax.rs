@@ -13,6 +13,8 @@ pub(crate) const XML_PI_RESERVED: &str = "xml";
 
 pub(crate) const XML_COMMENT_START: &str = "<!--";
 pub(crate) const XML_COMMENT_END: &str = "-->";
+pub(crate) const XML_COMMENT_DOUBLE_HYPHEN: &str = "--";
+pub(crate) const XML_COMMENT_HYPHEN: char = '-';
 
 pub(crate) const XML_CDATA_START: &str = "<![CDATA[";
 pub(crate) const XML_CDATA_END: &str = "]]>";
@@ -28,6 +30,7 @@ pub(crate) const XML_ELEMENT_START_START: &str = "<";
 pub(crate) const XML_ELEMENT_START_END: &str = ">";
 pub(crate) const XML_ELEMENT_END_START: &str = "</";
 pub(crate) const XML_ELEMENT_END_END: &str = ">";
+pub(crate) const XML_EMPTY_ELEMENT_END: &str = "/>";
 
 pub(crate) const XML_ENTITY_START: &str = "<!ENTITY";
 pub(crate) const XML_ENTITY_END: &str = ">";
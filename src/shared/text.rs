@@ -1,4 +1,7 @@
+use crate::level2::ext::XmlVersion;
+use crate::shared::error::{Error, Result};
 use crate::shared::syntax::*;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::str::FromStr;
 use std::sync::OnceLock;
@@ -7,15 +10,118 @@ use std::sync::OnceLock;
 //  Public Types
 // ------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug, Default, PartialEq)]
-pub(crate) enum SpaceHandling {
+///
+/// The handling of whitespace within an element, as specified by the `xml:space` attribute; see
+/// XML 1.1 §2.10 [White Space Handling](https://www.w3.org/TR/xml11/#sec-white-space).
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpaceHandling {
+    /// The application's default whitespace processing modes are acceptable.
     #[default]
     Default,
+    /// Applications should preserve all whitespace.
     Preserve,
 }
 
 pub(crate) trait EntityResolver {
-    fn resolve(&self, entity: &str) -> Option<String>;
+    ///
+    /// Returns the replacement text of the entity referenced by `entity`, which has the form
+    /// `&name;`, or `None` if no such entity is declared.
+    ///
+    fn resolve(&self, entity: &str) -> Result<Option<String>>;
+    ///
+    /// The limits on expanding the entities this resolver returns.
+    ///
+    fn entity_limits(&self) -> EntityLimits {
+        EntityLimits::default()
+    }
+}
+
+///
+/// Limits on the expansion of entity references in a value, the same limits that
+/// `ParserOptions` applies to a document as it is parsed.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct EntityLimits {
+    /// The depth to which references may be nested.
+    pub(crate) max_depth: usize,
+    /// The total size, in bytes, of the replacement text of all references.
+    pub(crate) max_expansion: usize,
+}
+
+// ------------------------------------------------------------------------------------------------
+//  Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The entities being expanded, innermost last, the size of their replacement text and the depth
+/// of nesting reached so far, and the entities already expanded.
+///
+struct Expansion {
+    open_entities: Vec<String>,
+    expanded_bytes: usize,
+    deepest: usize,
+    limits: EntityLimits,
+    expanded: HashMap<String, Expanded>,
+}
+
+// ------------------------------------------------------------------------------------------------
+//  Public Values
+// ------------------------------------------------------------------------------------------------
+
+pub(crate) const DEFAULT_MAX_ENTITY_DEPTH: usize = 20;
+
+pub(crate) const DEFAULT_MAX_ENTITY_EXPANSION: usize = 10 * 1024 * 1024;
+
+// ------------------------------------------------------------------------------------------------
+//  Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for EntityLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_ENTITY_DEPTH,
+            max_expansion: DEFAULT_MAX_ENTITY_EXPANSION,
+        }
+    }
+}
+
+impl Expansion {
+    ///
+    /// Charge the expansion of a reference nested `depth` deep, with `bytes` of replacement text,
+    /// returning `Error::StringSize` if this exceeds a limit.
+    ///
+    fn charge(&mut self, depth: usize, bytes: usize) -> Result<()> {
+        let depth = self.open_entities.len() + depth;
+        if depth > self.limits.max_depth {
+            error!(
+                "entity references nested more than {} deep",
+                self.limits.max_depth
+            );
+            return Err(Error::StringSize);
+        }
+        self.expanded_bytes = self.expanded_bytes.saturating_add(bytes);
+        if self.expanded_bytes > self.limits.max_expansion {
+            error!(
+                "entity references expand to more than {} bytes",
+                self.limits.max_expansion
+            );
+            return Err(Error::StringSize);
+        }
+        self.deepest = self.deepest.max(depth);
+        Ok(())
+    }
+}
+
+///
+/// An entity, once expanded, is not expanded again but is charged against the limits as if it
+/// were.
+///
+#[derive(Clone)]
+struct Expanded {
+    value: String,
+    depth: usize,
+    bytes: usize,
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -61,32 +167,93 @@ pub(crate) trait EntityResolver {
 /// It is an error if an attribute value contains a reference to an entity for which no declaration
 /// has been read.
 ///
+/// A reference to an entity for which no declaration has been read returns `Error::NotFound`, and
+/// a reference to an entity from within its own replacement text returns `Error::Syntax`.
+/// References nested, or expanded, beyond the resolver's `EntityLimits` return
+/// `Error::StringSize`.
+///
 pub(crate) fn normalize_attribute_value(
     value: impl AsRef<str>,
     resolver: &dyn EntityResolver,
     is_cdata: bool,
-) -> String {
-    let value = value.as_ref();
+) -> Result<String> {
+    let mut expansion = Expansion {
+        open_entities: Vec::new(),
+        expanded_bytes: 0,
+        deepest: 0,
+        limits: resolver.entity_limits(),
+        expanded: Default::default(),
+    };
+    normalize_value(value.as_ref(), resolver, is_cdata, &mut expansion)
+}
+
+///
+/// Normalize `value`, which is the replacement text of the entities open in `expansion` if any.
+///
+fn normalize_value(
+    value: &str,
+    resolver: &dyn EntityResolver,
+    is_cdata: bool,
+    expansion: &mut Expansion,
+) -> Result<String> {
     let step_1 = normalize_end_of_lines(value);
     let step_3 = if step_1.is_empty() {
         step_1
     } else {
         static FIND: OnceLock<regex::Regex> = OnceLock::new();
         let find = FIND.get_or_init(|| {regex::Regex::new(
-            r"(?P<entity_ref>[&%][\pL_][\pL\.\d_\-]*;)|(?P<char>&#\d+;)|(?P<char_hex>&#x[0-9a-fA-F]+;)|(?P<ws>[\u{09}\u{0A}\u{0D}])",
+            r"(?P<entity_ref>&[\pL_][\pL\.\d_\-]*;)|(?P<char>&#\d+;)|(?P<char_hex>&#x[0-9a-fA-F]+;)|(?P<ws>[\u{09}\u{0A}\u{0D}])",
         )
         .unwrap()});
         let mut step_2 = String::new();
         let mut last_end = 0;
         for capture in find.captures_iter(&step_1) {
             let (start, end, replacement) = if let Some(a_match) = capture.name("entity_ref") {
-                //
-                // TODO: this does not yet deal with entity references.
-                //
-                let replacement = match resolver.resolve(a_match.as_str()) {
-                    None => panic!("unknown entity reference {}", a_match.as_str()),
-                    Some(replacement) => {
-                        normalize_attribute_value(&replacement, resolver, is_cdata)
+                let reference = a_match.as_str();
+                let name = &reference
+                    [XML_ENTITYREF_START.len()..reference.len() - XML_ENTITYREF_END.len()];
+                if let Some(c) = predefined_entity(name) {
+                    //
+                    // The predefined entities are replaced by the character they reference,
+                    // which is not itself normalized.
+                    //
+                    step_2.push_str(&step_1[last_end..a_match.start()]);
+                    step_2.push(c);
+                    last_end = a_match.end();
+                    continue;
+                }
+                if expansion.open_entities.iter().any(|open| open == reference) {
+                    error!("recursive entity reference {}", reference);
+                    return Err(Error::Syntax);
+                }
+                let replacement = if let Some(expanded) = expansion.expanded.get(reference) {
+                    let expanded = expanded.clone();
+                    expansion.charge(expanded.depth, expanded.bytes)?;
+                    expanded.value
+                } else {
+                    match resolver.resolve(reference)? {
+                        None => {
+                            error!("unknown entity reference {}", reference);
+                            return Err(Error::NotFound);
+                        }
+                        Some(replacement) => {
+                            let outer_bytes = expansion.expanded_bytes;
+                            let outer_deepest = std::mem::take(&mut expansion.deepest);
+                            expansion.charge(1, replacement.len())?;
+                            expansion.open_entities.push(reference.to_string());
+                            let value =
+                                normalize_value(&replacement, resolver, is_cdata, expansion)?;
+                            let _safe_to_ignore = expansion.open_entities.pop();
+                            let expanded = Expanded {
+                                value: value.clone(),
+                                depth: expansion.deepest - expansion.open_entities.len(),
+                                bytes: expansion.expanded_bytes - outer_bytes,
+                            };
+                            expansion.deepest = expansion.deepest.max(outer_deepest);
+                            let _safe_to_ignore =
+                                expansion.expanded.insert(reference.to_string(), expanded);
+                            value
+                        }
                     }
                 };
                 (a_match.start(), a_match.end(), replacement)
@@ -113,9 +280,9 @@ pub(crate) fn normalize_attribute_value(
         step_2
     };
     if is_cdata {
-        step_3
+        Ok(step_3)
     } else {
-        step_3.trim_matches(' ').to_string()
+        Ok(step_3.trim_matches(' ').to_string())
     }
 }
 
@@ -201,6 +368,21 @@ pub(crate) fn escape(input: impl AsRef<str>) -> String {
     result
 }
 
+///
+/// Returns the character referenced by the predefined entity `name`, if it is one of `amp`,
+/// `apos`, `gt`, `lt`, or `quot`.
+///
+pub(crate) fn predefined_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some(XML_ESC_AMP_CHAR),
+        "apos" => Some(XML_ESC_APOS_CHAR),
+        "gt" => Some(XML_ESC_GT_CHAR),
+        "lt" => Some(XML_ESC_LT_CHAR),
+        "quot" => Some(XML_ESC_QUOT_CHAR),
+        _ => None,
+    }
+}
+
 pub(crate) fn to_entity(c: char) -> String {
     format!(
         "{}{}{}",
@@ -261,14 +443,13 @@ fn char_from_entity(entity: impl AsRef<str>) -> String {
 /// [#x10FFFE-#x10FFFF].
 /// ```
 ///
-#[allow(dead_code)]
 pub(crate) fn is_xml_10_char(c: char) -> bool {
     c == '\u{0009}'
         || c == '\u{000A}'
         || c == '\u{000D}'
         || ('\u{0020}'..='\u{D7FF}').contains(&c)
         || ('\u{E000}'..='\u{FFFD}').contains(&c)
-        || ('\u{10000}'..='\u{10FFF}').contains(&c)
+        || ('\u{10000}'..='\u{10FFFF}').contains(&c)
 }
 
 #[allow(dead_code)]
@@ -288,7 +469,6 @@ pub(crate) fn is_xml_10_restricted_char(c: char) -> bool {
 ///                 /* any Unicode character, excluding the surrogate blocks, FFFE, and FFFF. */
 /// ```
 ///
-#[allow(dead_code)]
 pub(crate) fn is_xml_11_char(c: char) -> bool {
     //
     // below ranges are always valid for XML 1.1 documents
@@ -296,7 +476,7 @@ pub(crate) fn is_xml_11_char(c: char) -> bool {
     //
     ('\u{0001}'..='\u{D7FF}').contains(&c)
         || ('\u{E000}'..='\u{FFFD}').contains(&c)
-        || ('\u{10000}'..='\u{10FFF}').contains(&c)
+        || ('\u{10000}'..='\u{10FFFF}').contains(&c)
 }
 
 ///
@@ -306,7 +486,6 @@ pub(crate) fn is_xml_11_char(c: char) -> bool {
 /// RestrictedChar  ::=  #x1-#x8] | [#xB-#xC] | [#xE-#x1F] | [#x7F-#x84] | [#x86-#x9F]
 /// ```
 ///
-#[allow(dead_code)]
 pub(crate) fn is_xml_11_restricted_char(c: char) -> bool {
     //
     // below ranges are always valid for XML 1.1 documents
@@ -319,6 +498,37 @@ pub(crate) fn is_xml_11_restricted_char(c: char) -> bool {
         || ('\u{86}'..='\u{9F}').contains(&c)
 }
 
+///
+/// Returns `true` if `c` matches the `Char` production of the specified version of XML; this is
+/// the set of characters a document may contain, including those given as character references.
+///
+pub(crate) fn is_xml_char(c: char, version: XmlVersion) -> bool {
+    match version {
+        XmlVersion::V10 => is_xml_10_char(c),
+        XmlVersion::V11 => is_xml_11_char(c),
+    }
+}
+
+///
+/// Returns `true` if `c` may appear literally in the specified version of XML. In XML 1.1 a
+/// `RestrictedChar` may only appear as a character reference, see
+/// [XML 11 §2.2](https://www.w3.org/TR/xml11/#charsets).
+///
+pub(crate) fn is_xml_literal_char(c: char, version: XmlVersion) -> bool {
+    match version {
+        XmlVersion::V10 => is_xml_10_char(c),
+        XmlVersion::V11 => is_xml_11_char(c) && !is_xml_11_restricted_char(c),
+    }
+}
+
+///
+/// Returns `true` if all the characters in `s` match the `Char` production of the specified
+/// version of XML.
+///
+pub(crate) fn is_xml_chars(s: impl AsRef<str>, version: XmlVersion) -> bool {
+    s.as_ref().chars().all(|c| is_xml_char(c, version))
+}
+
 ///
 /// S (white space) consists of one or more space (#x20) characters, carriage returns, line feeds,
 /// or tabs.
@@ -450,7 +660,7 @@ impl Display for SpaceHandling {
 impl FromStr for SpaceHandling {
     type Err = ();
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         if s == XML_NS_ATTR_SPACE_DEFAULT {
             Ok(SpaceHandling::Default)
         } else if s == XML_NS_ATTR_SPACE_PRESERVE {
@@ -522,10 +732,10 @@ mod tests {
     struct NoneEntityResolver {}
 
     impl EntityResolver for NoneEntityResolver {
-        fn resolve(&self, name: &str) -> Option<String> {
+        fn resolve(&self, name: &str) -> Result<Option<String>> {
             let result: Option<String> = None;
             println!("EntityResolver::resolve({:?}) -> {:?}", name, result);
-            result
+            Ok(result)
         }
     }
 
@@ -539,19 +749,27 @@ mod tests {
         let resolver = none_entity_resolver();
         let resolver = resolver.borrow();
         assert_eq!(
-            normalize_attribute_value("  abc ", resolver, true),
+            normalize_attribute_value("  abc ", resolver, true).unwrap(),
             "  abc "
         );
-        assert_eq!(normalize_attribute_value("  abc ", resolver, false), "abc");
+        assert_eq!(
+            normalize_attribute_value("  abc ", resolver, false).unwrap(),
+            "abc"
+        );
     }
 
     struct TestResolver {
         entity_map: HashMap<String, String>,
+        limits: EntityLimits,
     }
 
     impl EntityResolver for TestResolver {
-        fn resolve(&self, entity: &str) -> Option<String> {
-            self.entity_map.get(entity).cloned()
+        fn resolve(&self, entity: &str) -> Result<Option<String>> {
+            Ok(self.entity_map.get(entity).cloned())
+        }
+
+        fn entity_limits(&self) -> EntityLimits {
+            self.limits
         }
     }
 
@@ -559,6 +777,7 @@ mod tests {
         pub(crate) fn new() -> Self {
             let mut new_self = Self {
                 entity_map: Default::default(),
+                limits: Default::default(),
             };
             let _safe_to_ignore = new_self
                 .entity_map
@@ -587,16 +806,121 @@ mod tests {
         let resolver = test_resolver();
         let resolver = resolver.borrow();
         assert_eq!(
-            normalize_attribute_value("10$ in &pound;s please", resolver, true),
+            normalize_attribute_value("10$ in &pound;s please", resolver, true).unwrap(),
             "10$ in £s please"
         );
         assert_eq!(
-            normalize_attribute_value("&yen; to &euro;", resolver, false),
+            normalize_attribute_value("&yen; to &euro;", resolver, false).unwrap(),
             "¥ to €"
         );
         assert_eq!(
-            normalize_attribute_value("&currency;", resolver, false),
+            normalize_attribute_value("&currency;", resolver, false).unwrap(),
             "$, £, €, and ¥"
         );
+        assert_eq!(
+            normalize_attribute_value("50%off;", resolver, true).unwrap(),
+            "50%off;"
+        );
+    }
+
+    #[test]
+    fn test_normalize_avalue_errors() {
+        let mut resolver = TestResolver::new();
+        let _safe_to_ignore = resolver
+            .entity_map
+            .insert("&loop;".to_string(), "a &loop;".to_string());
+        assert_eq!(
+            normalize_attribute_value("&dollar;", &resolver, true),
+            Err(Error::NotFound)
+        );
+        assert_eq!(
+            normalize_attribute_value("&loop;", &resolver, true),
+            Err(Error::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_normalize_avalue_limits() {
+        let mut resolver = TestResolver::new();
+        resolver.limits.max_depth = 2;
+        assert!(normalize_attribute_value("&currency;", &resolver, true).is_ok());
+        resolver.limits.max_depth = 1;
+        assert_eq!(
+            normalize_attribute_value("&currency;", &resolver, true),
+            Err(Error::StringSize)
+        );
+        assert!(normalize_attribute_value("&pound;&pound;", &resolver, true).is_ok());
+
+        // The replacement text of currency, pound, euro, and yen is 29 + 2 + 3 + 2 bytes, and an
+        // entity expanded a second time is charged again.
+        resolver.limits.max_depth = 2;
+        resolver.limits.max_expansion = 72;
+        assert_eq!(
+            normalize_attribute_value("&currency; &currency;", &resolver, true),
+            Ok("$, £, €, and ¥ $, £, €, and ¥".to_string())
+        );
+        resolver.limits.max_expansion = 71;
+        assert_eq!(
+            normalize_attribute_value("&currency; &currency;", &resolver, true),
+            Err(Error::StringSize)
+        );
+        resolver.limits.max_depth = 1;
+        resolver.limits.max_expansion = 72;
+        assert_eq!(
+            normalize_attribute_value("&pound; &currency;", &resolver, true),
+            Err(Error::StringSize)
+        );
+    }
+
+    #[test]
+    fn test_normalize_avalue_predefined() {
+        let resolver = TestResolver::new();
+        for (value, expected) in [
+            ("x &amp; y", "x & y"),
+            ("&lt;a&gt;", "<a>"),
+            ("it&apos;s", "it's"),
+            ("&quot;quoted&quot;", "\"quoted\""),
+            ("&amp;lt;", "&lt;"),
+            ("&amp;&amp;", "&&"),
+        ] {
+            assert_eq!(
+                normalize_attribute_value(value, &resolver, true),
+                Ok(expected.to_string())
+            );
+        }
+        assert_eq!(
+            normalize_attribute_value(" &lt; ", &resolver, false),
+            Ok("<".to_string())
+        );
+    }
+
+    #[test]
+    fn test_xml_chars() {
+        for c in [
+            '\t',
+            '\n',
+            'a',
+            '\u{85}',
+            '\u{FFFD}',
+            '\u{10000}',
+            '\u{10FFFF}',
+        ] {
+            assert!(is_xml_char(c, XmlVersion::V10), "{:?}", c);
+            assert!(is_xml_char(c, XmlVersion::V11), "{:?}", c);
+        }
+        for c in ['\u{0}', '\u{FFFE}', '\u{FFFF}'] {
+            assert!(!is_xml_char(c, XmlVersion::V10), "{:?}", c);
+            assert!(!is_xml_char(c, XmlVersion::V11), "{:?}", c);
+        }
+        assert!(!is_xml_char('\u{1}', XmlVersion::V10));
+        assert!(is_xml_char('\u{1}', XmlVersion::V11));
+        assert!(is_xml_literal_char('\u{7F}', XmlVersion::V10));
+        assert!(!is_xml_literal_char('\u{7F}', XmlVersion::V11));
+        assert!(!is_xml_literal_char('\u{1}', XmlVersion::V11));
+        assert!(is_xml_literal_char('\u{85}', XmlVersion::V11));
+
+        assert!(is_xml_chars("text", XmlVersion::V10));
+        assert!(!is_xml_chars("te\u{1}xt", XmlVersion::V10));
+        assert!(is_xml_chars("te\u{1}xt", XmlVersion::V11));
     }
 }
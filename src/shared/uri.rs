@@ -0,0 +1,307 @@
+/*!
+Resolution of URI references, following [RFC 3986](https://www.rfc-editor.org/rfc/rfc3986)
+§5.2 _Relative Resolution_, as required by XML Base.
+
+A base that is itself relative, such as a file path, is resolved in the same way; a `..` segment
+that would leave such a base is kept rather than discarded.
+*/
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The components of a URI reference, RFC 3986 §3.
+///
+#[derive(Debug, Default)]
+struct Components<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+const SCHEME_END: char = ':';
+const AUTHORITY_START: &str = "//";
+const PATH_SEPARATOR: char = '/';
+const QUERY_START: char = '?';
+const FRAGMENT_START: char = '#';
+const CURRENT_SEGMENT: &str = ".";
+const PARENT_SEGMENT: &str = "..";
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Returns `reference` resolved against `base`, RFC 3986 §5.2.2.
+///
+pub(crate) fn resolve_reference(base: &str, reference: &str) -> String {
+    let base = Components::parse(base);
+    let reference = Components::parse(reference);
+    if reference.scheme.is_some() {
+        Components {
+            path: "",
+            ..reference
+        }
+        .with_path(remove_dot_segments(reference.path))
+    } else if reference.authority.is_some() {
+        Components {
+            scheme: base.scheme,
+            path: "",
+            ..reference
+        }
+        .with_path(remove_dot_segments(reference.path))
+    } else {
+        let (path, query) = if reference.path.is_empty() {
+            (base.path.to_string(), reference.query.or(base.query))
+        } else if reference.path.starts_with(PATH_SEPARATOR) {
+            (remove_dot_segments(reference.path), reference.query)
+        } else {
+            (
+                remove_dot_segments(&merge(&base, reference.path)),
+                reference.query,
+            )
+        };
+        Components {
+            scheme: base.scheme,
+            authority: base.authority,
+            path: "",
+            query,
+            fragment: reference.fragment,
+        }
+        .with_path(path)
+    }
+}
+
+///
+/// Returns `true` if `uri` starts with a scheme; a single letter is taken to be a drive letter
+/// rather than a scheme.
+///
+pub(crate) fn has_scheme(uri: &str) -> bool {
+    match uri.find(SCHEME_END) {
+        Some(end) if end > 1 => {
+            uri.starts_with(|c: char| c.is_ascii_alphabetic())
+                && uri[..end]
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<'a> Components<'a> {
+    fn parse(uri: &'a str) -> Self {
+        let (rest, fragment) = match uri.split_once(FRAGMENT_START) {
+            None => (uri, None),
+            Some((rest, fragment)) => (rest, Some(fragment)),
+        };
+        let (rest, query) = match rest.split_once(QUERY_START) {
+            None => (rest, None),
+            Some((rest, query)) => (rest, Some(query)),
+        };
+        let (scheme, rest) = if has_scheme(rest) {
+            let (scheme, rest) = rest.split_once(SCHEME_END).unwrap();
+            (Some(scheme), rest)
+        } else {
+            (None, rest)
+        };
+        let (authority, path) = match rest.strip_prefix(AUTHORITY_START) {
+            None => (None, rest),
+            Some(rest) => {
+                let end = rest.find(PATH_SEPARATOR).unwrap_or(rest.len());
+                (Some(&rest[..end]), &rest[end..])
+            }
+        };
+        Self {
+            scheme,
+            authority,
+            path,
+            query,
+            fragment,
+        }
+    }
+
+    ///
+    /// Recompose the components with `path`, RFC 3986 §5.3.
+    ///
+    fn with_path(self, path: String) -> String {
+        let mut uri = String::new();
+        if let Some(scheme) = self.scheme {
+            uri.push_str(scheme);
+            uri.push(SCHEME_END);
+        }
+        if let Some(authority) = self.authority {
+            uri.push_str(AUTHORITY_START);
+            uri.push_str(authority);
+        }
+        uri.push_str(&path);
+        if let Some(query) = self.query {
+            uri.push(QUERY_START);
+            uri.push_str(query);
+        }
+        if let Some(fragment) = self.fragment {
+            uri.push(FRAGMENT_START);
+            uri.push_str(fragment);
+        }
+        uri
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// RFC 3986 §5.2.3.
+///
+fn merge(base: &Components<'_>, path: &str) -> String {
+    if base.authority.is_some() && base.path.is_empty() {
+        format!("{}{}", PATH_SEPARATOR, path)
+    } else {
+        match base.path.rfind(PATH_SEPARATOR) {
+            None => path.to_string(),
+            Some(end) => format!("{}{}", &base.path[..=end], path),
+        }
+    }
+}
+
+///
+/// RFC 3986 §5.2.4, except that a relative path keeps any `..` segments that cannot be removed.
+///
+fn remove_dot_segments(path: &str) -> String {
+    let is_absolute = path.starts_with(PATH_SEPARATOR);
+    let segments: Vec<&str> = if is_absolute {
+        path[1..].split(PATH_SEPARATOR).collect()
+    } else {
+        path.split(PATH_SEPARATOR).collect()
+    };
+    let last = segments.len() - 1;
+    let mut output: Vec<&str> = Default::default();
+    for (index, segment) in segments.into_iter().enumerate() {
+        match segment {
+            CURRENT_SEGMENT => {}
+            PARENT_SEGMENT => {
+                if output.last().is_some_and(|last| *last != PARENT_SEGMENT) {
+                    let _safe_to_ignore = output.pop();
+                } else if !is_absolute {
+                    output.push(PARENT_SEGMENT);
+                }
+            }
+            segment => output.push(segment),
+        }
+        //
+        // A path ending in a dot segment refers to a directory, and so ends with a separator.
+        //
+        if index == last && (segment == CURRENT_SEGMENT || segment == PARENT_SEGMENT) {
+            output.push("");
+        }
+    }
+    let path = output.join("/");
+    if is_absolute {
+        format!("{}{}", PATH_SEPARATOR, path)
+    } else {
+        path
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "http://a/b/c/d;p?q";
+
+    #[test]
+    fn test_normal_examples() {
+        // RFC 3986 §5.4.1, except that the single letter scheme of "g:h" would be a drive letter.
+        for (reference, expected) in [
+            ("gg:h", "gg:h"),
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g#s", "http://a/b/c/g#s"),
+            ("g?y#s", "http://a/b/c/g?y#s"),
+            (";x", "http://a/b/c/;x"),
+            ("g;x", "http://a/b/c/g;x"),
+            ("g;x?y#s", "http://a/b/c/g;x?y#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("./", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../", "http://a/"),
+            ("../../g", "http://a/g"),
+        ] {
+            assert_eq!(
+                resolve_reference(BASE, reference),
+                expected,
+                "{}",
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn test_abnormal_examples() {
+        // RFC 3986 §5.4.2
+        for (reference, expected) in [
+            ("../../../g", "http://a/g"),
+            ("../../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("/../g", "http://a/g"),
+            ("g.", "http://a/b/c/g."),
+            (".g", "http://a/b/c/.g"),
+            ("g..", "http://a/b/c/g.."),
+            ("..g", "http://a/b/c/..g"),
+            ("./../g", "http://a/b/g"),
+            ("./g/.", "http://a/b/c/g/"),
+            ("g/./h", "http://a/b/c/g/h"),
+            ("g/../h", "http://a/b/c/h"),
+            ("g;x=1/./y", "http://a/b/c/g;x=1/y"),
+            ("g;x=1/../y", "http://a/b/c/y"),
+            ("g?y/./x", "http://a/b/c/g?y/./x"),
+            ("g#s/../x", "http://a/b/c/g#s/../x"),
+        ] {
+            assert_eq!(
+                resolve_reference(BASE, reference),
+                expected,
+                "{}",
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn test_relative_base() {
+        assert_eq!(resolve_reference("docs/book.xml", "a.dtd"), "docs/a.dtd");
+        assert_eq!(resolve_reference("book.xml", "a.dtd"), "a.dtd");
+        assert_eq!(
+            resolve_reference("docs/book.xml", "../../a.dtd"),
+            "../a.dtd"
+        );
+        assert_eq!(
+            resolve_reference("file:///docs/book.xml", "../dtd/a.dtd"),
+            "file:///dtd/a.dtd"
+        );
+        assert_eq!(
+            resolve_reference("C:/docs/book.xml", "a.dtd"),
+            "C:/docs/a.dtd"
+        );
+    }
+}
@@ -0,0 +1,656 @@
+/*!
+[Canonical XML](https://www.w3.org/TR/xml-c14n11/) serialization, versions 1.0 and 1.1, with or
+without comments, of a node or of a subset of the nodes beneath it.
+
+The canonical form of a document is written as UTF-8 without an XML declaration or document type
+declaration; line breaks are written as `#xA`, and the only whitespace outside the document
+element is a line break between it and each comment or processing instruction. Empty elements
+are written as a start and end tag, attributes are quoted with `"`, entity references and CDATA
+sections are replaced by their content, and character data and attribute values are escaped as
+the specification requires.
+
+Namespace declarations are written on an element only where they differ from those in effect on
+its nearest written ancestor, so that superfluous declarations are removed, and are sorted by
+prefix ahead of the other attributes, which are sorted by namespace URI and then local name. The
+namespaces in scope are those declared by `xmlns` attributes and those used by the names of
+elements and attributes. When the parent of a written element is not written, as for the node
+passed to [`canonicalize`](fn.canonicalize.html) if it is not a document, the element is given the
+attributes in the `xml` namespace of the omitted ancestors; version 1.1 only inherits `xml:lang`
+and `xml:space`, and joins the `xml:base` values of the omitted ancestors with that of the
+element.
+
+A node subset is described by a predicate, see [`canonicalize_subset`](fn.canonicalize_subset.html);
+an element that is not in the subset is omitted, but its children are still considered. The
+namespace declarations of an element are written if the element is in the subset.
+
+# Example
+
+```rust
+use xml_dom::parser::read_xml;
+use xml_dom::writer::c14n::{canonicalize, Canonicalization};
+
+let document_node = read_xml(concat!(
+    "<?xml version=\"1.0\"?>",
+    "<doc xmlns:b=\"urn:b\" xmlns:a=\"urn:a\" z=\"1\" a:y=\"2\" b:x=\"3\"><e/><!--note--></doc>"
+))
+.unwrap();
+
+let mut output: Vec<u8> = Vec::new();
+canonicalize(&document_node, &mut output, Canonicalization::C14n10).unwrap();
+assert_eq!(
+    String::from_utf8(output).unwrap(),
+    concat!(
+        "<doc xmlns:a=\"urn:a\" xmlns:b=\"urn:b\" z=\"1\" a:y=\"2\" b:x=\"3\">",
+        "<e></e></doc>"
+    )
+);
+```
+
+*/
+
+use crate::level2::convert::{as_attribute, as_element, as_processing_instruction};
+use crate::level2::*;
+use crate::shared::syntax::*;
+use crate::shared::text::{normalize_attribute_value, predefined_entity, EntityResolver};
+use crate::shared::uri::resolve_reference;
+use crate::writer::{Encoding, LineEnding, Output, Result};
+use std::collections::HashMap;
+use std::fmt::{Result as FmtResult, Write as FmtWrite};
+use std::io::Write;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The canonicalization algorithms supported.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Canonicalization {
+    /// [Canonical XML Version 1.0](https://www.w3.org/TR/2001/REC-xml-c14n-20010315), omitting
+    /// comments.
+    C14n10,
+    /// Canonical XML Version 1.0, with comments.
+    C14n10WithComments,
+    /// [Canonical XML Version 1.1](https://www.w3.org/TR/xml-c14n11/), omitting comments.
+    C14n11,
+    /// Canonical XML Version 1.1, with comments.
+    C14n11WithComments,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Write the canonical form of `node`, and its descendants, to `writer`; the writer is flushed
+/// once the node has been written.
+///
+pub fn canonicalize<W: Write>(
+    node: &RefNode,
+    writer: W,
+    canonicalization: Canonicalization,
+) -> Result<()> {
+    canonicalize_subset(node, |_| true, writer, canonicalization)
+}
+
+///
+/// Write the canonical form of the nodes beneath, and including, `node` for which `include`
+/// returns `true` to `writer`; the writer is flushed once the nodes have been written. The
+/// predicate is called for elements, attributes, text, comments, and processing instructions.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::{Node, NodeType};
+/// use xml_dom::parser::read_xml;
+/// use xml_dom::writer::c14n::{canonicalize_subset, Canonicalization};
+///
+/// let document_node = read_xml(
+///     "<doc xmlns=\"urn:d\" xml:lang=\"en\"><head id=\"1\"/><body><p>text</p></body></doc>"
+/// )
+/// .unwrap();
+///
+/// // Only the "body" element and its content.
+/// let in_body = |node: &xml_dom::level2::RefNode| {
+///     let mut current = Some(node.clone());
+///     while let Some(node) = current {
+///         if node.node_type() == NodeType::Element && node.local_name() == "body" {
+///             return true;
+///         }
+///         current = node.parent_node();
+///     }
+///     false
+/// };
+/// let mut output: Vec<u8> = Vec::new();
+/// canonicalize_subset(&document_node, in_body, &mut output, Canonicalization::C14n10).unwrap();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "<body xmlns=\"urn:d\" xml:lang=\"en\"><p>text</p></body>"
+/// );
+/// ```
+///
+pub fn canonicalize_subset<W: Write, F: Fn(&RefNode) -> bool>(
+    node: &RefNode,
+    include: F,
+    writer: W,
+    canonicalization: Canonicalization,
+) -> Result<()> {
+    let mut output = Output::new(writer, Encoding::Utf8, LineEnding::Lf);
+    let result = Canonicalizer {
+        f: &mut output,
+        include: &include,
+        canonicalization,
+    }
+    .apex(node);
+    output.check(result)?;
+    Ok(output.flush()?)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+struct Canonicalizer<'a, W: FmtWrite> {
+    f: &'a mut W,
+    include: &'a dyn Fn(&RefNode) -> bool,
+    canonicalization: Canonicalization,
+}
+
+///
+/// Namespace URIs by prefix, the default namespace has the empty prefix.
+///
+type Namespaces = HashMap<String, String>;
+
+///
+/// An attribute to write: namespace URI, local name, qualified name, and value.
+///
+type Attribute = (String, String, String, String);
+
+const XML_ATTR_BASE: &str = "base";
+const XML_ATTR_LANG: &str = "lang";
+const XML_ATTR_SPACE: &str = "space";
+
+static EMPTY_PREFIX: String = String::new();
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Canonicalization {
+    ///
+    /// The identifier of this algorithm, as used in XML Signature.
+    ///
+    pub fn uri(&self) -> &'static str {
+        match self {
+            Canonicalization::C14n10 => "http://www.w3.org/TR/2001/REC-xml-c14n-20010315",
+            Canonicalization::C14n10WithComments => {
+                "http://www.w3.org/TR/2001/REC-xml-c14n-20010315#WithComments"
+            }
+            Canonicalization::C14n11 => "http://www.w3.org/2006/12/xml-c14n11",
+            Canonicalization::C14n11WithComments => {
+                "http://www.w3.org/2006/12/xml-c14n11#WithComments"
+            }
+        }
+    }
+
+    ///
+    /// Returns `true` if comments are written.
+    ///
+    pub fn with_comments(&self) -> bool {
+        matches!(
+            self,
+            Canonicalization::C14n10WithComments | Canonicalization::C14n11WithComments
+        )
+    }
+
+    fn is_version_11(&self) -> bool {
+        matches!(
+            self,
+            Canonicalization::C14n11 | Canonicalization::C14n11WithComments
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<W: FmtWrite> Canonicalizer<'_, W> {
+    ///
+    /// The node passed to `canonicalize`; the namespaces in scope, and attributes in the `xml`
+    /// namespace, of an element are taken from its ancestors.
+    ///
+    fn apex(&mut self, node: &RefNode) -> FmtResult {
+        match node.node_type() {
+            NodeType::Document => self.document(node),
+            NodeType::Element => {
+                let mut ancestors = Vec::new();
+                let mut current = node.parent_node();
+                while let Some(ancestor) = current {
+                    if ancestor.node_type() != NodeType::Element {
+                        break;
+                    }
+                    current = ancestor.parent_node();
+                    ancestors.insert(0, ancestor);
+                }
+                let mut in_scope = Namespaces::new();
+                for ancestor in &ancestors {
+                    declare_namespaces(ancestor, &mut in_scope);
+                }
+                self.element(node, &in_scope, &Namespaces::new(), &ancestors)
+            }
+            NodeType::Attribute => {
+                if (self.include)(node) {
+                    let attribute = attribute_of(node);
+                    self.attribute(&attribute.2, &attribute.3)
+                } else {
+                    Ok(())
+                }
+            }
+            NodeType::DocumentType | NodeType::Entity | NodeType::Notation => Ok(()),
+            _ => self.content(node, &Namespaces::new(), &Namespaces::new(), &[]),
+        }
+    }
+
+    ///
+    /// Comments and processing instructions outside the document element are separated from it
+    /// by a line break.
+    ///
+    fn document(&mut self, document: &RefNode) -> FmtResult {
+        let mut after_element = false;
+        for child in document.child_nodes() {
+            match child.node_type() {
+                NodeType::Element => {
+                    self.element(&child, &Namespaces::new(), &Namespaces::new(), &[])?;
+                    after_element = true;
+                }
+                NodeType::Comment | NodeType::ProcessingInstruction if self.is_written(&child) => {
+                    if after_element {
+                        self.f.write_char('\n')?;
+                    }
+                    self.content(&child, &Namespaces::new(), &Namespaces::new(), &[])?;
+                    if !after_element {
+                        self.f.write_char('\n')?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// `in_scope` are the namespaces in scope for the parent of `node`, `rendered` those declared
+    /// in the output, and `omitted` the ancestors of `node` not written since the last that was.
+    ///
+    fn element(
+        &mut self,
+        node: &RefNode,
+        in_scope: &Namespaces,
+        rendered: &Namespaces,
+        omitted: &[RefNode],
+    ) -> FmtResult {
+        let mut in_scope = in_scope.clone();
+        declare_namespaces(node, &mut in_scope);
+        let element = as_element(node).unwrap();
+        if !(self.include)(node) {
+            let mut omitted = omitted.to_vec();
+            omitted.push(node.clone());
+            for child in element.child_nodes() {
+                self.content(&child, &in_scope, rendered, &omitted)?;
+            }
+            return Ok(());
+        }
+
+        let name = element.node_name().to_string();
+        write!(self.f, "{}{}", XML_ELEMENT_START_START, name)?;
+
+        let mut rendered = rendered.clone();
+        let mut prefixes: Vec<&String> = in_scope
+            .keys()
+            .filter(|prefix| *prefix != XML_NS_ATTRIBUTE)
+            .collect();
+        if !in_scope.contains_key("") {
+            prefixes.push(&EMPTY_PREFIX);
+        }
+        prefixes.sort();
+        for prefix in prefixes {
+            let namespace_uri = in_scope.get(prefix).map(String::as_str).unwrap_or_default();
+            let output_uri = rendered.get(prefix).map(String::as_str);
+            if prefix.is_empty() {
+                if namespace_uri != output_uri.unwrap_or_default() {
+                    self.attribute(XMLNS_NS_ATTRIBUTE, namespace_uri)?;
+                }
+            } else if !namespace_uri.is_empty() && output_uri != Some(namespace_uri) {
+                self.attribute(
+                    &format!("{}{}{}", XMLNS_NS_ATTRIBUTE, XML_NS_SEPARATOR, prefix),
+                    namespace_uri,
+                )?;
+            }
+            let _safe_to_ignore = rendered.insert(prefix.clone(), namespace_uri.to_string());
+        }
+
+        let mut attributes: Vec<Attribute> = element
+            .attributes()
+            .values()
+            .filter(|attribute| {
+                !attribute.node_name().is_namespace_attribute() && (self.include)(attribute)
+            })
+            .map(attribute_of)
+            .collect();
+        if !omitted.is_empty() {
+            self.inherit_xml_attributes(&mut attributes, omitted);
+        }
+        attributes.sort_by(|lhs, rhs| (&lhs.0, &lhs.1).cmp(&(&rhs.0, &rhs.1)));
+        for (_, _, name, value) in &attributes {
+            self.attribute(name, value)?;
+        }
+        self.f.write_str(XML_ELEMENT_START_END)?;
+
+        for child in element.child_nodes() {
+            self.content(&child, &in_scope, &rendered, &[])?;
+        }
+        write!(
+            self.f,
+            "{}{}{}",
+            XML_ELEMENT_END_START, name, XML_ELEMENT_END_END
+        )
+    }
+
+    fn content(
+        &mut self,
+        node: &RefNode,
+        in_scope: &Namespaces,
+        rendered: &Namespaces,
+        omitted: &[RefNode],
+    ) -> FmtResult {
+        match node.node_type() {
+            NodeType::Element => self.element(node, in_scope, rendered, omitted),
+            NodeType::Text | NodeType::CData if self.is_written(node) => {
+                self.text(&node.node_value().unwrap_or_default())
+            }
+            NodeType::Comment if self.is_written(node) => write!(
+                self.f,
+                "{}{}{}",
+                XML_COMMENT_START,
+                node.node_value().unwrap_or_default(),
+                XML_COMMENT_END
+            ),
+            NodeType::ProcessingInstruction if self.is_written(node) => {
+                let pi = as_processing_instruction(node).unwrap();
+                match pi.data() {
+                    Some(data) if !data.is_empty() => {
+                        write!(
+                            self.f,
+                            "{}{} {}{}",
+                            XML_PI_START,
+                            pi.target(),
+                            data,
+                            XML_PI_END
+                        )
+                    }
+                    _ => write!(self.f, "{}{}{}", XML_PI_START, pi.target(), XML_PI_END),
+                }
+            }
+            NodeType::EntityReference | NodeType::DocumentFragment => {
+                for child in node.child_nodes() {
+                    self.content(&child, in_scope, rendered, omitted)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    ///
+    /// An element whose parent is not written has the attributes in the `xml` namespace of its
+    /// omitted ancestors, unless it has its own; version 1.1 instead joins the values of
+    /// `xml:base`, and does not inherit `xml:id`.
+    ///
+    fn inherit_xml_attributes(&self, attributes: &mut Vec<Attribute>, omitted: &[RefNode]) {
+        let version_11 = self.canonicalization.is_version_11();
+        let mut bases: Vec<String> = Vec::new();
+        for ancestor in omitted.iter().rev() {
+            for attribute in as_element(ancestor).unwrap().attributes().values() {
+                if !is_xml_attribute(attribute) {
+                    continue;
+                }
+                let inherited = attribute_of(attribute);
+                if version_11 && inherited.1 == XML_ATTR_BASE {
+                    bases.insert(0, inherited.3);
+                } else if (!version_11
+                    || inherited.1 == XML_ATTR_LANG
+                    || inherited.1 == XML_ATTR_SPACE)
+                    && !attributes
+                        .iter()
+                        .any(|existing| existing.0 == XML_NS_URI && existing.1 == inherited.1)
+                {
+                    attributes.push(inherited);
+                }
+            }
+        }
+        if let Some(first) = bases.first() {
+            let mut base = bases[1..].iter().fold(first.clone(), |base, reference| {
+                resolve_reference(&base, reference)
+            });
+            match attributes
+                .iter_mut()
+                .find(|existing| existing.0 == XML_NS_URI && existing.1 == XML_ATTR_BASE)
+            {
+                Some(existing) => {
+                    base = resolve_reference(&base, &existing.3);
+                    existing.3 = base;
+                }
+                None => attributes.push((
+                    XML_NS_URI.to_string(),
+                    XML_ATTR_BASE.to_string(),
+                    format!("{}{}{}", XML_NS_ATTRIBUTE, XML_NS_SEPARATOR, XML_ATTR_BASE),
+                    base,
+                )),
+            }
+        }
+    }
+
+    fn is_written(&self, node: &RefNode) -> bool {
+        (node.node_type() != NodeType::Comment || self.canonicalization.with_comments())
+            && (self.include)(node)
+    }
+
+    fn attribute(&mut self, name: &str, value: &str) -> FmtResult {
+        write!(self.f, " {}=\"", name)?;
+        for c in value.chars() {
+            match c {
+                XML_ESC_AMP_CHAR => self.f.write_str("&amp;")?,
+                XML_ESC_LT_CHAR => self.f.write_str("&lt;")?,
+                XML_ESC_QUOT_CHAR => self.f.write_str("&quot;")?,
+                '\t' => self.f.write_str("&#x9;")?,
+                '\n' => self.f.write_str("&#xA;")?,
+                '\r' => self.f.write_str("&#xD;")?,
+                c => self.f.write_char(c)?,
+            }
+        }
+        self.f.write_char('"')
+    }
+
+    fn text(&mut self, data: &str) -> FmtResult {
+        for c in data.chars() {
+            match c {
+                XML_ESC_AMP_CHAR => self.f.write_str("&amp;")?,
+                XML_ESC_LT_CHAR => self.f.write_str("&lt;")?,
+                XML_ESC_GT_CHAR => self.f.write_str("&gt;")?,
+                '\r' => self.f.write_str("&#xD;")?,
+                c => self.f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Add the namespaces declared by `element`, by `xmlns` attributes or by the names of the
+/// element and its attributes, to `in_scope`.
+///
+fn declare_namespaces(element: &RefNode, in_scope: &mut Namespaces) {
+    let mut used = vec![element.node_name()];
+    for (name, attribute) in &as_element(element).unwrap().attributes() {
+        if name.is_namespace_attribute() {
+            let prefix = match name.prefix() {
+                None => String::new(),
+                Some(_) => name.local_name().clone(),
+            };
+            let _safe_to_ignore = in_scope.insert(prefix, attribute_of(attribute).3);
+        } else {
+            used.push(name.clone());
+        }
+    }
+    for name in used {
+        if let Some(namespace_uri) = name.namespace_uri() {
+            let prefix = name.prefix().clone().unwrap_or_default();
+            if prefix != XML_NS_ATTRIBUTE && in_scope.get(&prefix) != Some(namespace_uri) {
+                let _safe_to_ignore = in_scope.insert(prefix, namespace_uri.clone());
+            }
+        }
+    }
+}
+
+fn is_xml_attribute(attribute: &RefNode) -> bool {
+    let name = attribute.node_name();
+    name.namespace_uri().as_deref() == Some(XML_NS_URI)
+        || name.prefix().as_deref() == Some(XML_NS_ATTRIBUTE)
+}
+
+///
+/// The name and value of an attribute, entity references in the value are replaced by their
+/// content, as are the character references and references to the predefined entities that
+/// are retained in attribute values.
+///
+fn attribute_of(attribute: &RefNode) -> Attribute {
+    let name = attribute.node_name();
+    let namespace_uri = if is_xml_attribute(attribute) {
+        XML_NS_URI.to_string()
+    } else {
+        name.namespace_uri().clone().unwrap_or_default()
+    };
+    let mut value = String::new();
+    for child in as_attribute(attribute).unwrap().child_nodes() {
+        if child.node_type() == NodeType::EntityReference {
+            append_text_content(&child, &mut value);
+        } else {
+            value.push_str(&replace_references(
+                &child.node_value().unwrap_or_default(),
+                attribute,
+            ));
+        }
+    }
+    (
+        namespace_uri,
+        name.local_name().clone(),
+        name.to_string(),
+        value,
+    )
+}
+
+fn append_text_content(node: &RefNode, text: &mut String) {
+    for child in node.child_nodes() {
+        match child.node_type() {
+            NodeType::Text | NodeType::CData => {
+                text.push_str(&child.node_value().unwrap_or_default())
+            }
+            NodeType::EntityReference => append_text_content(&child, text),
+            _ => {}
+        }
+    }
+}
+
+///
+/// Replace the character and entity references retained in an attribute value. The replacement
+/// text of a general entity, declared in the document's `DocumentType`, is normalized as described
+/// in [§3.3.3 Attribute-Value Normalization](https://www.w3.org/TR/xml/#AVNormalize); a reference
+/// that cannot be replaced is kept as it is.
+///
+fn replace_references(value: &str, resolver: &dyn EntityResolver) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find(XML_ENTITYREF_START) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let replacement = rest.find(XML_ENTITYREF_END).and_then(|end| {
+            let reference = &rest[XML_ENTITYREF_START.len()..end];
+            let replacement = if let Some(digits) = reference.strip_prefix("#x") {
+                u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(String::from)
+            } else if let Some(digits) = reference.strip_prefix('#') {
+                digits
+                    .parse::<u32>()
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(String::from)
+            } else if let Some(c) = predefined_entity(reference) {
+                Some(String::from(c))
+            } else {
+                resolver
+                    .resolve(&rest[..end + XML_ENTITYREF_END.len()])
+                    .ok()
+                    .flatten()
+                    .and_then(|text| normalize_attribute_value(text, resolver, true).ok())
+            };
+            replacement.map(|replacement| (replacement, end + XML_ENTITYREF_END.len()))
+        });
+        match replacement {
+            Some((replacement, length)) => {
+                result.push_str(&replacement);
+                rest = &rest[length..];
+            }
+            None => {
+                result.push_str(XML_ENTITYREF_START);
+                rest = &rest[XML_ENTITYREF_START.len()..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Entities;
+
+    impl EntityResolver for Entities {
+        fn resolve(&self, entity: &str) -> crate::shared::error::Result<Option<String>> {
+            Ok(match entity {
+                "&tab;" => Some("a\tb&#9;c".to_string()),
+                "&outer;" => Some("[&tab;]".to_string()),
+                _ => None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_replace_references() {
+        assert_eq!(replace_references("plain", &Entities), "plain");
+        assert_eq!(
+            replace_references("a&amp;b&#60;c&#x3E;", &Entities),
+            "a&b<c>"
+        );
+        assert_eq!(replace_references("AT&T", &Entities), "AT&T");
+        assert_eq!(
+            replace_references("&unknown; &#xZZ;", &Entities),
+            "&unknown; &#xZZ;"
+        );
+        assert_eq!(
+            replace_references("&tab; &outer;", &Entities),
+            "a b\tc [a b\tc]"
+        );
+    }
+}
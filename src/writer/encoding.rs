@@ -0,0 +1,177 @@
+/*!
+Encoding of serialized text into the bytes of a target character encoding.
+*/
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The character encodings that may be written, see
+/// [`WriterOptions::encoding`](struct.WriterOptions.html#method.encoding). UTF-16 output starts
+/// with a byte order mark.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, without a byte order mark; the default.
+    #[default]
+    Utf8,
+    /// UTF-16, little-endian.
+    Utf16Le,
+    /// UTF-16, big-endian.
+    Utf16Be,
+    /// ISO-8859-1 (Latin-1).
+    Iso8859_1,
+    /// ISO-8859-15 (Latin-9).
+    Iso8859_15,
+    /// US-ASCII.
+    UsAscii,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Encoding {
+    ///
+    /// The name of this encoding, as it is written in an XML declaration.
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le | Encoding::Utf16Be => "UTF-16",
+            Encoding::Iso8859_1 => "ISO-8859-1",
+            Encoding::Iso8859_15 => "ISO-8859-15",
+            Encoding::UsAscii => "US-ASCII",
+        }
+    }
+
+    ///
+    /// Returns `true` if the character `c` can be represented in this encoding.
+    ///
+    pub fn can_encode(&self, c: char) -> bool {
+        match self {
+            Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be => true,
+            Encoding::Iso8859_1 => (c as u32) < 0x100,
+            Encoding::Iso8859_15 => iso_8859_15_byte(c).is_some(),
+            Encoding::UsAscii => c.is_ascii(),
+        }
+    }
+
+    ///
+    /// The bytes written before any serialized text.
+    ///
+    pub(crate) fn byte_order_mark(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            _ => &[],
+        }
+    }
+
+    ///
+    /// Append the encoded form of `c` to `bytes`, returning `false` if it cannot be represented.
+    ///
+    pub(crate) fn encode(&self, c: char, bytes: &mut Vec<u8>) -> bool {
+        match self {
+            Encoding::Utf8 => {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let mut buffer = [0; 2];
+                for unit in c.encode_utf16(&mut buffer) {
+                    if *self == Encoding::Utf16Le {
+                        bytes.extend_from_slice(&unit.to_le_bytes());
+                    } else {
+                        bytes.extend_from_slice(&unit.to_be_bytes());
+                    }
+                }
+            }
+            Encoding::Iso8859_1 | Encoding::UsAscii if self.can_encode(c) => bytes.push(c as u8),
+            Encoding::Iso8859_15 => match iso_8859_15_byte(c) {
+                None => return false,
+                Some(byte) => bytes.push(byte),
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// ISO-8859-15 differs from ISO-8859-1 in eight positions.
+///
+fn iso_8859_15_byte(c: char) -> Option<u8> {
+    match c {
+        '\u{20AC}' => Some(0xA4),
+        '\u{0160}' => Some(0xA6),
+        '\u{0161}' => Some(0xA8),
+        '\u{017D}' => Some(0xB4),
+        '\u{017E}' => Some(0xB8),
+        '\u{0152}' => Some(0xBC),
+        '\u{0153}' => Some(0xBD),
+        '\u{0178}' => Some(0xBE),
+        '\u{A4}' | '\u{A6}' | '\u{A8}' | '\u{B4}' | '\u{B8}' | '\u{BC}' | '\u{BD}' | '\u{BE}' => {
+            None
+        }
+        c if (c as u32) < 0x100 => Some(c as u8),
+        _ => None,
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(encoding: Encoding, text: &str) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for c in text.chars() {
+            if !encoding.encode(c, &mut bytes) {
+                return None;
+            }
+        }
+        Some(bytes)
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            encoded(Encoding::Utf8, "a\u{E9}"),
+            Some(vec![0x61, 0xC3, 0xA9])
+        );
+        assert_eq!(
+            encoded(Encoding::Utf16Le, "a\u{1F600}"),
+            Some(vec![0x61, 0x00, 0x3D, 0xD8, 0x00, 0xDE])
+        );
+        assert_eq!(encoded(Encoding::Utf16Be, "a"), Some(vec![0x00, 0x61]));
+        assert_eq!(
+            encoded(Encoding::Iso8859_1, "a\u{E9}"),
+            Some(vec![0x61, 0xE9])
+        );
+        assert_eq!(encoded(Encoding::Iso8859_1, "\u{20AC}"), None);
+        assert_eq!(
+            encoded(Encoding::Iso8859_15, "\u{20AC}\u{E9}"),
+            Some(vec![0xA4, 0xE9])
+        );
+        assert_eq!(encoded(Encoding::Iso8859_15, "\u{A4}"), None);
+        assert_eq!(encoded(Encoding::UsAscii, "a"), Some(vec![0x61]));
+        assert_eq!(encoded(Encoding::UsAscii, "\u{E9}"), None);
+    }
+}
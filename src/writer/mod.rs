@@ -0,0 +1,253 @@
+/*!
+Provides a serializer that writes nodes as XML text to any `std::io::Write`, encoding the text as
+it is produced rather than building the complete text in memory first.
+
+The text written with the default [`WriterOptions`](struct.WriterOptions.html) is that of the
+`Display` implementation of `RefNode`; the options control whether an XML declaration is written,
+the character encoding, how empty elements are written, the quote character for attribute values,
+and the line ending.
+
+The output may also be indented, see [`WriterOptions::indent`](struct.WriterOptions.html#method.indent);
+line breaks and indentation are only added where whitespace is not significant, so that elements
+containing character data, or with `xml:space="preserve"` in scope, are written unchanged. Start
+tags wider than [`WriterOptions::max_line_width`](struct.WriterOptions.html#method.max_line_width)
+are wrapped, with one attribute per line.
+
+The [`c14n`](c14n/index.html) module writes the canonical form of a node, or of a subset of the
+nodes beneath it, as used when signing or hashing XML.
+
+# Example
+
+```rust
+use xml_dom::level2::get_implementation;
+use xml_dom::writer::{serialize_to, Declaration, Encoding, LineEnding, WriterOptions};
+
+let document_node = get_implementation()
+    .create_document(None, Some("config"), None)
+    .unwrap();
+
+let options = WriterOptions::new()
+    .xml_declaration(Declaration::Always)
+    .encoding(Encoding::Iso8859_1)
+    .line_ending(LineEnding::CrLf);
+let mut output: Vec<u8> = Vec::new();
+serialize_to(&document_node, &mut output, &options).unwrap();
+assert_eq!(
+    output,
+    b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><config></config>"
+);
+```
+
+*/
+
+use crate::level2::RefNode;
+use crate::shared::display::Serializer;
+use std::fmt::{Debug, Formatter, Result as FmtResult, Write as FmtWrite};
+use std::io::Write;
+use thiserror::Error as E;
+
+// ------------------------------------------------------------------------------------------------
+// Public Modules
+// ------------------------------------------------------------------------------------------------
+
+pub mod c14n;
+
+// ------------------------------------------------------------------------------------------------
+// Private Modules
+// ------------------------------------------------------------------------------------------------
+
+mod encoding;
+pub use encoding::Encoding;
+
+mod options;
+pub use options::{Declaration, EmptyElements, LineEnding, Quote, WriterOptions};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Errors writing a node.
+///
+#[derive(Debug, E)]
+pub enum Error {
+    /// A character in a name, comment, processing instruction, or CDATA section cannot be
+    /// represented in the target encoding.
+    #[error("character {0:?} cannot be written in the encoding {1}")]
+    Unencodable(char, Encoding),
+    /// Errors passed through from the underlying writer.
+    #[error("I/O error: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+///
+/// A result type where the error is always the writer `Error`.
+///
+pub type Result<T> = std::result::Result<T, Error>;
+
+///
+/// Writes nodes, one after another, to an underlying `std::io::Write`.
+///
+pub struct XmlWriter<W: Write> {
+    output: Output<W>,
+    options: WriterOptions,
+    started: bool,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Write `node`, and its descendants, to `writer` as XML text; the writer is flushed once the
+/// node has been written.
+///
+pub fn serialize_to<W: Write>(node: &RefNode, writer: W, options: &WriterOptions) -> Result<()> {
+    let mut writer = XmlWriter::new(writer, options.clone());
+    writer.write_node(node)?;
+    writer.flush()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Adapts an `io::Write` as the `fmt::Write` used by the serializer; as the serializer's errors
+/// carry no detail the actual error is kept here. The encoded output is buffered, and written to
+/// the underlying writer when the buffer is full and once each node has been written.
+///
+struct Output<W: Write> {
+    inner: W,
+    encoding: Encoding,
+    line_ending: LineEnding,
+    buffer: Vec<u8>,
+    error: Option<Error>,
+}
+
+const BUFFER_CAPACITY: usize = 8 * 1024;
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<W: Write> Debug for XmlWriter<W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("XmlWriter")
+            .field("options", &self.options)
+            .field("started", &self.started)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> XmlWriter<W> {
+    ///
+    /// Construct a new writer that writes to `writer`.
+    ///
+    pub fn new(writer: W, options: WriterOptions) -> Self {
+        Self {
+            output: Output::new(writer, options.encoding, options.line_ending),
+            options,
+            started: false,
+        }
+    }
+
+    ///
+    /// Write `node`, and its descendants; a byte order mark, if the encoding requires one, is
+    /// written before the first node.
+    ///
+    pub fn write_node(&mut self, node: &RefNode) -> Result<()> {
+        if !self.started {
+            self.started = true;
+            let byte_order_mark = self.output.encoding.byte_order_mark();
+            self.output.buffer.extend_from_slice(byte_order_mark);
+        }
+        let result = Serializer::new(&mut self.output, &self.options, Some(self.options.encoding))
+            .node(node);
+        let written = self.output.write_buffer();
+        self.output.check(result)?;
+        Ok(written?)
+    }
+
+    ///
+    /// Flush the underlying writer.
+    ///
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.output.flush()?)
+    }
+
+    ///
+    /// Return the underlying writer.
+    ///
+    pub fn into_inner(self) -> W {
+        self.output.inner
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<W: Write> Output<W> {
+    fn new(inner: W, encoding: Encoding, line_ending: LineEnding) -> Self {
+        Self {
+            inner,
+            encoding,
+            line_ending,
+            buffer: Vec::with_capacity(BUFFER_CAPACITY),
+            error: None,
+        }
+    }
+
+    ///
+    /// Write, and clear, the buffered output.
+    ///
+    fn write_buffer(&mut self) -> std::io::Result<()> {
+        let result = self.inner.write_all(&self.buffer);
+        self.buffer.clear();
+        result
+    }
+
+    ///
+    /// Write the buffered output, and flush the underlying writer.
+    ///
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_buffer()?;
+        self.inner.flush()
+    }
+
+    ///
+    /// Returns the error behind a failed `result` of writing to this output.
+    ///
+    fn check(&mut self, result: FmtResult) -> Result<()> {
+        match (result, self.error.take()) {
+            (Ok(()), _) => Ok(()),
+            (Err(_), Some(error)) => Err(error),
+            (Err(_), None) => Err(std::io::Error::other("formatter error").into()),
+        }
+    }
+}
+
+impl<W: Write> FmtWrite for Output<W> {
+    fn write_str(&mut self, s: &str) -> FmtResult {
+        if self.encoding == Encoding::Utf8 && self.line_ending == LineEnding::Lf {
+            self.buffer.extend_from_slice(s.as_bytes());
+        } else {
+            for c in s.chars() {
+                if c == '\n' {
+                    for c in self.line_ending.as_str().chars() {
+                        let _safe_to_ignore = self.encoding.encode(c, &mut self.buffer);
+                    }
+                } else if !self.encoding.encode(c, &mut self.buffer) {
+                    self.error = Some(Error::Unencodable(c, self.encoding));
+                    return Err(std::fmt::Error);
+                }
+            }
+        }
+        if self.buffer.len() >= BUFFER_CAPACITY {
+            self.write_buffer().map_err(|e| {
+                self.error = Some(e.into());
+                std::fmt::Error
+            })?;
+        }
+        Ok(())
+    }
+}
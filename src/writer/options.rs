@@ -0,0 +1,215 @@
+/*!
+Options that control the output of the writer, see [`serialize_to`](../fn.serialize_to.html).
+*/
+
+use crate::writer::Encoding;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Options that control the output of the writer. The default options produce the same text as
+/// the `Display` implementation of `RefNode`, encoded as UTF-8.
+///
+/// # Example
+///
+/// ```rust
+/// use xml_dom::level2::convert::as_document;
+/// use xml_dom::level2::get_implementation;
+/// use xml_dom::writer::{serialize_to, Declaration, EmptyElements, Quote, WriterOptions};
+///
+/// let document_node = get_implementation()
+///     .create_document(None, Some("config"), None)
+///     .unwrap();
+///
+/// let options = WriterOptions::new()
+///     .xml_declaration(Declaration::Always)
+///     .empty_elements(EmptyElements::SelfClosing)
+///     .quote(Quote::Single);
+/// let mut output: Vec<u8> = Vec::new();
+/// serialize_to(&document_node, &mut output, &options).unwrap();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "<?xml version='1.0'?><config/>"
+/// );
+/// ```
+///
+#[derive(Clone, Debug, Default)]
+pub struct WriterOptions {
+    pub(crate) xml_declaration: Declaration,
+    pub(crate) encoding: Encoding,
+    pub(crate) empty_elements: EmptyElements,
+    pub(crate) quote: Quote,
+    pub(crate) line_ending: LineEnding,
+    pub(crate) indent: Option<String>,
+    pub(crate) max_line_width: Option<usize>,
+    pub(crate) sort_attributes: bool,
+}
+
+///
+/// Whether an XML declaration is written before a document, see
+/// [`WriterOptions::xml_declaration`](struct.WriterOptions.html#method.xml_declaration).
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Declaration {
+    /// Write the document's declaration, if it has one; the default.
+    #[default]
+    AsDocument,
+    /// Always write a declaration, the document's or an XML 1.0 declaration if it has none.
+    Always,
+    /// Never write a declaration.
+    Never,
+}
+
+///
+/// How an element with no children is written, see
+/// [`WriterOptions::empty_elements`](struct.WriterOptions.html#method.empty_elements).
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyElements {
+    /// As a start tag followed by an end tag, `<a></a>`; the default.
+    #[default]
+    Expanded,
+    /// As an empty-element tag, `<a/>`.
+    SelfClosing,
+}
+
+///
+/// The character used to quote attribute values and the values in an XML declaration, see
+/// [`WriterOptions::quote`](struct.WriterOptions.html#method.quote).
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quote {
+    /// Quotation marks, `"`; the default.
+    #[default]
+    Double,
+    /// Apostrophes, `'`.
+    Single,
+}
+
+///
+/// The line ending written for each line break, see
+/// [`WriterOptions::line_ending`](struct.WriterOptions.html#method.line_ending).
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// A line feed, `\n`; the default.
+    #[default]
+    Lf,
+    /// A carriage return followed by a line feed, `\r\n`.
+    CrLf,
+    /// A carriage return, `\r`.
+    Cr,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl WriterOptions {
+    ///
+    /// Construct the default set of options.
+    ///
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    ///
+    /// Whether to write an XML declaration before a document. The default is
+    /// [`Declaration::AsDocument`](enum.Declaration.html#variant.AsDocument). A declaration that
+    /// is written names the target encoding if the document's declaration names an encoding, or
+    /// the target encoding is not UTF-8.
+    ///
+    pub fn xml_declaration(mut self, xml_declaration: Declaration) -> Self {
+        self.xml_declaration = xml_declaration;
+        self
+    }
+
+    ///
+    /// The character encoding of the output. The default is UTF-8. Characters in text and
+    /// attribute values that cannot be represented in the encoding are written as character
+    /// references; elsewhere, such as in names and comments, they are an error.
+    ///
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    ///
+    /// How to write elements with no children. The default is
+    /// [`EmptyElements::Expanded`](enum.EmptyElements.html#variant.Expanded).
+    ///
+    pub fn empty_elements(mut self, empty_elements: EmptyElements) -> Self {
+        self.empty_elements = empty_elements;
+        self
+    }
+
+    ///
+    /// The character used to quote attribute values, occurrences of this character in a value
+    /// are escaped. The default is [`Quote::Double`](enum.Quote.html#variant.Double).
+    ///
+    pub fn quote(mut self, quote: Quote) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    ///
+    /// The line ending to write for each line break in text, comments, processing instructions,
+    /// CDATA sections, and the internal subset; line breaks in attribute values are always
+    /// written as character references. The default is [`LineEnding::Lf`](enum.LineEnding.html#variant.Lf).
+    ///
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    ///
+    /// Indent the output, writing each node in element content on a line of its own, indented
+    /// by `indent` for each level of nesting, and each node outside the document element on a
+    /// line of its own. Elements that contain character data, or that have `xml:space="preserve"`
+    /// in scope, are written without change. The default is not to indent.
+    ///
+    pub fn indent(mut self, indent: &str) -> Self {
+        self.indent = Some(indent.to_string());
+        self
+    }
+
+    ///
+    /// When indenting, the width beyond which a start tag with more than one attribute is
+    /// wrapped, writing each attribute on a line of its own indented one level deeper than the
+    /// element. The default is not to wrap.
+    ///
+    pub fn max_line_width(mut self, max_line_width: usize) -> Self {
+        self.max_line_width = Some(max_line_width);
+        self
+    }
+
+    ///
+    /// Write the attributes of each element sorted by their qualified names, rather than in the
+    /// order in which they were added to the element. The default is `false`.
+    ///
+    pub fn sort_attributes(mut self, sort_attributes: bool) -> Self {
+        self.sort_attributes = sort_attributes;
+        self
+    }
+}
+
+impl Quote {
+    pub(crate) fn as_char(&self) -> char {
+        match self {
+            Quote::Double => '"',
+            Quote::Single => '\'',
+        }
+    }
+}
+
+impl LineEnding {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
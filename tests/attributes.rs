@@ -75,6 +75,51 @@ fn test_set_attribute() {
     assert_eq!(element.get_attribute("test-2").unwrap(), "2");
 }
 
+#[test]
+fn test_attribute_order() {
+    let document_node = common::create_empty_rdf_document();
+    let document = as_document(&document_node).unwrap();
+    let mut element_node = document.document_element().unwrap();
+    let element = as_element_mut(&mut element_node).unwrap();
+
+    fn names(element: &dyn Element<NodeRef = RefNode>) -> Vec<String> {
+        element
+            .attributes()
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    for name in ["zeta", "alpha", "mu", "beta"] {
+        assert!(element.set_attribute(name, name).is_ok());
+    }
+    assert_eq!(names(element), vec!["zeta", "alpha", "mu", "beta"]);
+
+    // Replacing an attribute keeps its position
+    assert!(element.set_attribute("alpha", "first").is_ok());
+    assert_eq!(names(element), vec!["zeta", "alpha", "mu", "beta"]);
+    let attribute_node = document.create_attribute_with("mu", "second").unwrap();
+    assert!(element.set_attribute_node(attribute_node).is_ok());
+    assert_eq!(names(element), vec!["zeta", "alpha", "mu", "beta"]);
+
+    // Removing an attribute keeps the order of the others
+    assert!(element.remove_attribute("alpha").is_ok());
+    assert_eq!(names(element), vec!["zeta", "mu", "beta"]);
+    assert!(element.set_attribute("alpha", "again").is_ok());
+    assert_eq!(names(element), vec!["zeta", "mu", "beta", "alpha"]);
+
+    let values: Vec<String> = element
+        .attributes()
+        .values()
+        .map(|attribute| as_attribute(attribute).unwrap().value().unwrap())
+        .collect();
+    assert_eq!(values, vec!["zeta", "second", "beta", "again"]);
+    assert_eq!(
+        element_node.to_string(),
+        "<rdf:RDF zeta=\"zeta\" mu=\"second\" beta=\"beta\" alpha=\"again\"></rdf:RDF>"
+    );
+}
+
 #[test]
 fn test_model_parent_owner() {
     //
@@ -230,16 +275,105 @@ fn test_normalization_character_entities() {
 }
 
 #[test]
-#[should_panic]
 fn test_normalization_entity_not_found() {
     let document_node = common::create_empty_rdf_document();
     let document = as_document(&document_node).unwrap();
     let mut element_node = document.document_element().unwrap();
     let element = as_element_mut(&mut element_node).unwrap();
 
-    element.set_attribute("test", "hello&pound;world").unwrap();
     assert_eq!(
-        element.get_attribute("test"),
-        Some("hello£world".to_string())
+        element.set_attribute("test", "hello&pound;world"),
+        Err(Error::NotFound)
+    );
+    assert!(element.get_attribute("test").is_none());
+    assert_eq!(
+        document
+            .create_attribute_with("test", "hello&pound;world")
+            .err(),
+        Some(Error::NotFound)
     );
+
+    let mut attribute_node = document.create_attribute("test").unwrap();
+    let attribute = as_attribute_mut(&mut attribute_node).unwrap();
+    assert_eq!(attribute.set_value("&pound;"), Err(Error::NotFound));
+}
+
+#[test]
+fn test_set_attribute_predefined_entities() {
+    let document_node = common::create_empty_rdf_document();
+    let document = as_document(&document_node).unwrap();
+    let mut element_node = document.document_element().unwrap();
+    let element = as_element_mut(&mut element_node).unwrap();
+
+    for (name, value) in [
+        ("amp", "x &amp; y"),
+        ("lt", "&lt;tag"),
+        ("gt", "tag&gt;"),
+        ("apos", "it&apos;s"),
+        ("quot", "&quot;quoted&quot;"),
+    ] {
+        assert!(
+            element.set_attribute(name, value).is_ok(),
+            "expecting {} to be accepted",
+            value
+        );
+    }
+    let value = |name: &str| {
+        let attribute = element.get_attribute_node(name).unwrap();
+        as_attribute(&attribute).unwrap().value().unwrap()
+    };
+    assert_eq!(value("amp"), "x &#38; y");
+    assert_eq!(value("lt"), "&#60;tag");
+    assert_eq!(value("gt"), "tag&#62;");
+    assert_eq!(value("apos"), "it&#39;s");
+    assert_eq!(value("quot"), "&#34;quoted&#34;");
+
+    // Other undeclared entities are still an error.
+    assert!(element.set_attribute("other", "&nbsp;").is_err());
+}
+
+#[cfg(feature = "quick_parser")]
+#[test]
+fn test_set_attribute_entity_limits() {
+    use xml_dom::parser::{read_xml, read_xml_with_options, ParserOptions};
+
+    let mut laughs = String::from("<!DOCTYPE doc [<!ENTITY l0 \"ha\">");
+    for level in 1..8 {
+        laughs.push_str(&format!(
+            "<!ENTITY l{} \"{}\">",
+            level,
+            format!("&l{};", level - 1).repeat(10)
+        ));
+    }
+    laughs.push_str("]><doc/>");
+
+    let document_node = read_xml(&laughs).unwrap();
+    let document = as_document(&document_node).unwrap();
+    let mut element_node = document.document_element().unwrap();
+    let element = as_element_mut(&mut element_node).unwrap();
+    assert_eq!(element.set_attribute("b", "&l7;"), Err(Error::StringSize));
+    assert!(element.set_attribute("b", "&l2;").is_ok());
+    assert_eq!(
+        element.get_attribute("b").map(|value| value.len()),
+        Some(200)
+    );
+
+    let document_node = read_xml_with_options(
+        &laughs,
+        ParserOptions::new()
+            .max_entity_depth(2)
+            .max_entity_expansion(1000),
+    )
+    .unwrap();
+    let document = as_document(&document_node).unwrap();
+    let mut element_node = document.document_element().unwrap();
+    let element = as_element_mut(&mut element_node).unwrap();
+    assert!(element.set_attribute("b", "&l1;").is_ok());
+    assert_eq!(element.set_attribute("b", "&l2;"), Err(Error::StringSize));
+    let document_node =
+        read_xml_with_options(&laughs, ParserOptions::new().max_entity_expansion(100)).unwrap();
+    let document = as_document(&document_node).unwrap();
+    let mut element_node = document.document_element().unwrap();
+    let element = as_element_mut(&mut element_node).unwrap();
+    assert_eq!(element.set_attribute("b", "&l2;"), Err(Error::StringSize));
 }
@@ -0,0 +1,252 @@
+use xml_dom::level2::convert::as_document;
+use xml_dom::level2::*;
+use xml_dom::parser::{read_xml, read_xml_with_options, ParserOptions};
+use xml_dom::writer::c14n::{canonicalize, canonicalize_subset, Canonicalization};
+
+pub mod common;
+
+fn canonical(node: &RefNode, canonicalization: Canonicalization) -> String {
+    let mut output: Vec<u8> = Vec::new();
+    canonicalize(node, &mut output, canonicalization).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+fn canonical_subset<F: Fn(&RefNode) -> bool>(
+    node: &RefNode,
+    include: F,
+    canonicalization: Canonicalization,
+) -> String {
+    let mut output: Vec<u8> = Vec::new();
+    canonicalize_subset(node, include, &mut output, canonicalization).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+fn has_ancestor_named(node: &RefNode, local_name: &str) -> bool {
+    let mut current = Some(node.clone());
+    while let Some(node) = current {
+        if node.node_type() == NodeType::Element && node.local_name() == local_name {
+            return true;
+        }
+        current = node.parent_node();
+    }
+    false
+}
+
+#[test]
+fn test_algorithm_uris() {
+    assert_eq!(
+        Canonicalization::C14n10.uri(),
+        "http://www.w3.org/TR/2001/REC-xml-c14n-20010315"
+    );
+    assert_eq!(
+        Canonicalization::C14n11WithComments.uri(),
+        "http://www.w3.org/2006/12/xml-c14n11#WithComments"
+    );
+    assert!(!Canonicalization::C14n11.with_comments());
+    assert!(Canonicalization::C14n10WithComments.with_comments());
+}
+
+#[test]
+fn test_document_level_nodes() {
+    let document_node = read_xml(concat!(
+        "<?xml version=\"1.0\"?>\n",
+        "<?xml-stylesheet href=\"doc.xsl\" type=\"text/xsl\"?>\n",
+        "<!DOCTYPE doc SYSTEM \"doc.dtd\">\n",
+        "<doc>Hello, world!<!-- Comment 1 --></doc>\n",
+        "<?pi-without-data?>\n",
+        "<!-- Comment 2 -->\n",
+        "<!-- Comment 3 -->"
+    ))
+    .unwrap();
+
+    assert_eq!(
+        canonical(&document_node, Canonicalization::C14n10),
+        concat!(
+            "<?xml-stylesheet href=\"doc.xsl\" type=\"text/xsl\"?>\n",
+            "<doc>Hello, world!</doc>\n",
+            "<?pi-without-data?>"
+        )
+    );
+    assert_eq!(
+        canonical(&document_node, Canonicalization::C14n10WithComments),
+        concat!(
+            "<?xml-stylesheet href=\"doc.xsl\" type=\"text/xsl\"?>\n",
+            "<doc>Hello, world!<!-- Comment 1 --></doc>\n",
+            "<?pi-without-data?>\n",
+            "<!-- Comment 2 -->\n",
+            "<!-- Comment 3 -->"
+        )
+    );
+}
+
+#[test]
+fn test_elements_and_attributes() {
+    let document_node = read_xml(concat!(
+        "<doc xmlns:b=\"http://www.ietf.org\" xmlns:a=\"http://www.w3.org\">",
+        "<e1   /><e2 ></e2>",
+        "<e3   name = \"elem3\"   id=\"elem3\"   />",
+        "<e5 a:attr=\"out\" b:attr=\"sorted\" attr2=\"all\" attr=\"I'm\" ",
+        "xmlns:b=\"http://www.ietf.org\" xmlns:a=\"http://www.w3.org\" ",
+        "xmlns=\"http://example.org\"/>",
+        "</doc>"
+    ))
+    .unwrap();
+
+    assert_eq!(
+        canonical(&document_node, Canonicalization::C14n11),
+        concat!(
+            "<doc xmlns:a=\"http://www.w3.org\" xmlns:b=\"http://www.ietf.org\">",
+            "<e1></e1><e2></e2>",
+            "<e3 id=\"elem3\" name=\"elem3\"></e3>",
+            "<e5 xmlns=\"http://example.org\" attr=\"I'm\" attr2=\"all\" ",
+            "b:attr=\"sorted\" a:attr=\"out\"></e5>",
+            "</doc>"
+        )
+    );
+}
+
+#[test]
+fn test_namespace_declarations() {
+    let document_node = read_xml(concat!(
+        "<a xmlns=\"urn:a\" xmlns:p=\"urn:p\">",
+        "<b xmlns=\"urn:a\" xmlns:p=\"urn:p\"><c xmlns=\"\"><d xmlns=\"\"/></c></b>",
+        "<p:e xmlns:p=\"urn:other\"/>",
+        "</a>"
+    ))
+    .unwrap();
+
+    assert_eq!(
+        canonical(&document_node, Canonicalization::C14n10),
+        concat!(
+            "<a xmlns=\"urn:a\" xmlns:p=\"urn:p\">",
+            "<b><c xmlns=\"\"><d></d></c></b>",
+            "<p:e xmlns:p=\"urn:other\"></p:e>",
+            "</a>"
+        )
+    );
+}
+
+#[test]
+fn test_character_escaping() {
+    let document_node = read_xml_with_options(
+        concat!(
+            "<doc attr=\"a&lt;b&gt;c&amp;d&quot;e&#x9;f&#xA;g&#xD;\">",
+            "text &amp; &lt;more&gt; &#xD; \"quoted\"",
+            "<![CDATA[ <cdata> & ]]>",
+            "</doc>"
+        ),
+        ParserOptions::default().trim_text(false),
+    )
+    .unwrap();
+
+    assert_eq!(
+        canonical(&document_node, Canonicalization::C14n10),
+        concat!(
+            "<doc attr=\"a&lt;b>c&amp;d&quot;e&#x9;f&#xA;g&#xD;\">",
+            "text &amp; &lt;more&gt; &#xD; \"quoted\"",
+            " &lt;cdata&gt; &amp; ",
+            "</doc>"
+        )
+    );
+}
+
+#[test]
+fn test_line_ends() {
+    let document_node = read_xml_with_options(
+        concat!(
+            "<doc>\r\n",
+            "a\rb\r\nc&#xD;&#xA;<![CDATA[d\r\ne]]>\r\n",
+            "</doc>"
+        ),
+        ParserOptions::default().trim_text(false),
+    )
+    .unwrap();
+
+    assert_eq!(
+        canonical(&document_node, Canonicalization::C14n10),
+        "<doc>\na\nb\nc&#xD;\nd\ne\n</doc>"
+    );
+}
+
+#[test]
+fn test_attribute_normalization() {
+    let document_node = read_xml(concat!(
+        "<!DOCTYPE doc [<!ENTITY ent \"p\tq\">]>",
+        "<doc a=\"x\ty\" b=\"x\r\ny\rz\" c=\"x&#9;y&#xA;z&#xD;\" d=\"[&ent;]\"/>"
+    ))
+    .unwrap();
+
+    assert_eq!(
+        canonical(&document_node, Canonicalization::C14n10),
+        "<doc a=\"x y\" b=\"x y z\" c=\"x&#x9;y&#xA;z&#xD;\" d=\"[p q]\"></doc>"
+    );
+}
+
+#[test]
+fn test_element_apex() {
+    let document_node = read_xml(concat!(
+        "<doc xmlns=\"urn:d\" xmlns:x=\"urn:x\" xml:lang=\"en\" xml:id=\"top\">",
+        "<x:item a=\"1\"><!--c-->text</x:item>",
+        "</doc>"
+    ))
+    .unwrap();
+    let document = as_document(&document_node).unwrap();
+    let item = document.document_element().unwrap().first_child().unwrap();
+
+    assert_eq!(
+        canonical(&item, Canonicalization::C14n10),
+        "<x:item xmlns=\"urn:d\" xmlns:x=\"urn:x\" a=\"1\" xml:id=\"top\" xml:lang=\"en\">text</x:item>"
+    );
+    assert_eq!(
+        canonical(&item, Canonicalization::C14n11WithComments),
+        "<x:item xmlns=\"urn:d\" xmlns:x=\"urn:x\" a=\"1\" xml:lang=\"en\"><!--c-->text</x:item>"
+    );
+}
+
+#[test]
+fn test_subset_xml_base() {
+    let document_node = read_xml(concat!(
+        "<doc xml:base=\"http://www.example.com/one/\" xml:space=\"preserve\">",
+        "<a xml:base=\"two/\"><b xml:base=\"three/file.xml\" attr=\"1\">text</b></a>",
+        "</doc>"
+    ))
+    .unwrap();
+    // Attributes are considered only for elements in the subset.
+    let in_b =
+        |node: &RefNode| node.node_type() == NodeType::Attribute || has_ancestor_named(node, "b");
+
+    assert_eq!(
+        canonical_subset(&document_node, in_b, Canonicalization::C14n10),
+        concat!(
+            "<b attr=\"1\" xml:base=\"three/file.xml\" xml:space=\"preserve\">",
+            "text</b>"
+        )
+    );
+    assert_eq!(
+        canonical_subset(&document_node, in_b, Canonicalization::C14n11),
+        concat!(
+            "<b attr=\"1\" xml:base=\"http://www.example.com/one/two/three/file.xml\" ",
+            "xml:space=\"preserve\">text</b>"
+        )
+    );
+}
+
+#[test]
+fn test_subset_excluding_attributes_and_elements() {
+    let document_node = read_xml(concat!(
+        "<doc xmlns:p=\"urn:p\" keep=\"1\" drop=\"2\">",
+        "<skip><p:child/></skip>",
+        "</doc>"
+    ))
+    .unwrap();
+    let include = |node: &RefNode| match node.node_type() {
+        NodeType::Element => node.local_name() != "skip",
+        NodeType::Attribute => node.local_name() != "drop",
+        _ => true,
+    };
+
+    assert_eq!(
+        canonical_subset(&document_node, include, Canonicalization::C14n10),
+        "<doc xmlns:p=\"urn:p\" keep=\"1\"><p:child></p:child></doc>"
+    );
+}
@@ -71,7 +71,9 @@ fn test_display_text() {
     let document_node = common::create_empty_rdf_document();
     let document = as_document(&document_node).unwrap();
 
-    let test_node = document.create_text_node("this is textual test data");
+    let test_node = document
+        .create_text_node("this is textual test data")
+        .unwrap();
 
     let result = format!("{}", test_node);
     assert_eq!(result, "this is textual test data");
@@ -87,7 +89,7 @@ fn test_display_cdata() {
         .unwrap();
 
     let result = format!("{}", test_node);
-    assert_eq!(result, "<![CDATA[ this is textual test data ]]>");
+    assert_eq!(result, "<![CDATA[this is textual test data]]>");
 }
 
 #[test]
@@ -124,7 +126,9 @@ fn test_display_comment() {
     let document_node = common::create_empty_rdf_document();
     let document = as_document(&document_node).unwrap();
 
-    let test_node = document.create_comment("this is textual test data");
+    let test_node = document
+        .create_comment("this is textual test data")
+        .unwrap();
 
     let result = format!("{}", test_node);
     assert_eq!(result, "<!--this is textual test data-->");
@@ -149,7 +153,7 @@ fn test_display_document() {
         .unwrap();
 
     let result = format!("{}", test_node);
-    assert_eq!(result, "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" SYSTEM \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\"><html></html>");
+    assert_eq!(result, "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\"><html></html>");
 }
 
 #[test]
@@ -176,7 +180,7 @@ fn test_display_document_with_decl() {
     assert!(result.is_ok());
 
     let result = format!("{}", test_node);
-    assert_eq!(result, "<?xml version=\"1.1\" encoding=\"UTF-8\"?><!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" SYSTEM \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\"><html></html>");
+    assert_eq!(result, "<?xml version=\"1.1\" encoding=\"UTF-8\"?><!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\"><html></html>");
 }
 
 #[test]
@@ -191,7 +195,7 @@ fn test_display_document_type() {
         .unwrap();
 
     let result = format!("{}", test_node);
-    assert_eq!(result, "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" SYSTEM \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">");
+    assert_eq!(result, "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">");
 }
 
 #[test]
@@ -211,10 +215,7 @@ fn test_display_document_fragment() {
     }
 
     let result = format!("{}", test_node);
-    assert_eq!(
-        result,
-        "<![CDATA[#document-fragment <one></one><two></two><three></three>]]>"
-    );
+    assert_eq!(result, "<one></one><two></two><three></three>");
 }
 #[test]
 fn test_display_entity() {
@@ -334,3 +335,61 @@ fn test_display_notation() {
         "<!NOTATION name PUBLIC \"foo-bar\" \"file-name.xml\">"
     );
 }
+
+#[test]
+fn test_display_escaping() {
+    let document_node = common::create_empty_rdf_document();
+    let document = as_document(&document_node).unwrap();
+
+    let test_node = document.create_text_node("a < b && c > d\r\n").unwrap();
+    let result = format!("{}", test_node);
+    assert_eq!(result, "a &lt; b &amp;&amp; c &gt; d&#xD;\n");
+
+    let mut test_node = document.create_attribute("test").unwrap();
+    let attribute = as_attribute_mut(&mut test_node).unwrap();
+    assert!(attribute.set_value("say \"<hi>\"\tAT&T").is_ok());
+    let result = format!("{}", test_node);
+    assert_eq!(result, "test=\"say &quot;&lt;hi&gt;&quot;&#x9;AT&amp;T\"");
+
+    let test_node = document.create_cdata_section("a]]>b").unwrap();
+    let result = format!("{}", test_node);
+    assert_eq!(result, "<![CDATA[a]]]]><![CDATA[>b]]>");
+
+    let test_node = document.create_comment("a < b & c").unwrap();
+    let result = format!("{}", test_node);
+    assert_eq!(result, "<!--a < b & c-->");
+}
+
+#[cfg(feature = "quick_parser")]
+#[test]
+fn test_display_round_trip() {
+    use xml_dom::parser::read_xml;
+
+    const XML: &str = "<doc a=\"1 &lt; 2 &quot;&amp;&quot;\">x &lt;&amp;&gt; y\
+        <![CDATA[<raw>]]]]><![CDATA[>]]><!--<c>--><?pi data?></doc>";
+    let document_node = read_xml(XML).unwrap();
+    let result = format!("{}", document_node);
+    assert_eq!(result, XML);
+    let reparsed = read_xml(&result).unwrap();
+    assert_eq!(format!("{}", reparsed), result);
+}
+
+#[cfg(feature = "quick_parser")]
+#[test]
+fn test_display_restricted_characters() {
+    use xml_dom::parser::read_xml;
+
+    const XML: &str = "<?xml version=\"1.1\"?><doc a=\"&#x1;&#x7F;\">&#x1F;\t&#x85;&#x9F;</doc>";
+    let document_node = read_xml(XML).unwrap();
+    let result = format!("{}", document_node);
+    assert_eq!(
+        result,
+        "<?xml version=\"1.1\"?><doc a=\"&#x1;&#x7F;\">&#x1F;\t&#x85;&#x9F;</doc>"
+    );
+    let reparsed = read_xml(&result).unwrap();
+    assert_eq!(format!("{}", reparsed), result);
+
+    const XML_10: &str = "<doc>\u{7F}&#x85;</doc>";
+    let document_node = read_xml(XML_10).unwrap();
+    assert_eq!(format!("{}", document_node), "<doc>\u{7F}\u{85}</doc>");
+}
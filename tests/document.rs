@@ -1,8 +1,10 @@
 use std::str::FromStr;
 use xml_dom::level2::convert::{
-    as_attribute, as_cdata_section, as_comment, as_document, as_document_fragment, as_document_mut,
-    as_element, as_entity_reference, as_processing_instruction, as_text,
+    as_attribute, as_cdata_section, as_character_data_mut, as_comment, as_document,
+    as_document_fragment, as_document_mut, as_element, as_entity_reference,
+    as_processing_instruction, as_text, as_text_mut,
 };
+use xml_dom::level2::ext::{DocumentDecl, XmlDecl, XmlVersion};
 use xml_dom::level2::{get_implementation, Error, Name};
 
 pub mod common;
@@ -104,7 +106,7 @@ fn test_create_entity_reference() {
 fn test_create_comment() {
     let document_node = common::create_empty_rdf_document();
     let document = as_document(&document_node).unwrap();
-    let node = document.create_comment(TEST_TEXT);
+    let node = document.create_comment(TEST_TEXT).unwrap();
     let comment = as_comment(&node).unwrap();
     assert!(comment.parent_node().is_none());
     assert!(comment.owner_document().is_some());
@@ -114,6 +116,28 @@ fn test_create_comment() {
     assert!(!comment.has_child_nodes());
 }
 
+#[test]
+fn test_create_comment_with_hyphens() {
+    let document_node = common::create_empty_rdf_document();
+    let document = as_document(&document_node).unwrap();
+    for data in ["a -- b", "a -", "-"] {
+        assert!(
+            matches!(document.create_comment(data), Err(Error::Syntax)),
+            "expecting an error for {:?}",
+            data
+        );
+    }
+
+    let mut node = document.create_comment("a - b").unwrap();
+    let comment = as_character_data_mut(&mut node).unwrap();
+    assert!(matches!(comment.append_data("-"), Err(Error::Syntax)));
+    assert!(matches!(comment.insert_data(2, "-"), Err(Error::Syntax)));
+    assert!(matches!(comment.set_data("--"), Err(Error::Syntax)));
+    assert_eq!(comment.data(), Some("a - b".to_string()));
+    assert!(comment.append_data(" c").is_ok());
+    assert_eq!(comment.data(), Some("a - b c".to_string()));
+}
+
 #[test]
 fn test_create_element() {
     let document_node = common::create_empty_rdf_document();
@@ -168,7 +192,7 @@ fn test_create_processing_instruction() {
 fn test_create_text_node() {
     let document_node = common::create_empty_rdf_document();
     let document = as_document(&document_node).unwrap();
-    let node = document.create_text_node(TEST_TEXT);
+    let node = document.create_text_node(TEST_TEXT).unwrap();
     let text = as_text(&node).unwrap();
     assert!(text.parent_node().is_none());
     assert!(text.owner_document().is_some());
@@ -319,3 +343,51 @@ fn test_replace_root() {
     let expected_name = Name::from_str("should_work").unwrap();
     assert_eq!(element.node_name(), expected_name);
 }
+
+#[test]
+fn test_xml_version_characters() {
+    let mut document_node = common::create_empty_rdf_document();
+    let document = as_document(&document_node).unwrap();
+    for data in ["\u{1}", "\u{FFFE}"] {
+        assert_eq!(
+            document.create_text_node(data).err(),
+            Some(Error::InvalidCharacter)
+        );
+        assert_eq!(
+            document.create_comment(data).err(),
+            Some(Error::InvalidCharacter)
+        );
+        assert_eq!(
+            document.create_cdata_section(data).err(),
+            Some(Error::InvalidCharacter)
+        );
+        assert_eq!(
+            document
+                .create_processing_instruction("pi", Some(data))
+                .err(),
+            Some(Error::InvalidCharacter)
+        );
+        assert_eq!(
+            document.create_attribute_with("a", data).err(),
+            Some(Error::InvalidCharacter)
+        );
+    }
+    let mut text_node = document.create_text_node(TEST_TEXT).unwrap();
+    let text = as_text_mut(&mut text_node).unwrap();
+    assert_eq!(text.set_data("\u{1}"), Err(Error::InvalidCharacter));
+    assert_eq!(text.append_data("\u{1}"), Err(Error::InvalidCharacter));
+    assert_eq!(text.insert_data(0, "\u{1}"), Err(Error::InvalidCharacter));
+    assert_eq!(text.data(), Some(TEST_TEXT.to_string()));
+
+    document_node
+        .set_xml_declaration(XmlDecl::new(XmlVersion::V11, None, None))
+        .unwrap();
+    let document = as_document(&document_node).unwrap();
+    assert!(document.create_text_node("\u{1}\u{7F}").is_ok());
+    assert!(document.create_comment("\u{1}").is_ok());
+    assert!(text.set_data("\u{1}").is_ok());
+    assert_eq!(
+        document.create_text_node("\u{0}").err(),
+        Some(Error::InvalidCharacter)
+    );
+}
@@ -19,6 +19,7 @@ fn test_is_child_allowed() {
                 NodeType::ProcessingInstruction,
                 NodeType::CData,
                 NodeType::EntityReference,
+                NodeType::DocumentFragment,
             ],
         ),
         (
@@ -36,6 +37,7 @@ fn test_is_child_allowed() {
                 NodeType::ProcessingInstruction,
                 NodeType::CData,
                 NodeType::EntityReference,
+                NodeType::DocumentFragment,
             ],
         ),
         (
@@ -47,6 +49,7 @@ fn test_is_child_allowed() {
                 NodeType::ProcessingInstruction,
                 NodeType::CData,
                 NodeType::EntityReference,
+                NodeType::DocumentFragment,
             ],
         ),
         (NodeType::ProcessingInstruction, vec![]),
@@ -61,6 +64,7 @@ fn test_is_child_allowed() {
                 NodeType::ProcessingInstruction,
                 NodeType::CData,
                 NodeType::EntityReference,
+                NodeType::DocumentFragment,
             ],
         ),
         (NodeType::Notation, vec![]),
@@ -99,7 +103,7 @@ fn test_insert_child_node() {
         assert!(new_node.parent_node().is_some());
         assert!(new_node.owner_document().is_some());
         compare_node_names(
-            &mut_root.child_nodes(),
+            mut_root.child_nodes(),
             &[
                 "child-1",
                 "child-2",
@@ -121,7 +125,7 @@ fn test_insert_child_node() {
         assert!(new_node.parent_node().is_some());
         assert!(new_node.owner_document().is_some());
         compare_node_names(
-            &mut_root.child_nodes(),
+            mut_root.child_nodes(),
             &[
                 "inserted-2",
                 "child-1",
@@ -143,7 +147,7 @@ fn test_insert_child_node() {
         assert!(new_node.parent_node().is_some());
         assert!(new_node.owner_document().is_some());
         compare_node_names(
-            &mut_root.child_nodes(),
+            mut_root.child_nodes(),
             &[
                 "inserted-2",
                 "child-1",
@@ -186,7 +190,7 @@ fn test_replace_child_node() {
         let result = mut_root.replace_child(new_child_node, mid_node.clone());
         assert!(result.is_ok());
         compare_node_names(
-            &mut_root.child_nodes(),
+            mut_root.child_nodes(),
             &["child-1", "child-2", "inserted-1", "child-4", "child-5"],
         );
     }
@@ -198,7 +202,7 @@ fn test_replace_child_node() {
         let result = mut_root.replace_child(new_child_node, first_node.clone());
         assert!(result.is_ok());
         compare_node_names(
-            &mut_root.child_nodes(),
+            mut_root.child_nodes(),
             &["inserted-2", "child-2", "inserted-1", "child-4", "child-5"],
         );
     }
@@ -210,7 +214,7 @@ fn test_replace_child_node() {
         let result = mut_root.replace_child(new_child_node, last_node.clone());
         assert!(result.is_ok());
         compare_node_names(
-            &mut_root.child_nodes(),
+            mut_root.child_nodes(),
             &[
                 "inserted-2",
                 "child-2",
@@ -249,7 +253,7 @@ fn test_remove_child_node() {
         let result = mut_root.remove_child(mid_node.clone());
         assert!(result.is_ok());
         compare_node_names(
-            &mut_root.child_nodes(),
+            mut_root.child_nodes(),
             &["child-1", "child-2", "child-4", "child-5"],
         );
     }
@@ -259,7 +263,7 @@ fn test_remove_child_node() {
         let first_node = child_nodes.first().unwrap();
         let result = mut_root.remove_child(first_node.clone());
         assert!(result.is_ok());
-        compare_node_names(&mut_root.child_nodes(), &["child-2", "child-4", "child-5"]);
+        compare_node_names(mut_root.child_nodes(), &["child-2", "child-4", "child-5"]);
     }
 
     {
@@ -267,7 +271,7 @@ fn test_remove_child_node() {
         let last_node = child_nodes.last().unwrap();
         let result = mut_root.remove_child(last_node.clone());
         assert!(result.is_ok());
-        compare_node_names(&mut_root.child_nodes(), &["child-2", "child-4"]);
+        compare_node_names(mut_root.child_nodes(), &["child-2", "child-4"]);
     }
 
     {
@@ -427,13 +431,13 @@ fn make_node(document: RefNode, node_type: NodeType, prefix: &str) -> RefNode {
     match node_type {
         NodeType::Element => document.create_element(&named("element")).unwrap(),
         NodeType::Attribute => document.create_attribute(&named("attribute")).unwrap(),
-        NodeType::Text => document.create_text_node(&named("text")),
+        NodeType::Text => document.create_text_node(&named("text")).unwrap(),
         NodeType::CData => document.create_cdata_section(&named("cdata")).unwrap(),
         NodeType::EntityReference => document.create_entity_reference(&named("text")).unwrap(),
         NodeType::ProcessingInstruction => document
             .create_processing_instruction(&named("pi"), None)
             .unwrap(),
-        NodeType::Comment => document.create_comment(&named("comment")),
+        NodeType::Comment => document.create_comment(&named("comment")).unwrap(),
         NodeType::Document => document,
         NodeType::DocumentType => {
             let implementation = get_implementation();
@@ -510,7 +514,7 @@ fn append_text_node(parent_node: &mut RefNode, content: &str) -> RefNode {
 
     let mut document_node = mut_parent.owner_document().unwrap();
     let mut_document = as_document_mut(&mut document_node).unwrap();
-    let new_text_node = mut_document.create_text_node(content);
+    let new_text_node = mut_document.create_text_node(content).unwrap();
 
     let result = mut_parent.append_child(new_text_node.clone());
     assert!(result.is_ok());
@@ -544,3 +548,73 @@ fn compare_node_names(nodes: impl AsRef<[RefNode]>, expected_names: &[&str]) {
     let expected_names: Vec<String> = expected_names.iter().map(|s| String::from(*s)).collect();
     assert_eq!(names, expected_names);
 }
+
+#[test]
+fn test_base_uri() {
+    use xml_dom::level2::ext::{DocumentDecl, XmlBase};
+
+    let mut document_node = get_implementation()
+        .create_document(None, Some("book"), None)
+        .unwrap();
+    assert_eq!(document_node.base_uri(), None);
+    document_node
+        .set_document_uri("http://example.com/docs/book.xml")
+        .unwrap();
+    assert_eq!(
+        document_node.base_uri(),
+        Some("http://example.com/docs/book.xml".to_string())
+    );
+
+    let document = as_document(&document_node).unwrap();
+    let mut root_node = document.document_element().unwrap();
+    assert_eq!(
+        root_node.base_uri(),
+        Some("http://example.com/docs/book.xml".to_string())
+    );
+
+    let mut chapter_node = root_node
+        .append_child(document.create_element("chapter").unwrap())
+        .unwrap();
+    as_element_mut(&mut chapter_node)
+        .unwrap()
+        .set_attribute("xml:base", "../chapters/one/")
+        .unwrap();
+    let text_node = chapter_node
+        .append_child(document.create_text_node("text").unwrap())
+        .unwrap();
+    assert_eq!(
+        chapter_node.base_uri(),
+        Some("http://example.com/chapters/one/".to_string())
+    );
+    assert_eq!(text_node.base_uri(), chapter_node.base_uri());
+    let attribute_node = as_element(&chapter_node)
+        .unwrap()
+        .get_attribute_node("xml:base")
+        .unwrap();
+    assert_eq!(attribute_node.base_uri(), chapter_node.base_uri());
+
+    let mut section_node = chapter_node
+        .clone()
+        .append_child(document.create_element("section").unwrap())
+        .unwrap();
+    as_element_mut(&mut section_node)
+        .unwrap()
+        .set_attribute("xml:base", "ftp://example.org/section.xml")
+        .unwrap();
+    assert_eq!(
+        section_node.base_uri(),
+        Some("ftp://example.org/section.xml".to_string())
+    );
+
+    let detached_node = document.create_comment("detached").unwrap();
+    assert_eq!(
+        detached_node.base_uri(),
+        Some("http://example.com/docs/book.xml".to_string())
+    );
+
+    document_node.unset_document_uri().unwrap();
+    assert_eq!(
+        chapter_node.base_uri(),
+        Some("../chapters/one/".to_string())
+    );
+}
@@ -1,4 +1,5 @@
 use xml_dom::level2::convert::{as_document, as_processing_instruction_mut};
+use xml_dom::level2::Error;
 
 pub mod common;
 
@@ -54,3 +55,24 @@ fn test_reserved_name() {
         .create_processing_instruction("xml-ok", Some("should-work"))
         .is_ok());
 }
+
+#[test]
+fn test_data_with_end_delimiter() {
+    let document_node = common::create_empty_rdf_document();
+    let document = as_document(&document_node).unwrap();
+
+    assert!(matches!(
+        document.create_processing_instruction("test", Some("a ?> b")),
+        Err(Error::Syntax)
+    ));
+
+    let mut node = document
+        .create_processing_instruction("test", Some("a ? > b"))
+        .unwrap();
+    let processing_instruction = as_processing_instruction_mut(&mut node).unwrap();
+    assert!(matches!(
+        processing_instruction.set_data("a?>"),
+        Err(Error::Syntax)
+    ));
+    assert_eq!(processing_instruction.data(), Some("a ? > b".to_string()));
+}
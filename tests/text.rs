@@ -44,7 +44,7 @@ fn test_text_substring() {
     let mut root_node = document.document_element().unwrap();
     let root_element = as_element_mut(&mut root_node).unwrap();
 
-    let text_node = document.create_text_node("Hello cruel world!");
+    let text_node = document.create_text_node("Hello cruel world!").unwrap();
     let text_node = root_element.append_child(text_node);
     assert!(text_node.is_ok());
 
@@ -68,7 +68,7 @@ fn test_text_insert() {
     let mut root_node = document.document_element().unwrap();
     let root_element = as_element_mut(&mut root_node).unwrap();
 
-    let text_node = document.create_text_node("Hello cruel world!");
+    let text_node = document.create_text_node("Hello cruel world!").unwrap();
     let text_node = root_element.append_child(text_node);
     assert!(text_node.is_ok());
 
@@ -102,7 +102,7 @@ fn test_text_replace() {
     let mut root_node = document.document_element().unwrap();
     let root_element = as_element_mut(&mut root_node).unwrap();
 
-    let text_node = document.create_text_node("Hello cruel world!");
+    let text_node = document.create_text_node("Hello cruel world!").unwrap();
     let text_node = root_element.append_child(text_node);
     assert!(text_node.is_ok());
 
@@ -139,7 +139,7 @@ fn test_text_delete() {
     let mut root_node = document.document_element().unwrap();
     let root_element = as_element_mut(&mut root_node).unwrap();
 
-    let text_node = document.create_text_node("Hello cruel world!");
+    let text_node = document.create_text_node("Hello cruel world!").unwrap();
     let text_node = root_element.append_child(text_node);
     assert!(text_node.is_ok());
 
@@ -209,7 +209,7 @@ fn test_text_split() {
     let root_element = as_element_mut(&mut root_node).unwrap();
 
     for content in ["onetwo", "threefour", "fivesix"] {
-        let text_node = document.create_text_node(content);
+        let text_node = document.create_text_node(content).unwrap();
         let _ignore = root_element.append_child(text_node);
     }
 
@@ -0,0 +1,413 @@
+use xml_dom::level2::convert::{as_document, as_element_mut};
+use xml_dom::level2::ext::convert::as_document_decl_mut;
+use xml_dom::level2::ext::{XmlDecl, XmlVersion};
+use xml_dom::level2::*;
+use xml_dom::writer::{
+    serialize_to, Declaration, EmptyElements, Encoding, Error, LineEnding, Quote, WriterOptions,
+    XmlWriter,
+};
+
+pub mod common;
+
+fn create_document() -> RefNode {
+    let mut document_node = get_implementation()
+        .create_document(None, Some("config"), None)
+        .unwrap();
+    let document = as_document(&document_node).unwrap();
+    let mut root_node = document.document_element().unwrap();
+    let root = as_element_mut(&mut root_node).unwrap();
+    root.set_attribute("name", "it's \"quoted\"").unwrap();
+    let mut child = document.create_element("empty").unwrap();
+    let _safe_to_ignore = root.append_child(child.clone()).unwrap();
+    let _safe_to_ignore = child
+        .append_child(document.create_comment("a\nb").unwrap())
+        .unwrap();
+    let _safe_to_ignore = root
+        .append_child(document.create_element("empty").unwrap())
+        .unwrap();
+    let _safe_to_ignore = root
+        .append_child(document.create_text_node("caf\u{E9} \u{20AC}5\n").unwrap())
+        .unwrap();
+    let document = as_document_decl_mut(&mut document_node).unwrap();
+    document
+        .set_xml_declaration(XmlDecl::new(
+            XmlVersion::V10,
+            Some("UTF-8".to_string()),
+            Some(true),
+        ))
+        .unwrap();
+    document_node
+}
+
+fn write(node: &RefNode, options: &WriterOptions) -> Vec<u8> {
+    let mut output: Vec<u8> = Vec::new();
+    serialize_to(node, &mut output, options).unwrap();
+    output
+}
+
+fn write_string(node: &RefNode, options: &WriterOptions) -> String {
+    String::from_utf8(write(node, options)).unwrap()
+}
+
+#[test]
+fn test_write_default() {
+    let document_node = create_document();
+    let result = write_string(&document_node, &WriterOptions::default());
+    assert_eq!(result, document_node.to_string());
+}
+
+#[test]
+fn test_write_declaration() {
+    let document_node = create_document();
+
+    common::sub_test("test_write_declaration", "never");
+    let result = write_string(
+        &document_node,
+        &WriterOptions::new().xml_declaration(Declaration::Never),
+    );
+    assert!(result.starts_with("<config "));
+
+    common::sub_test("test_write_declaration", "always");
+    let mut empty_node = get_implementation()
+        .create_document(None, Some("config"), None)
+        .unwrap();
+    let result = write_string(
+        &empty_node,
+        &WriterOptions::new().xml_declaration(Declaration::Always),
+    );
+    assert_eq!(result, "<?xml version=\"1.0\"?><config></config>");
+
+    common::sub_test("test_write_declaration", "target_encoding");
+    let result = write(
+        &empty_node,
+        &WriterOptions::new()
+            .xml_declaration(Declaration::Always)
+            .encoding(Encoding::UsAscii),
+    );
+    assert_eq!(
+        result,
+        b"<?xml version=\"1.0\" encoding=\"US-ASCII\"?><config></config>"
+    );
+
+    common::sub_test("test_write_declaration", "as_document");
+    let document = as_document_decl_mut(&mut empty_node).unwrap();
+    document
+        .set_xml_declaration(XmlDecl::new(XmlVersion::V11, None, None))
+        .unwrap();
+    let result = write_string(&empty_node, &WriterOptions::new());
+    assert_eq!(result, "<?xml version=\"1.1\"?><config></config>");
+}
+
+#[test]
+fn test_write_empty_elements_and_quotes() {
+    let document_node = create_document();
+    let root = as_document(&document_node)
+        .unwrap()
+        .document_element()
+        .unwrap();
+
+    let result = write_string(&root, &WriterOptions::new());
+    assert!(result.starts_with(
+        "<config name=\"it's &quot;quoted&quot;\"><empty><!--a\nb--></empty><empty></empty>"
+    ));
+
+    let result = write_string(
+        &root,
+        &WriterOptions::new()
+            .empty_elements(EmptyElements::SelfClosing)
+            .quote(Quote::Single),
+    );
+    assert!(result
+        .starts_with("<config name='it&apos;s \"quoted\"'><empty><!--a\nb--></empty><empty/>"));
+
+    let result = write_string(&document_node, &WriterOptions::new().quote(Quote::Single));
+    assert!(result.starts_with("<?xml version='1.0' encoding='UTF-8' standalone='yes'?>"));
+}
+
+#[test]
+fn test_write_line_endings() {
+    let document_node = create_document();
+    let result = write_string(
+        &document_node,
+        &WriterOptions::new().line_ending(LineEnding::CrLf),
+    );
+    assert!(result.contains("<!--a\r\nb-->"));
+    assert!(result.ends_with("5\r\n</config>"));
+
+    let result = write_string(
+        &document_node,
+        &WriterOptions::new().line_ending(LineEnding::Cr),
+    );
+    assert!(result.contains("<!--a\rb-->"));
+}
+
+#[test]
+fn test_write_encodings() {
+    let document_node = create_document();
+
+    common::sub_test("test_write_encodings", "iso_8859_1");
+    let result = write(
+        &document_node,
+        &WriterOptions::new().encoding(Encoding::Iso8859_1),
+    );
+    assert!(
+        result.starts_with(b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\" standalone=\"yes\"?>")
+    );
+    assert!(result.ends_with(b"caf\xE9 &#x20AC;5\n</config>"));
+
+    common::sub_test("test_write_encodings", "iso_8859_15");
+    let result = write(
+        &document_node,
+        &WriterOptions::new().encoding(Encoding::Iso8859_15),
+    );
+    assert!(result.ends_with(b"caf\xE9 \xA45\n</config>"));
+
+    common::sub_test("test_write_encodings", "utf_16");
+    let result = write(
+        &document_node,
+        &WriterOptions::new()
+            .xml_declaration(Declaration::Never)
+            .encoding(Encoding::Utf16Be),
+    );
+    assert_eq!(&result[..6], &[0xFE, 0xFF, 0x00, b'<', 0x00, b'c']);
+    let result = write(
+        &document_node,
+        &WriterOptions::new()
+            .xml_declaration(Declaration::Never)
+            .encoding(Encoding::Utf16Le),
+    );
+    assert_eq!(&result[..6], &[0xFF, 0xFE, b'<', 0x00, b'c', 0x00]);
+    let text: Vec<u16> = result[2..]
+        .chunks(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    let expected = write_string(
+        &document_node,
+        &WriterOptions::new().xml_declaration(Declaration::Never),
+    );
+    assert_eq!(String::from_utf16(&text).unwrap(), expected);
+}
+
+#[test]
+fn test_write_unencodable() {
+    let document_node = create_document();
+    let document = as_document(&document_node).unwrap();
+    let comment = document.create_comment("\u{20AC}").unwrap();
+    let mut root_node = document.document_element().unwrap();
+    let _safe_to_ignore = root_node.append_child(comment).unwrap();
+
+    let mut output: Vec<u8> = Vec::new();
+    let result = serialize_to(
+        &document_node,
+        &mut output,
+        &WriterOptions::new().encoding(Encoding::UsAscii),
+    );
+    assert!(matches!(
+        result,
+        Err(Error::Unencodable('\u{20AC}', Encoding::UsAscii))
+    ));
+
+    let result = serialize_to(
+        &document_node,
+        &mut output,
+        &WriterOptions::new().encoding(Encoding::Utf16Le),
+    );
+    assert!(result.is_ok());
+}
+
+///
+/// Counts the calls made to write, to check that output is buffered.
+///
+#[derive(Default)]
+struct CountingWriter {
+    bytes: Vec<u8>,
+    writes: usize,
+}
+
+impl std::io::Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writes += 1;
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_write_buffered() {
+    let document_node = create_document();
+    for encoding in [Encoding::Utf8, Encoding::Utf16Le] {
+        let options = WriterOptions::new().encoding(encoding);
+        let mut writer = XmlWriter::new(CountingWriter::default(), options.clone());
+        writer.write_node(&document_node).unwrap();
+        writer.flush().unwrap();
+        let output = writer.into_inner();
+        assert_eq!(output.writes, 1);
+        assert_eq!(output.bytes, write(&document_node, &options));
+    }
+}
+
+#[test]
+fn test_xml_writer() {
+    let document_node = get_implementation()
+        .create_document(None, Some("config"), None)
+        .unwrap();
+    let document = as_document(&document_node).unwrap();
+    let first = document.create_element("first").unwrap();
+    let second = document.create_element("second").unwrap();
+
+    let mut writer = XmlWriter::new(
+        Vec::new(),
+        WriterOptions::new()
+            .empty_elements(EmptyElements::SelfClosing)
+            .encoding(Encoding::Utf16Be),
+    );
+    writer.write_node(&first).unwrap();
+    writer.write_node(&second).unwrap();
+    writer.flush().unwrap();
+    let output = writer.into_inner();
+    assert_eq!(&output[..2], &[0xFE, 0xFF]);
+    let text: Vec<u16> = output[2..]
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    assert_eq!(String::from_utf16(&text).unwrap(), "<first/><second/>");
+}
+
+#[cfg(feature = "quick_parser")]
+#[test]
+fn test_write_round_trip() {
+    use xml_dom::parser::{read_bytes_with_options, ParserOptions};
+
+    let document_node = create_document();
+    let expected = as_document(&document_node)
+        .unwrap()
+        .document_element()
+        .unwrap()
+        .to_string();
+    for encoding in [
+        Encoding::Utf8,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+        Encoding::Iso8859_1,
+        Encoding::Iso8859_15,
+        Encoding::UsAscii,
+    ] {
+        let options = WriterOptions::new()
+            .encoding(encoding)
+            .empty_elements(EmptyElements::SelfClosing)
+            .quote(Quote::Single);
+        let output = write(&document_node, &options);
+        let parsed =
+            read_bytes_with_options(output, ParserOptions::new().trim_text(false)).unwrap();
+        let parsed = as_document(&parsed).unwrap().document_element().unwrap();
+        assert_eq!(parsed.to_string(), expected, "encoding {}", encoding);
+    }
+}
+
+#[cfg(feature = "quick_parser")]
+#[test]
+fn test_write_indented() {
+    use xml_dom::parser::read_xml;
+
+    let document_node = read_xml(concat!(
+        "<?xml version=\"1.0\"?><!--header--><config><server name=\"main\">",
+        "<port>80</port><empty/><!--note--><p>Some<b><i>bold</i></b>text</p>",
+        "<raw xml:space=\"preserve\"><a><b/></a><c xml:space=\"default\"><d/></c></raw>",
+        "</server></config><?pi data?>"
+    ))
+    .unwrap();
+
+    let result = write_string(
+        &document_node,
+        &WriterOptions::new()
+            .indent("  ")
+            .empty_elements(EmptyElements::SelfClosing),
+    );
+    assert_eq!(
+        result,
+        concat!(
+            "<?xml version=\"1.0\"?>\n",
+            "<!--header-->\n",
+            "<config>\n",
+            "  <server name=\"main\">\n",
+            "    <port>80</port>\n",
+            "    <empty/>\n",
+            "    <!--note-->\n",
+            "    <p>Some<b><i>bold</i></b>text</p>\n",
+            "    <raw xml:space=\"preserve\"><a><b/></a><c xml:space=\"default\">\n",
+            "        <d/>\n",
+            "      </c></raw>\n",
+            "  </server>\n",
+            "</config>\n",
+            "<?pi data?>\n",
+        )
+    );
+
+    common::sub_test("test_write_indented", "line_ending");
+    let result = write_string(
+        &document_node,
+        &WriterOptions::new()
+            .indent("\t")
+            .line_ending(LineEnding::CrLf),
+    );
+    assert!(result.starts_with("<?xml version=\"1.0\"?>\r\n<!--header-->\r\n<config>\r\n\t<server"));
+}
+
+#[cfg(feature = "quick_parser")]
+#[test]
+fn test_write_wrapped_attributes() {
+    use xml_dom::parser::read_xml;
+
+    let document_node =
+        read_xml("<config><item name=\"first\" value=\"1\"/><item name=\"second\"/></config>")
+            .unwrap();
+    let root = as_document(&document_node)
+        .unwrap()
+        .document_element()
+        .unwrap();
+
+    let options = WriterOptions::new()
+        .indent("  ")
+        .empty_elements(EmptyElements::SelfClosing);
+    let result = write_string(&root, &options.clone().max_line_width(40));
+    assert!(result.starts_with("<config>\n  <item "));
+    assert_eq!(result.lines().count(), 4);
+
+    let result = write_string(&root, &options.max_line_width(20));
+    assert_eq!(
+        result,
+        concat!(
+            "<config>\n",
+            "  <item\n",
+            "    name=\"first\"\n",
+            "    value=\"1\"/>\n",
+            "  <item name=\"second\"/>\n",
+            "</config>"
+        )
+    );
+}
+
+#[cfg(feature = "quick_parser")]
+#[test]
+fn test_write_attribute_order() {
+    use xml_dom::parser::read_xml;
+
+    let xml = "<item zone=\"1\" xmlns:p=\"urn:p\" p:id=\"2\" alpha=\"3\"></item>";
+    let document_node = read_xml(xml).unwrap();
+    let root = as_document(&document_node)
+        .unwrap()
+        .document_element()
+        .unwrap();
+    assert_eq!(root.to_string(), xml);
+    assert_eq!(write_string(&root, &WriterOptions::new()), xml);
+
+    let result = write_string(&root, &WriterOptions::new().sort_attributes(true));
+    assert_eq!(
+        result,
+        "<item alpha=\"3\" p:id=\"2\" xmlns:p=\"urn:p\" zone=\"1\"></item>"
+    );
+}